@@ -1,15 +1,42 @@
+mod list;
+
 use anyhow;
+use gloo_timers::callback::Timeout;
+use list::ListView;
 use wasm_bindgen::prelude::*;
 use yew::prelude::*;
 use yew::services::fetch::{FetchTask, Request, Response};
 
+/// Local storage key of the theme picked by the user.
+const DARK_MODE_KEY: &str = "goto_dark_mode";
+
+/// Mode is the view shown by the front-end.
+#[derive(Clone, Copy, PartialEq)]
+enum Mode {
+    /// A form creating short URLs.
+    Form,
+    /// The list of existing short URLs.
+    List,
+}
+
 struct Model {
     link: ComponentLink<Self>,
     message: Option<String>,
     error: Option<String>,
+    short_id: Option<String>,
     fetch_task: Option<FetchTask>,
     id: String,
     target: String,
+    target_valid: bool,
+    /// Value of the expiry time input, in local time, e.g. 2021-05-19T17:36.
+    expires_at: Option<String>,
+    copied: bool,
+    copied_timeout: Option<Timeout>,
+    /// Whether the QR code of the new short URL is shown. It is only fetched
+    /// once shown.
+    qr_visible: bool,
+    mode: Mode,
+    dark_mode: bool,
 }
 
 enum Msg {
@@ -17,9 +44,54 @@ enum Msg {
     ReceiveResponse(Result<String, anyhow::Error>),
     UpdateId(String),
     UpdateTarget(String),
+    ValidateTarget(String),
+    UpdateExpiry(String),
+    CopyToClipboard(String),
+    ResetCopied,
+    /// Show the QR code of a short URL, given its id.
+    ShowQrCode(String),
+    HideQrCode,
+    ViewMode(Mode),
+    ToggleDarkMode,
 }
 
 impl Model {
+    fn view_theme_toggle(&self) -> Html {
+        let (icon, title) = if self.dark_mode {
+            ("\u{2600}", "Switch to the light theme")
+        } else {
+            ("\u{263e}", "Switch to the dark theme")
+        };
+
+        html! {
+            <button class="theme-toggle" title=title onclick=self.link.callback(|_| Msg::ToggleDarkMode)>
+                { icon }
+            </button>
+        }
+    }
+
+    fn view_nav(&self) -> Html {
+        let link = |mode: Mode, label: &str| {
+            let onclick = self.link.callback(move |e: MouseEvent| {
+                e.prevent_default();
+                Msg::ViewMode(mode)
+            });
+            if self.mode == mode {
+                html! { <strong>{ label }</strong> }
+            } else {
+                html! { <a href="#" onclick=onclick>{ label }</a> }
+            }
+        };
+
+        html! {
+            <nav>
+                { link(Mode::Form, "Create") }
+                { " | " }
+                { link(Mode::List, "All short URLs") }
+            </nav>
+        }
+    }
+
     fn view_form(&self) -> Html {
         let oninput_id = self.link.callback(|e: InputData| Msg::UpdateId(e.value));
 
@@ -27,18 +99,52 @@ impl Model {
             .link
             .callback(|e: InputData| Msg::UpdateTarget(e.value));
 
+        let target = self.target.clone();
+        let onblur_target = self
+            .link
+            .callback(move |_: FocusEvent| Msg::ValidateTarget(target.clone()));
+
+        let oninput_expiry = self
+            .link
+            .callback(|e: InputData| Msg::UpdateExpiry(e.value));
+
+        let disabled = self.id.is_empty() || self.target.is_empty() || !self.target_valid;
+
         html! {
             <>
                 <h1>{ "Goto" }</h1>
                 <input type="text" placeholder="shortened_url" oninput=oninput_id value=self.id.clone() /><br />
-                <input type="text" placeholder="https://linkedin.com/in/tsauvajon/" oninput=oninput_target value=self.target.clone() />
-                <button onclick=self.link.callback(|_| Msg::Create())>
+                <input type="text" placeholder="https://linkedin.com/in/tsauvajon/" oninput=oninput_target onblur=onblur_target value=self.target.clone() />
+                { self.view_target_error() }
+                <label>
+                    { "Expires on (optional) " }
+                    <input type="datetime-local" oninput=oninput_expiry value=self.expires_at.clone().unwrap_or_default() />
+                </label>
+                <button disabled=disabled onclick=self.link.callback(|_| Msg::Create())>
                     { "Shorten URL" }
                 </button>
+                { self.view_expiry() }
             </>
         }
     }
 
+    fn view_expiry(&self) -> Html {
+        match &self.expires_at {
+            Some(expires_at) => html! {
+                <p>{ format!("This link will expire on {}", expires_at.replace('T', " ")) }</p>
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_target_error(&self) -> Html {
+        if self.target_valid {
+            html! {}
+        } else {
+            html! { <p>{ "\u{26a0} Not a valid URL" }</p> }
+        }
+    }
+
     fn view_message(&self) -> Html {
         match self.message.clone() {
             Some(msg) => html! { <p>{ msg }</p> },
@@ -46,6 +152,48 @@ impl Model {
         }
     }
 
+    fn view_qr_code(&self) -> Html {
+        match self.short_id.clone() {
+            Some(id) if self.qr_visible => {
+                let qr = format!("{}/{}/qr", api_base(), id);
+                html! {
+                    <>
+                        <img src=qr.clone() alt=format!("QR code for {}", id) />
+                        <a href=qr download="qr.png">{ "Download QR" }</a>
+                        <button onclick=self.link.callback(|_| Msg::HideQrCode)>
+                            { "Hide QR code" }
+                        </button>
+                    </>
+                }
+            }
+            Some(id) => html! {
+                <button onclick=self.link.callback(move |_| Msg::ShowQrCode(id.clone()))>
+                    { "Show QR code" }
+                </button>
+            },
+            None => html! {},
+        }
+    }
+
+    fn view_copy_button(&self) -> Html {
+        match (&self.message, &self.short_id) {
+            (Some(_), Some(id)) => {
+                let url = short_url(id);
+                let label = if self.copied {
+                    "Copied!"
+                } else {
+                    "Copy short URL"
+                };
+                html! {
+                    <button onclick=self.link.callback(move |_| Msg::CopyToClipboard(url.clone()))>
+                        { label }
+                    </button>
+                }
+            }
+            _ => html! {},
+        }
+    }
+
     fn view_error(&self) -> Html {
         match self.error.clone() {
             Some(err) => html! { <p>{ err }</p> },
@@ -69,17 +217,40 @@ impl Component for Model {
             link,
             message: None,
             error: None,
+            short_id: None,
             fetch_task: None,
             id: "".to_string(),
             target: "".to_string(),
+            target_valid: true,
+            expires_at: None,
+            copied: false,
+            copied_timeout: None,
+            qr_visible: false,
+            mode: Mode::Form,
+            dark_mode: load_dark_mode(),
         }
     }
 
     fn update(&mut self, msg: Self::Message) -> ShouldRender {
         match msg {
             Msg::Create() => {
-                let request = Request::post(format!("/{}", self.id))
-                    .body(Ok(self.target.clone()))
+                let form = form_urlencoded::Serializer::new(String::new())
+                    .append_pair("id", &self.id)
+                    .append_pair("target", &self.target)
+                    .finish();
+                let uri = match &self.expires_at {
+                    Some(expires_at) => format!(
+                        "{}/{}?expires_at={}",
+                        api_base(),
+                        self.id,
+                        form_urlencoded::byte_serialize(to_iso_8601(expires_at).as_bytes())
+                            .collect::<String>()
+                    ),
+                    None => format!("{}/{}", api_base(), self.id),
+                };
+                let request = Request::post(uri)
+                    .header("Content-Type", "application/x-www-form-urlencoded")
+                    .body(Ok(form))
                     .unwrap();
 
                 let callback =
@@ -92,6 +263,9 @@ impl Component for Model {
                 let task = yew::services::FetchService::fetch(request, callback)
                     .expect("failed to start request");
 
+                self.short_id = None;
+                self.copied = false;
+                self.qr_visible = false;
                 self.fetch_task = Some(task);
                 true
             }
@@ -99,6 +273,7 @@ impl Component for Model {
             Msg::ReceiveResponse(response) => {
                 match response {
                     Ok(msg) => {
+                        self.short_id = parse_short_id(&msg);
                         self.message = Some(msg);
                     }
                     Err(error) => self.error = Some(error.to_string()),
@@ -115,9 +290,63 @@ impl Component for Model {
             }
 
             Msg::UpdateTarget(target) => {
+                // once the target was found invalid, tell as soon as it's fixed
+                if !self.target_valid {
+                    self.target_valid = is_valid_url(&target);
+                }
                 self.target = target;
                 true
             }
+
+            Msg::UpdateExpiry(expires_at) => {
+                self.expires_at = Some(expires_at).filter(|expires_at| !expires_at.is_empty());
+                true
+            }
+
+            Msg::ValidateTarget(target) => {
+                self.target_valid = target.is_empty() || is_valid_url(&target);
+                true
+            }
+
+            Msg::CopyToClipboard(url) => {
+                copy_to_clipboard(&url);
+                self.copied = true;
+
+                // replacing the previous timeout cancels it
+                let link = self.link.clone();
+                self.copied_timeout = Some(Timeout::new(2_000, move || {
+                    link.send_message(Msg::ResetCopied)
+                }));
+                true
+            }
+
+            Msg::ResetCopied => {
+                self.copied = false;
+                self.copied_timeout = None;
+                true
+            }
+
+            Msg::ShowQrCode(id) => {
+                // the short URL may have changed since the button was shown
+                self.qr_visible = self.short_id.as_deref() == Some(id.as_str());
+                true
+            }
+
+            Msg::HideQrCode => {
+                self.qr_visible = false;
+                true
+            }
+
+            Msg::ViewMode(mode) => {
+                self.mode = mode;
+                true
+            }
+
+            Msg::ToggleDarkMode => {
+                self.dark_mode = !self.dark_mode;
+                store_dark_mode(self.dark_mode);
+                true
+            }
         }
     }
 
@@ -129,10 +358,26 @@ impl Component for Model {
     }
 
     fn view(&self) -> Html {
+        let class = if self.dark_mode { "dark" } else { "" };
+
+        if self.mode == Mode::List {
+            return html! {
+                <div class=class>
+                    { self.view_theme_toggle() }
+                    { self.view_nav() }
+                    <ListView />
+                </div>
+            };
+        }
+
         html! {
-            <div>
+            <div class=class>
+                { self.view_theme_toggle() }
+                { self.view_nav() }
                 { self.view_form() }
                 { self.view_message() }
+                { self.view_copy_button() }
+                { self.view_qr_code() }
                 { self.view_error() }
                 { self.view_fetching_task() }
             </div>
@@ -144,6 +389,82 @@ impl Component for Model {
     fn destroy(&mut self) {}
 }
 
+/// parse_short_id extracts the short URL ID from the API response to a
+/// successful creation, e.g. `/hello now redirects to https://world`.
+fn parse_short_id(msg: &str) -> Option<String> {
+    msg.split_whitespace()
+        .next()
+        .and_then(|path| path.strip_prefix('/'))
+        .filter(|id| !id.is_empty())
+        .map(|id| id.to_string())
+}
+
+/// load_dark_mode reads whether the user picked the dark theme. It defaults
+/// to the light theme when local storage is unavailable.
+fn load_dark_mode() -> bool {
+    web_sys::window()
+        .and_then(|window| window.local_storage().ok().flatten())
+        .and_then(|storage| storage.get_item(DARK_MODE_KEY).ok().flatten())
+        .map_or(false, |value| value == "true")
+}
+
+/// store_dark_mode remembers the theme picked by the user, if local storage
+/// is available.
+fn store_dark_mode(dark_mode: bool) {
+    if let Some(storage) =
+        web_sys::window().and_then(|window| window.local_storage().ok().flatten())
+    {
+        let _ = storage.set_item(DARK_MODE_KEY, &dark_mode.to_string());
+    }
+}
+
+/// to_iso_8601 converts the local time of a datetime-local input to an ISO
+/// 8601 timestamp in UTC, e.g. 2021-05-19T15:36:00.000Z.
+fn to_iso_8601(local: &str) -> String {
+    js_sys::Date::new(&JsValue::from_str(local))
+        .to_iso_string()
+        .into()
+}
+
+/// is_valid_url tells whether the browser can parse `input` as an absolute
+/// URL. The API validates targets again when creating short URLs.
+fn is_valid_url(input: &str) -> bool {
+    web_sys::Url::new(input).is_ok()
+}
+
+/// short_url returns the full URL of the short URL `id`, on the host serving
+/// the front-end.
+fn short_url(id: &str) -> String {
+    let origin = web_sys::window()
+        .and_then(|window| window.location().origin().ok())
+        .unwrap_or_default();
+    format!("{}{}/{}", origin, api_base(), id)
+}
+
+/// api_base returns the path the API is served under, e.g. `/goto`, from the
+/// `<meta name="api-base">` tag added by the API to index.html. It is empty
+/// when the API is served at the root.
+pub fn api_base() -> String {
+    web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| {
+            document
+                .query_selector(r#"meta[name="api-base"]"#)
+                .ok()
+                .flatten()
+        })
+        .and_then(|meta| meta.get_attribute("content"))
+        .unwrap_or_default()
+}
+
+/// copy_to_clipboard writes `text` to the clipboard of the user. It doesn't
+/// wait for the browser to acknowledge it.
+fn copy_to_clipboard(text: &str) {
+    if let Some(window) = web_sys::window() {
+        let _ = window.navigator().clipboard().write_text(text);
+    }
+}
+
 #[wasm_bindgen(start)]
 pub fn run_app() {
     App::<Model>::new().mount_to_body();
@@ -0,0 +1,408 @@
+use crate::api_base;
+use serde::Deserialize;
+use std::collections::HashMap;
+use yew::format::{Json, Nothing};
+use yew::prelude::*;
+use yew::services::fetch::{FetchService, FetchTask, Request, Response};
+
+/// Number of short URLs shown on each page.
+const PAGE_SIZE: usize = 20;
+
+/// A short URL, as listed by the API.
+#[derive(Deserialize, Clone, Debug)]
+pub struct UrlEntry {
+    id: String,
+    target: String,
+    #[serde(default)]
+    clicks: u64,
+}
+
+/// A page of short URLs, as listed by the API.
+#[derive(Deserialize, Debug)]
+pub struct UrlPage {
+    items: Vec<UrlEntry>,
+    next_cursor: Option<String>,
+    total: usize,
+}
+
+/// ListView shows a table of the short URLs, which can be edited or
+/// deleted.
+pub struct ListView {
+    link: ComponentLink<Self>,
+    entries: Vec<UrlEntry>,
+    total: usize,
+    /// Cursors of the pages up to the current one, the first page has none.
+    cursors: Vec<Option<String>>,
+    next_cursor: Option<String>,
+    error: Option<String>,
+    fetch_task: Option<FetchTask>,
+    /// Deletions in progress, by short URL.
+    delete_tasks: HashMap<String, FetchTask>,
+    /// Why deleting short URLs failed, by short URL.
+    delete_errors: HashMap<String, String>,
+    /// Short URL whose target is being edited, if any.
+    editing_id: Option<String>,
+    /// Target typed in the edited row.
+    editing_value: String,
+    edit_task: Option<FetchTask>,
+    edit_error: Option<String>,
+}
+
+pub enum Msg {
+    ReceivePage(Result<UrlPage, anyhow::Error>),
+    NextPage,
+    PreviousPage,
+    StartEdit(String),
+    UpdateEditValue(String),
+    SaveEdit(String),
+    EditResponse(String, Result<String, String>),
+    Delete(String),
+    ConfirmDelete(String),
+    DeleteResponse(String, Result<(), String>),
+}
+
+impl ListView {
+    /// load fetches the page of the last cursor.
+    fn load(&mut self) {
+        let url = match self.cursors.last().cloned().flatten() {
+            Some(cursor) => format!(
+                "{}/api/urls?limit={}&cursor={}",
+                api_base(),
+                PAGE_SIZE,
+                cursor
+            ),
+            None => format!("{}/api/urls?limit={}", api_base(), PAGE_SIZE),
+        };
+        let request = Request::get(url).body(Nothing).unwrap();
+
+        let callback =
+            self.link
+                .callback(|response: Response<Json<Result<UrlPage, anyhow::Error>>>| {
+                    let Json(data) = response.into_body();
+                    Msg::ReceivePage(data)
+                });
+
+        self.fetch(request, callback);
+    }
+
+    fn fetch<IN, OUT: 'static>(&mut self, request: Request<IN>, callback: Callback<Response<OUT>>)
+    where
+        IN: Into<yew::format::Text>,
+        OUT: From<yew::format::Text>,
+    {
+        match FetchService::fetch(request, callback) {
+            Ok(task) => self.fetch_task = Some(task),
+            Err(err) => self.error = Some(err.to_string()),
+        }
+    }
+
+    fn view_row(&self, entry: &UrlEntry) -> Html {
+        let delete_id = entry.id.clone();
+
+        html! {
+            <tr>
+                <td><a href=format!("{}/{}", api_base(), entry.id)>{ format!("/{}", entry.id) }</a></td>
+                { self.view_target(entry) }
+                <td>{ entry.clicks }</td>
+                <td>
+                    { self.view_edit_button(entry) }
+                    <button
+                        disabled=self.delete_tasks.contains_key(&entry.id)
+                        onclick=self.link.callback(move |_| Msg::Delete(delete_id.clone()))>
+                        { "Delete" }
+                    </button>
+                    { self.view_delete_error(&entry.id) }
+                </td>
+            </tr>
+        }
+    }
+
+    fn view_target(&self, entry: &UrlEntry) -> Html {
+        if self.editing_id.as_deref() != Some(entry.id.as_str()) {
+            return html! { <td>{ &entry.target }</td> };
+        }
+
+        let oninput = self
+            .link
+            .callback(|e: InputData| Msg::UpdateEditValue(e.value));
+
+        html! {
+            <td>
+                <input type="url" oninput=oninput value=self.editing_value.clone() />
+                { self.view_edit_error() }
+            </td>
+        }
+    }
+
+    fn view_edit_button(&self, entry: &UrlEntry) -> Html {
+        let id = entry.id.clone();
+
+        if self.editing_id.as_deref() == Some(entry.id.as_str()) {
+            html! {
+                <button
+                    disabled=self.edit_task.is_some() || self.editing_value.is_empty()
+                    onclick=self.link.callback(move |_| Msg::SaveEdit(id.clone()))>
+                    { "Save" }
+                </button>
+            }
+        } else {
+            html! {
+                <button onclick=self.link.callback(move |_| Msg::StartEdit(id.clone()))>
+                    { "Edit" }
+                </button>
+            }
+        }
+    }
+
+    fn view_edit_error(&self) -> Html {
+        match &self.edit_error {
+            Some(err) => html! { <p>{ err }</p> },
+            None => html! {},
+        }
+    }
+
+    fn view_delete_error(&self, id: &str) -> Html {
+        match self.delete_errors.get(id) {
+            Some(err) => html! { <p>{ err }</p> },
+            None => html! {},
+        }
+    }
+
+    fn view_pagination(&self) -> Html {
+        if self.total <= PAGE_SIZE {
+            return html! {};
+        }
+
+        html! {
+            <p>
+                <button
+                    disabled=self.cursors.len() <= 1
+                    onclick=self.link.callback(|_| Msg::PreviousPage)>
+                    { "Previous" }
+                </button>
+                { format!("Page {} of {}", self.cursors.len(), (self.total + PAGE_SIZE - 1) / PAGE_SIZE) }
+                <button
+                    disabled=self.next_cursor.is_none()
+                    onclick=self.link.callback(|_| Msg::NextPage)>
+                    { "Next" }
+                </button>
+            </p>
+        }
+    }
+
+    fn view_error(&self) -> Html {
+        match self.error.clone() {
+            Some(err) => html! { <p>{ err }</p> },
+            None => html! {},
+        }
+    }
+}
+
+impl Component for ListView {
+    type Message = Msg;
+    type Properties = ();
+
+    fn create(_: Self::Properties, link: ComponentLink<Self>) -> Self {
+        let mut list = Self {
+            link,
+            entries: vec![],
+            total: 0,
+            cursors: vec![None],
+            next_cursor: None,
+            error: None,
+            fetch_task: None,
+            delete_tasks: HashMap::new(),
+            delete_errors: HashMap::new(),
+            editing_id: None,
+            editing_value: String::new(),
+            edit_task: None,
+            edit_error: None,
+        };
+        list.load();
+        list
+    }
+
+    fn update(&mut self, msg: Self::Message) -> ShouldRender {
+        match msg {
+            Msg::ReceivePage(page) => {
+                match page {
+                    Ok(page) => {
+                        self.entries = page.items;
+                        self.next_cursor = page.next_cursor;
+                        self.total = page.total;
+                        self.error = None;
+                    }
+                    Err(err) => self.error = Some(err.to_string()),
+                }
+                self.fetch_task = None;
+                true
+            }
+
+            Msg::NextPage => {
+                if let Some(cursor) = self.next_cursor.take() {
+                    self.cursors.push(Some(cursor));
+                    self.load();
+                }
+                false
+            }
+
+            Msg::PreviousPage => {
+                if self.cursors.len() > 1 {
+                    self.cursors.pop();
+                    self.load();
+                }
+                false
+            }
+
+            Msg::StartEdit(id) => {
+                self.editing_value = self
+                    .entries
+                    .iter()
+                    .find(|entry| entry.id == id)
+                    .map(|entry| entry.target.clone())
+                    .unwrap_or_default();
+                self.editing_id = Some(id);
+                self.edit_task = None;
+                self.edit_error = None;
+                true
+            }
+
+            Msg::UpdateEditValue(value) => {
+                self.editing_value = value;
+                true
+            }
+
+            Msg::SaveEdit(id) => {
+                let target = self.editing_value.clone();
+                let request = Request::patch(format!("{}/{}", api_base(), id))
+                    .body(Ok(target.clone()))
+                    .unwrap();
+
+                let callback =
+                    self.link
+                        .callback(move |response: Response<Result<String, anyhow::Error>>| {
+                            let (meta, body) = response.into_parts();
+                            // the API explains in the body why the request failed
+                            let res = match body {
+                                Ok(_) if meta.status.is_success() => Ok(target.clone()),
+                                Ok(body) => Err(body),
+                                Err(err) => Err(err.to_string()),
+                            };
+                            Msg::EditResponse(id.clone(), res)
+                        });
+
+                match FetchService::fetch(request, callback) {
+                    Ok(task) => {
+                        self.edit_task = Some(task);
+                        self.edit_error = None;
+                    }
+                    Err(err) => self.edit_error = Some(err.to_string()),
+                }
+                true
+            }
+
+            Msg::EditResponse(id, res) => {
+                self.edit_task = None;
+                match res {
+                    Ok(target) => {
+                        if let Some(entry) = self.entries.iter_mut().find(|entry| entry.id == id) {
+                            entry.target = target;
+                        }
+                        if self.editing_id.as_deref() == Some(id.as_str()) {
+                            self.editing_id = None;
+                        }
+                    }
+                    // keep the input, so the target can be fixed and saved again
+                    Err(err) => self.edit_error = Some(err),
+                }
+                true
+            }
+
+            Msg::Delete(id) => {
+                let confirmed = web_sys::window()
+                    .and_then(|window| {
+                        window
+                            .confirm_with_message(&format!("Delete /{}?", id))
+                            .ok()
+                    })
+                    .unwrap_or(false);
+
+                if confirmed {
+                    self.link.send_message(Msg::ConfirmDelete(id));
+                }
+                false
+            }
+
+            Msg::ConfirmDelete(id) => {
+                let request = Request::delete(format!("{}/{}", api_base(), id))
+                    .body(Nothing)
+                    .unwrap();
+
+                let deleted = id.clone();
+                let callback =
+                    self.link
+                        .callback(move |response: Response<Result<String, anyhow::Error>>| {
+                            let (meta, body) = response.into_parts();
+                            let res = match body {
+                                Ok(_) if meta.status.is_success() => Ok(()),
+                                Ok(body) => Err(body),
+                                Err(err) => Err(err.to_string()),
+                            };
+                            Msg::DeleteResponse(deleted.clone(), res)
+                        });
+
+                match FetchService::fetch(request, callback) {
+                    Ok(task) => {
+                        self.delete_errors.remove(&id);
+                        self.delete_tasks.insert(id, task);
+                    }
+                    Err(err) => {
+                        self.delete_errors.insert(id, err.to_string());
+                    }
+                }
+                true
+            }
+
+            Msg::DeleteResponse(id, res) => {
+                self.delete_tasks.remove(&id);
+                match res {
+                    // the rest of the page is still accurate
+                    Ok(()) => {
+                        self.entries.retain(|entry| entry.id != id);
+                        self.total = self.total.saturating_sub(1);
+                    }
+                    Err(err) => {
+                        self.delete_errors.insert(id, err);
+                    }
+                }
+                true
+            }
+        }
+    }
+
+    fn change(&mut self, _props: Self::Properties) -> ShouldRender {
+        false
+    }
+
+    fn view(&self) -> Html {
+        html! {
+            <>
+                <table>
+                    <thead>
+                        <tr>
+                            <th>{ "Short URL" }</th>
+                            <th>{ "Target" }</th>
+                            <th>{ "Clicks" }</th>
+                            <th>{ "Actions" }</th>
+                        </tr>
+                    </thead>
+                    <tbody>
+                        { for self.entries.iter().map(|entry| self.view_row(entry)) }
+                    </tbody>
+                </table>
+                { self.view_pagination() }
+                { self.view_error() }
+            </>
+        }
+    }
+}
@@ -0,0 +1,4 @@
+// loaded from a file rather than inline, which the Content-Security-Policy
+// of goto forbids
+import init from "./wasm.js"
+init()
@@ -1,54 +1,447 @@
+/*!
+goto is a CLI tool to create and browse shortened URLs.
+
+## Shell completions
+
+Completion scripts for bash, zsh, fish, powershell and elvish are generated
+with `goto completions <shell>`:
+
+```sh
+# bash
+goto completions bash > /etc/bash_completion.d/goto
+
+# zsh, make sure the directory is in your $fpath
+goto completions zsh > ~/.zsh/completions/_goto
+
+# fish
+goto completions fish > ~/.config/fish/completions/goto.fish
+```
+*/
+
 use async_trait::async_trait;
-use home;
-use hyper::{Client as HyperClient, Uri};
-use serde;
+use clap::parser::ValueSource;
+use clap::{ArgMatches, CommandFactory, FromArgMatches, Parser, Subcommand, ValueEnum};
+use clap_complete::Shell;
+use indexmap::IndexMap;
 use std::fmt::Debug;
 use std::fs::OpenOptions;
-use std::path::PathBuf;
-use structopt::StructOpt;
-use webbrowser;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 const DEFAULT_API_URL: &str = "http://127.0.0.1:8080";
-
-#[derive(StructOpt, Clone)]
-#[structopt(about = "Create shortened URLs")]
+const DEFAULT_CHECK_TIMEOUT_SECS: u64 = 10;
+const DEFAULT_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_MAX_RETRIES: u32 = 3;
+const DEFAULT_RETRY_BASE_DELAY_MS: u64 = 100;
+const DOCTOR_TIMEOUT_SECS: u64 = 5;
+const DEFAULT_PING_COUNT: u64 = 4;
+const DEFAULT_PING_INTERVAL_MS: u64 = 1000;
+/// Header in which the API lists its optional endpoints.
+const CAPABILITIES_HEADER: &str = "x-goto-capabilities";
+/// User of the admin routes of the API, which need a password when the API
+/// runs with --admin-password.
+const ADMIN_USER: &str = "admin";
+
+#[derive(Parser, Clone)]
+#[command(version, about = "Create shortened URLs")]
 struct Args {
-    #[structopt(help = "Shortened URL")]
-    shorturl: String,
-    #[structopt(help = "URL to shorten")]
+    #[arg(help = "Shortened URL")]
+    shorturl: Option<String>,
+    #[arg(help = "URL to shorten")]
     target: Option<String>,
 
-    #[structopt(long = "api", help = "Base URL of the Goto API")]
+    #[arg(long = "api", env = "GOTO_API_URL", help = "Base URL of the Goto API")]
     api_url: Option<String>,
 
-    #[structopt(short = "s", long = "silent", help = "Don't print redirections")]
+    #[arg(
+        long = "config",
+        help = "Config file, in TOML if it ends with .toml or YAML otherwise [default: ~/.goto/config.yml]"
+    )]
+    config: Option<PathBuf>,
+
+    #[arg(short = 's', long = "silent", help = "Don't print redirections")]
     silent: bool,
 
-    #[structopt(short = "n", long = "no-open-browser", help = "Don't open the browser")]
+    #[arg(short = 'n', long = "no-open-browser", help = "Don't open the browser")]
     no_browser: bool,
+
+    #[arg(
+        long = "timeout",
+        env = "GOTO_TIMEOUT",
+        help = "Maximum number of seconds to wait for the API [default: 30]"
+    )]
+    timeout: Option<u64>,
+
+    #[arg(
+        long = "max-retries",
+        help = "Number of times to retry requests that failed to connect or got a 503 [default: 3]"
+    )]
+    max_retries: Option<u32>,
+
+    #[arg(
+        long = "retry-base-delay-ms",
+        help = "Milliseconds to wait before the first retry, doubled after each retry [default: 100]"
+    )]
+    retry_base_delay_ms: Option<u64>,
+
+    #[arg(
+        long = "admin-password",
+        env = "GOTO_ADMIN_PASSWORD",
+        help = "Password of the admin user of the API, to delete and import shortened URLs"
+    )]
+    admin_password: Option<String>,
+
+    #[arg(
+        long = "max-clicks",
+        value_parser = clap::value_parser!(u64).range(1..),
+        help = "Delete the shortened URL after it was used this many times"
+    )]
+    max_clicks: Option<u64>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum Command {
+    #[command(about = "Delete a shortened URL")]
+    Delete {
+        #[arg(help = "Shortened URL")]
+        shorturl: String,
+    },
+
+    #[command(about = "List all the shortened URLs")]
+    List {
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_enum,
+            default_value_t = OutputFormat::Table,
+            help = "Output format"
+        )]
+        output: OutputFormat,
+    },
+
+    #[command(about = "Rename a shortened URL, keeping its target")]
+    Rename {
+        #[arg(help = "Current shortened URL")]
+        old: String,
+        #[arg(help = "New shortened URL")]
+        new: String,
+
+        #[arg(
+            short = 'f',
+            long = "force",
+            help = "Overwrite the new shortened URL if it already exists"
+        )]
+        force: bool,
+    },
+
+    #[command(about = "Check that the targets of shortened URLs are still reachable")]
+    Check {
+        #[arg(required = true, help = "Shortened URLs")]
+        shorturls: Vec<String>,
+
+        #[arg(
+            long = "timeout",
+            default_value_t = DEFAULT_CHECK_TIMEOUT_SECS,
+            help = "Maximum number of seconds to wait for each target"
+        )]
+        timeout: u64,
+    },
+
+    #[command(about = "Check that the targets of all the shortened URLs are still reachable")]
+    Verify {
+        #[arg(long = "all", required = true, help = "Verify every shortened URL")]
+        all: bool,
+
+        #[arg(
+            short = 'c',
+            long = "concurrency",
+            default_value_t = 10,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Maximum number of targets checked in parallel"
+        )]
+        concurrency: u64,
+
+        #[arg(
+            long = "timeout",
+            default_value_t = DEFAULT_CHECK_TIMEOUT_SECS,
+            help = "Maximum number of seconds to wait for each target"
+        )]
+        timeout: u64,
+
+        #[arg(long = "output-file", help = "Write the broken links to a JSON file")]
+        output_file: Option<PathBuf>,
+    },
+
+    #[command(about = "Import shortened URLs from a CSV or JSON file")]
+    Import {
+        #[arg(help = "CSV file with id,target columns, or JSON list of {id, target}")]
+        file: PathBuf,
+
+        #[arg(long = "dry-run", help = "Validate the file without importing it")]
+        dry_run: bool,
+    },
+
+    #[command(about = "Run the create, update, delete or check commands read from stdin")]
+    Batch,
+
+    #[command(about = "Export all the shortened URLs")]
+    Export {
+        #[arg(
+            short = 'o',
+            long = "output",
+            help = "File to write to, instead of stdout"
+        )]
+        output: Option<PathBuf>,
+
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_enum,
+            default_value_t = ExportFormat::Yaml,
+            help = "Export format, yaml can be used as the database of the API"
+        )]
+        format: ExportFormat,
+    },
+
+    #[command(about = "Show how many times a shortened URL was used")]
+    Stats {
+        #[arg(help = "Shortened URL")]
+        shorturl: String,
+
+        #[arg(
+            short = 'o',
+            long = "output",
+            value_enum,
+            default_value_t = OutputFormat::Table,
+            help = "Output format"
+        )]
+        output: OutputFormat,
+    },
+
+    #[command(about = "Diagnose the configuration and the connection to the API")]
+    Doctor,
+
+    #[command(about = "Measure how long the API takes to respond")]
+    Ping {
+        #[arg(
+            short = 'c',
+            long = "count",
+            default_value_t = DEFAULT_PING_COUNT,
+            value_parser = clap::value_parser!(u64).range(1..),
+            help = "Number of requests to send"
+        )]
+        count: u64,
+
+        #[arg(
+            short = 'i',
+            long = "interval",
+            default_value_t = DEFAULT_PING_INTERVAL_MS,
+            help = "Milliseconds to wait between requests"
+        )]
+        interval: u64,
+    },
+
+    #[command(about = "Read or change a setting of the config file")]
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+
+    #[command(about = "Print the completion script for a shell")]
+    Completions {
+        #[arg(value_enum, help = "Shell to generate completions for")]
+        shell: Shell,
+    },
+}
+
+#[derive(Subcommand, Clone, Debug)]
+enum ConfigAction {
+    #[command(about = "Print a setting")]
+    Get {
+        #[arg(help = "Setting, e.g. api_url")]
+        key: String,
+    },
+
+    #[command(about = "Change a setting")]
+    Set {
+        #[arg(help = "Setting, e.g. api_url")]
+        key: String,
+        #[arg(help = "New value")]
+        value: String,
+    },
+
+    #[command(about = "Print the settings in use, and where they come from")]
+    Show {
+        #[arg(
+            short = 'f',
+            long = "format",
+            value_enum,
+            default_value_t = ShowFormat::Table,
+            help = "Output format"
+        )]
+        format: ShowFormat,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ShowFormat {
+    Table,
+    Json,
+    Yaml,
+}
+
+#[derive(ValueEnum, Clone, Debug, PartialEq)]
+enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum ExportFormat {
+    Csv,
+    Json,
+    Yaml,
+}
+
+/// A shortened URL, as listed by the API.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+struct UrlEntry {
+    id: String,
+    target: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    clicks: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<String>,
+}
+
+/// A page of shortened URLs, as listed by the API.
+#[derive(serde::Deserialize, Debug)]
+struct UrlPage {
+    items: Vec<UrlEntry>,
+    next_cursor: Option<String>,
+}
+
+/// A shortened URL to create with the JSON endpoint of the API.
+#[derive(serde::Serialize, Debug)]
+struct ShortenRequest {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<String>,
+    target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_clicks: Option<u64>,
+}
+
+/// A shortened URL, as created by the JSON endpoint of the API.
+#[derive(serde::Deserialize, Debug)]
+struct ShortenResponse {
+    id: String,
+    short_url: String,
+    target: String,
+}
+
+/// Usage statistics of a shortened URL, as returned by the API.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+struct UrlStats {
+    target: String,
+    clicks: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    created: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    last_accessed: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires: Option<String>,
+}
+
+/// The health of the API, as returned by its health endpoint.
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct Health {
+    version: String,
+    entries: usize,
 }
 
 #[derive(Debug, PartialEq)]
 enum GoToError {
     NoRedirection,
+    Timeout,
     CliError(String),
     ApiError(String),
+    /// The API answered with a client or server error: statuses from 500
+    /// are the API's fault, the others the CLI's.
+    Http {
+        status: u16,
+        body: String,
+    },
 }
 
-impl From<actix_web::http::uri::InvalidUri> for GoToError {
-    fn from(error: actix_web::http::uri::InvalidUri) -> Self {
-        GoToError::CliError(error.to_string())
+impl std::fmt::Display for GoToError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GoToError::NoRedirection => write!(f, "no redirection found"),
+            GoToError::Timeout => write!(f, "the API took too long to respond"),
+            GoToError::CliError(err) => write!(f, "CLI error: {}", err),
+            GoToError::ApiError(err) => write!(f, "API error: {}", err),
+            GoToError::Http { status, body } => write!(f, "HTTP {}: {}", status, body),
+        }
+    }
+}
+
+impl std::error::Error for GoToError {}
+
+#[test]
+fn test_display_error() {
+    assert_eq!("no redirection found", GoToError::NoRedirection.to_string());
+    assert_eq!(
+        "the API took too long to respond",
+        GoToError::Timeout.to_string()
+    );
+    assert_eq!(
+        "CLI error: not found",
+        GoToError::CliError("not found".to_string()).to_string()
+    );
+    assert_eq!(
+        "API error: woops",
+        GoToError::ApiError("woops".to_string()).to_string()
+    );
+    assert_eq!(
+        "HTTP 404: not found",
+        GoToError::Http {
+            status: 404,
+            body: "not found".to_string()
+        }
+        .to_string()
+    );
+}
+
+impl From<url::ParseError> for GoToError {
+    fn from(error: url::ParseError) -> Self {
+        GoToError::CliError(format!("invalid url: {}", error))
+    }
+}
+
+impl From<reqwest::Error> for GoToError {
+    fn from(error: reqwest::Error) -> Self {
+        if error.is_timeout() {
+            return GoToError::Timeout;
+        }
+
+        GoToError::ApiError(error.to_string())
     }
 }
 
 impl From<std::string::FromUtf8Error> for GoToError {
     fn from(error: std::string::FromUtf8Error) -> Self {
-        GoToError::ApiError(format!("expected utf8: {}", error.to_string()))
+        GoToError::ApiError(format!("expected utf8: {}", error))
     }
 }
 
-impl From<hyper::header::ToStrError> for GoToError {
-    fn from(error: hyper::header::ToStrError) -> Self {
+impl From<reqwest::header::ToStrError> for GoToError {
+    fn from(error: reqwest::header::ToStrError) -> Self {
         GoToError::ApiError(error.to_string())
     }
 }
@@ -56,6 +449,8 @@ impl From<hyper::header::ToStrError> for GoToError {
 struct CliOptions {
     shorturl: String,
     target: Option<String>,
+    command: Option<Command>,
+    max_clicks: Option<u64>,
 
     verbose: bool,
     open_browser: bool,
@@ -84,8 +479,10 @@ impl CliOptions {
         };
 
         CliOptions {
-            shorturl: args.shorturl.to_owned(),
+            shorturl: args.shorturl.to_owned().unwrap_or_default(),
             target: args.target.to_owned(),
+            command: args.command.to_owned(),
+            max_clicks: args.max_clicks,
             verbose,
             open_browser,
         }
@@ -99,105 +496,127 @@ mod test_cli_options {
     #[test]
     fn test_open_browser() {
         let mut args = Args {
-            shorturl: String::new(),
+            shorturl: None,
             target: None,
             api_url: None,
+            config: None,
             silent: false,
             no_browser: false,
+            timeout: None,
+            max_retries: None,
+            retry_base_delay_ms: None,
+            admin_password: None,
+            max_clicks: None,
+            command: None,
         };
 
         let mut config = Config {
             api_url: None,
             silent: None,
             no_browser: None,
+            timeout: None,
+            max_retries: None,
+            retry_base_delay_ms: None,
+            admin_password: None,
         };
 
         // default
         args.no_browser = false;
         config.no_browser = None;
         let got = CliOptions::new(&args, &config);
-        assert_eq!(true, got.open_browser);
+        assert!(got.open_browser);
 
         // both args and config agree
         args.no_browser = true;
         config.no_browser = Some(true);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.open_browser);
+        assert!(!got.open_browser);
 
         args.no_browser = false;
         config.no_browser = Some(false);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(true, got.open_browser);
+        assert!(got.open_browser);
 
         // args take precendence over config
         args.no_browser = true;
         config.no_browser = Some(false);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.open_browser);
+        assert!(!got.open_browser);
 
         // only args
         args.no_browser = true;
         config.no_browser = None;
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.open_browser);
+        assert!(!got.open_browser);
 
         // only config
         args.no_browser = false;
         config.no_browser = Some(true);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.open_browser);
+        assert!(!got.open_browser);
     }
 
     #[test]
     fn test_verbose() {
         let mut args = Args {
-            shorturl: String::new(),
+            shorturl: None,
             target: None,
             api_url: None,
+            config: None,
             silent: false,
             no_browser: false,
+            timeout: None,
+            max_retries: None,
+            retry_base_delay_ms: None,
+            admin_password: None,
+            max_clicks: None,
+            command: None,
         };
 
         let mut config = Config {
             api_url: None,
             silent: None,
             no_browser: None,
+            timeout: None,
+            max_retries: None,
+            retry_base_delay_ms: None,
+            admin_password: None,
         };
 
         // default
         args.silent = false;
         config.silent = None;
         let got = CliOptions::new(&args, &config);
-        assert_eq!(true, got.verbose);
+        assert!(got.verbose);
 
         // both args and config agree
         args.silent = true;
         config.silent = Some(true);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.verbose);
+        assert!(!got.verbose);
 
         args.silent = false;
         config.silent = Some(false);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(true, got.verbose);
+        assert!(got.verbose);
 
         // args take precendence over config
         args.silent = true;
         config.silent = Some(false);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.verbose);
+        assert!(!got.verbose);
 
         // only args
         args.silent = true;
         config.silent = None;
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.verbose);
+        assert!(!got.verbose);
 
         // only config
         args.silent = false;
         config.silent = Some(true);
         let got = CliOptions::new(&args, &config);
-        assert_eq!(false, got.verbose);
+        assert!(!got.verbose);
     }
 }
 
@@ -207,411 +626,3149 @@ struct Cli<C: Client> {
 }
 
 impl<C: Client> Cli<C> {
-    async fn run(self) -> Result<(), GoToError> {
+    /// run executes the command, and returns the output of creating or
+    /// resolving a shortened URL, if any.
+    async fn run(self) -> Result<Option<String>, GoToError> {
+        if let Some(command) = self.options.command {
+            return match command {
+                Command::Delete { shorturl } => self.client.delete_url(shorturl).await,
+                Command::Rename { old, new, force } => rename(&self.client, old, new, force).await,
+                Command::List { output } => {
+                    let entries = self.client.list_urls().await?;
+                    display_entries(&entries, output, &mut std::io::stdout())
+                }
+                Command::Stats { shorturl, output } => {
+                    let stats = self.client.get_stats(shorturl).await?;
+                    display_stats(&stats, output, &mut std::io::stdout())
+                }
+                Command::Check { shorturls, timeout } => {
+                    let http = http_client()?;
+                    let checks =
+                        check_all(&self.client, &http, shorturls, Duration::from_secs(timeout))
+                            .await;
+                    display_checks(&checks, &mut std::io::stdout())
+                }
+                Command::Verify {
+                    concurrency,
+                    timeout,
+                    output_file,
+                    ..
+                } => {
+                    let entries = self.client.list_urls().await?;
+                    let checks = verify_all(
+                        http_client()?,
+                        entries,
+                        concurrency as usize,
+                        Duration::from_secs(timeout),
+                    )
+                    .await?;
+
+                    if let Some(output_file) = output_file {
+                        write_broken_links(&checks, &output_file)?;
+                    }
+
+                    let stdout = std::io::stdout();
+                    let color = stdout.is_terminal();
+                    display_verified(&checks, color, stdout)
+                }
+                Command::Import { file, dry_run } => {
+                    let entries = read_import_file(&file)?;
+                    import(&self.client, entries, dry_run, std::io::stdout()).await?;
+                    Ok(())
+                }
+                Command::Batch => {
+                    let http = http_client()?;
+                    let stdin = std::io::BufReader::new(std::io::stdin());
+                    batch(&self.client, &http, stdin, std::io::stdout()).await
+                }
+                Command::Export { output, format } => {
+                    let entries = self.client.list_urls().await?;
+                    let data = render_export(&entries, format)?;
+                    write_export(&data, output.as_deref())
+                }
+                Command::Ping { count, interval } => {
+                    let interval = Duration::from_millis(interval);
+                    ping(&self.client, count, interval, std::io::stdout()).await
+                }
+                // the config file may be broken or missing, so `run` handles
+                // them before reading it
+                Command::Doctor | Command::Config { .. } => Err(GoToError::CliError(
+                    "doctor and config don't use the API".to_string(),
+                )),
+                Command::Completions { shell } => {
+                    print!("{}", generate_completions(shell));
+                    Ok(())
+                }
+            }
+            .map(|_| None);
+        }
+
         match self.options.target {
-            Some(target) => self.client.create_new(self.options.shorturl, target).await,
+            Some(target) => {
+                let message = self
+                    .client
+                    .create_new(self.options.shorturl, target, self.options.max_clicks)
+                    .await?;
+
+                Ok(Some(message))
+            }
             None => {
                 let location = self.client.get_long_url(self.options.shorturl).await?;
-
-                display_location(&location, self.options.verbose, &mut std::io::stdout());
                 open_location(&location, self.options.open_browser);
 
-                Ok(())
+                Ok(Some(location))
             }
         }
     }
 }
 
-fn display_location(loc: &str, verbose: bool, mut writer: impl std::io::Write) {
-    if verbose {
-        writeln!(writer, "redirecting to {}", loc).unwrap();
+/// rename makes `new` redirect to the target of `old`, then deletes `old`.
+///
+/// The API has no transactions, so this isn't atomic: if deleting `old`
+/// fails, both shortened URLs exist afterwards. Unless `force` is set, it
+/// fails before deleting anything if `new` already exists.
+async fn rename<C: Client>(
+    client: &C,
+    old: String,
+    new: String,
+    force: bool,
+) -> Result<(), GoToError> {
+    if old == new {
+        return Err(GoToError::CliError(format!(
+            "cannot rename /{} to itself",
+            old
+        )));
     }
-}
 
-#[test]
-fn test_display_location_silent() {
-    let mut result = Vec::new();
-    display_location("hi there", false, &mut result);
-
-    assert_eq!(b"".to_vec(), result);
-}
+    let target = client.resolve_url(old.to_owned()).await?;
 
-#[test]
-fn test_display_location_verbose() {
-    let mut result = Vec::new();
-    display_location("http://hi.there", true, &mut result);
+    match client
+        .create_new(new.to_owned(), target.to_owned(), None)
+        .await
+    {
+        Ok(_) => {}
+        // the API rejects existing ids with a client error
+        Err(GoToError::Http { status, .. }) if force && status < 500 => {
+            client.update_url(new, target).await?;
+        }
+        Err(err) => return Err(err),
+    }
 
-    assert_eq!(b"redirecting to http://hi.there\n".to_vec(), result,);
+    client.delete_url(old).await
 }
 
-#[cfg(not(tarpaulin_include))]
-fn open_location(loc: &str, browser: bool) {
-    if browser {
-        webbrowser::open(loc).unwrap();
-    }
-}
+/// batch runs the commands read from `input`, one per line: `create <id>
+/// <target>`, `update <id> <target>`, `delete <id>` or `check <id>`. Empty
+/// lines and lines starting with `#` are skipped.
+///
+/// Failed commands are reported and the following ones still run, but batch
+/// fails once every line was processed.
+async fn batch<C: Client>(
+    client: &C,
+    http: &reqwest::Client,
+    input: impl std::io::BufRead,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let write_err = |err: std::io::Error| GoToError::CliError(err.to_string());
+
+    let mut processed = 0;
+    let mut errors = 0;
+    for (number, line) in input.lines().enumerate() {
+        let line = line.map_err(|err| GoToError::CliError(format!("read stdin: {}", err)))?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
 
-#[derive(serde::Serialize, serde::Deserialize, PartialEq, Debug)]
-struct Config {
-    api_url: Option<String>,
-    silent: Option<bool>,
-    no_browser: Option<bool>,
-}
+        processed += 1;
+        match run_batch_command(client, http, line).await {
+            Ok(output) => writeln!(writer, "{}", output),
+            Err(err) => {
+                errors += 1;
+                writeln!(writer, "line {}: {}", number + 1, err)
+            }
+        }
+        .map_err(write_err)?;
+    }
 
-fn open_or_create_config(filepath: &PathBuf) -> Result<Config, GoToError> {
-    let _ = std::fs::create_dir_all(filepath.parent().unwrap());
+    writeln!(writer, "Processed: {}, Errors: {}", processed, errors).map_err(write_err)?;
 
-    let file = OpenOptions::new()
-        .write(true)
-        .create(true)
-        .read(true)
-        .truncate(false)
-        .open(filepath)
-        .or_else(|err| Err(GoToError::CliError(format!("open config file: {}", err))))?;
+    if errors > 0 {
+        return Err(GoToError::CliError(format!(
+            "{} of {} commands failed",
+            errors, processed
+        )));
+    }
 
-    read_or_write_config(file)
+    Ok(())
 }
 
-fn read_or_write_config(
-    mut file: impl std::io::Read + std::io::Write,
-) -> Result<Config, GoToError> {
-    let mut buf = String::new();
-    match file.read_to_string(&mut buf) {
-        Err(err) => Err(GoToError::CliError(format!("read config file: {}", err))),
-        Ok(len) => {
-            if len == 0 {
-                let default = Config {
-                    silent: Some(false),
-                    no_browser: Some(false),
-                    api_url: Some(DEFAULT_API_URL.to_string()),
-                };
-
-                file.write_all(serde_yaml::to_string(&default).unwrap().as_bytes())
-                    .or_else(|err| {
-                        Err(GoToError::CliError(format!(
-                            "write default config: {}",
-                            err
-                        )))
-                    })?;
+/// run_batch_command runs a line of `batch`, and returns what to print.
+async fn run_batch_command<C: Client>(
+    client: &C,
+    http: &reqwest::Client,
+    line: &str,
+) -> Result<String, GoToError> {
+    let usage = |usage: &str| -> Result<String, GoToError> {
+        Err(GoToError::CliError(format!("usage: {}", usage)))
+    };
 
-                Ok(default)
+    match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+        ["create", id, target] => {
+            client
+                .create_new(id.to_string(), target.to_string(), None)
+                .await
+        }
+        ["create", ..] => usage("create <id> <target>"),
+        ["update", id, target] => client.update_url(id.to_string(), target.to_string()).await,
+        ["update", ..] => usage("update <id> <target>"),
+        ["delete", id] => {
+            client.delete_url(id.to_string()).await?;
+            Ok(format!("deleted /{}", id))
+        }
+        ["delete", ..] => usage("delete <id>"),
+        ["check", id] => {
+            let timeout = Duration::from_secs(DEFAULT_CHECK_TIMEOUT_SECS);
+            let checks = check_all(client, http, vec![id.to_string()], timeout).await;
+            let check = &checks[0];
+            if check.is_ok() {
+                Ok(render_check(check))
             } else {
-                let yaml_contents = serde_yaml::from_str(&buf).or_else(|err| {
-                    Err(GoToError::CliError(format!("parse config data: {}", err)))
-                })?;
-
-                Ok(yaml_contents)
+                Err(GoToError::CliError(render_check(check)))
             }
         }
+        ["check", ..] => usage("check <id>"),
+        [command, ..] => Err(GoToError::CliError(format!("unknown command: {}", command))),
+        [] => unreachable!("empty lines are skipped"),
     }
 }
 
-#[cfg(test)]
-mod config_tests {
-    use std::env::temp_dir;
-    use std::fs::File;
-    use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
-
-    use super::*;
+/// The result of checking whether the target of a shortened URL is reachable.
+#[derive(Debug, PartialEq)]
+struct TargetCheck {
+    shorturl: String,
+    target: Option<String>,
+    status: Result<u16, String>,
+}
 
-    #[test]
-    fn test_create_config_when_missing() {
-        let mut data: Vec<u8> = Vec::new();
-        let mut mock_file = Cursor::new(&mut data);
+impl TargetCheck {
+    fn is_ok(&self) -> bool {
+        matches!(self.status, Ok(status) if (200..400).contains(&status))
+    }
 
-        read_or_write_config(&mut mock_file).unwrap();
+    /// status_text describes the status, like `200 OK` or the reason the
+    /// target couldn't be reached.
+    fn status_text(&self) -> String {
+        match &self.status {
+            Ok(status) => match reqwest::StatusCode::from_u16(*status) {
+                Ok(status) => status.to_string(),
+                Err(_) => status.to_string(),
+            },
+            Err(err) => err.to_owned(),
+        }
+    }
+}
 
-        let got = String::from_utf8(data).unwrap();
-        assert!(got.contains("silent: false"), "{}", got);
-        assert!(got.contains("no_browser: false"), "{}", got);
-        assert!(got.contains("api_url: \"http://"), "{}", got);
+/// check_all resolves the targets of `shorturls`, then checks them
+/// concurrently with `http`.
+async fn check_all<C: Client>(
+    client: &C,
+    http: &reqwest::Client,
+    shorturls: Vec<String>,
+    timeout: Duration,
+) -> Vec<TargetCheck> {
+    let mut targets = Vec::with_capacity(shorturls.len());
+    for shorturl in shorturls {
+        let target = client.resolve_url(shorturl.to_owned()).await;
+        targets.push((shorturl, target));
     }
 
-    #[test]
-    fn test_read_existing_config() {
-        let mut data: Vec<u8> = Vec::from("silent: true\napi_url: \"hello\"");
-        let mut mock_file = Cursor::new(&mut data);
+    futures::future::join_all(targets.into_iter().map(|(shorturl, target)| async move {
+        match target {
+            Ok(target) => {
+                let status = check_target(http, &target, timeout).await;
+                TargetCheck {
+                    shorturl,
+                    target: Some(target),
+                    status,
+                }
+            }
+            Err(err) => TargetCheck {
+                shorturl,
+                target: None,
+                status: Err(err.to_string()),
+            },
+        }
+    }))
+    .await
+}
 
-        let got = read_or_write_config(&mut mock_file).unwrap();
+/// check_target sends a HEAD request to `url` and returns the status code of
+/// the response. Redirections aren't followed if `http` was built with
+/// `http_client`.
+async fn check_target(http: &reqwest::Client, url: &str, timeout: Duration) -> Result<u16, String> {
+    let resp = http
+        .head(url)
+        .timeout(timeout)
+        .send()
+        .await
+        .map_err(|err| err.to_string())?;
+    Ok(resp.status().as_u16())
+}
 
-        assert_eq!(Some(true), got.silent);
-        assert_eq!(None, got.no_browser);
-        assert_eq!(Some("hello".to_string()), got.api_url);
-        assert_eq!(
-            "silent: true\napi_url: \"hello\"".to_string(),
-            String::from_utf8(data).unwrap()
-        );
+/// display_checks prints one line per check, and fails if any target is
+/// unreachable.
+fn display_checks(
+    checks: &[TargetCheck],
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    for check in checks {
+        writeln!(writer, "{}", render_check(check))
+            .map_err(|err| GoToError::CliError(err.to_string()))?;
     }
 
-    #[test]
-    fn test_create_config() {
-        let mut filepath = temp_dir();
-        filepath.push("test_create_config.yml");
+    let failed = checks.iter().filter(|check| !check.is_ok()).count();
+    if failed > 0 {
+        return Err(GoToError::CliError(format!(
+            "{} of {} targets are unreachable",
+            failed,
+            checks.len()
+        )));
+    }
 
-        let got = open_or_create_config(&filepath);
-        assert!(got.is_ok());
+    Ok(())
+}
 
-        let mut file = File::open(&filepath).unwrap();
-        let mut content = String::new();
-        file.read_to_string(&mut content).unwrap();
+/// render_check formats a check like `✓ /foo → https://example.com (200 OK)`.
+fn render_check(check: &TargetCheck) -> String {
+    let mark = if check.is_ok() { '✓' } else { '✗' };
+    let status = check.status_text();
 
-        assert!(content.contains("api_url"));
+    match &check.target {
+        Some(target) => format!("{} /{} → {} ({})", mark, check.shorturl, target, status),
+        None => format!("{} /{} ({})", mark, check.shorturl, status),
     }
+}
 
-    #[test]
-    fn test_open_config() {
-        let mut filepath = temp_dir();
-        filepath.push("test_existing_config.yml");
+/// verify_all checks the targets of `entries`, running up to `concurrency`
+/// checks in parallel. The checks share the connection pool of `http`, and
+/// are returned in the order of `entries`.
+async fn verify_all(
+    http: reqwest::Client,
+    entries: Vec<UrlEntry>,
+    concurrency: usize,
+    timeout: Duration,
+) -> Result<Vec<TargetCheck>, GoToError> {
+    let mut checks = Vec::with_capacity(entries.len());
+    let mut set = tokio::task::JoinSet::new();
+
+    for (index, entry) in entries.into_iter().enumerate() {
+        if set.len() >= concurrency {
+            if let Some(res) = set.join_next().await {
+                checks.push(res.map_err(|err| GoToError::CliError(err.to_string()))?);
+            }
+        }
 
-        let mut file = File::create(&filepath).unwrap();
-        file.write_all(b"api_url: \"http://hello.world\"\n")
-            .unwrap();
+        let http = http.clone();
+        set.spawn(async move {
+            let status = check_target(&http, &entry.target, timeout).await;
+            let check = TargetCheck {
+                shorturl: entry.id,
+                target: Some(entry.target),
+                status,
+            };
+            (index, check)
+        });
+    }
 
-        let got = open_or_create_config(&filepath).unwrap();
-        assert_eq!(Some("http://hello.world".to_string()), got.api_url);
+    while let Some(res) = set.join_next().await {
+        checks.push(res.map_err(|err| GoToError::CliError(err.to_string()))?);
     }
 
-    #[test]
-    fn test_open_config_invalid_data() {
-        let mut filepath = temp_dir();
-        filepath.push("contains_invalid_data");
+    checks.sort_by_key(|(index, _)| *index);
+    Ok(checks.into_iter().map(|(_, check)| check).collect())
+}
 
-        let mut file = File::create(&filepath).unwrap();
-        file.write_all(b"what is this... it doesn't look like valid YAML!{ }}}} P{{")
-            .unwrap();
+/// display_verified prints a table of the checks followed by a summary, and
+/// fails if any link is broken.
+fn display_verified(
+    checks: &[TargetCheck],
+    color: bool,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let write_err = |err: std::io::Error| GoToError::CliError(err.to_string());
+
+    writeln!(writer, "{}", render_verified(checks, color)).map_err(write_err)?;
+
+    let broken = checks.iter().filter(|check| !check.is_ok()).count();
+    writeln!(
+        writer,
+        "{} healthy, {} broken",
+        checks.len() - broken,
+        broken
+    )
+    .map_err(write_err)?;
+
+    if broken > 0 {
+        return Err(GoToError::CliError(format!(
+            "found {} broken links",
+            broken
+        )));
+    }
 
-        let got = open_or_create_config(&filepath);
-        assert!(got.is_err());
+    Ok(())
+}
 
-        let err = got.err().unwrap();
-        assert!(
-            format!("{:?}", err).contains("parse config data:"),
-            "{:?}",
-            err
-        );
+/// render_verified formats checks into an `ID TARGET STATUS` table. With
+/// `color`, healthy statuses are green and broken ones are red.
+fn render_verified(checks: &[TargetCheck], color: bool) -> String {
+    let rows: Vec<[String; 3]> = checks
+        .iter()
+        .map(|check| {
+            [
+                check.shorturl.to_owned(),
+                check.target.to_owned().unwrap_or_else(|| "-".to_string()),
+                check.status_text(),
+            ]
+        })
+        .collect();
+
+    let mut lines = render_columns(["ID", "TARGET", "STATUS"], &rows);
+    if color {
+        // the status is the last column, so it's never padded
+        for (line, (check, row)) in lines.iter_mut().skip(1).zip(checks.iter().zip(&rows)) {
+            let code = if check.is_ok() { 32 } else { 31 };
+            let prefix = &line[..line.len() - row[2].len()];
+            *line = format!("{}\x1b[{}m{}\x1b[0m", prefix, code, row[2]);
+        }
     }
 
-    #[test]
-    fn test_open_config_wrong_file() {
-        let mut filepath = temp_dir();
-        filepath.push("{}///////\\\\\\////");
+    lines.join("\n")
+}
 
-        let got = open_or_create_config(&filepath);
-        assert!(got.is_err());
+/// A link whose target couldn't be reached, as written by `goto verify`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct BrokenLink {
+    id: String,
+    target: String,
+    status: String,
+}
 
-        let err = got.err().unwrap();
-        assert!(
-            format!("{:?}", err).contains("open config file:"),
-            "{:?}",
-            err
-        );
-    }
+/// write_broken_links writes the broken links among checks to a JSON file.
+fn write_broken_links(checks: &[TargetCheck], filepath: &Path) -> Result<(), GoToError> {
+    let broken: Vec<BrokenLink> = checks
+        .iter()
+        .filter(|check| !check.is_ok())
+        .map(|check| BrokenLink {
+            id: check.shorturl.to_owned(),
+            target: check.target.to_owned().unwrap_or_default(),
+            status: check.status_text(),
+        })
+        .collect();
+
+    let data = serde_json::to_string_pretty(&broken)
+        .map_err(|err| GoToError::CliError(format!("serialise broken links: {}", err)))?;
+
+    std::fs::write(filepath, data)
+        .map_err(|err| GoToError::CliError(format!("write broken links: {}", err)))
+}
 
-    struct RWMockCantRead {}
+#[cfg(test)]
+mod check_tests {
+    use super::*;
 
-    impl std::io::Read for RWMockCantRead {
-        fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
-            Err(Error::new(ErrorKind::Other, "oh no!"))
+    use httpmock::{Method, MockServer};
+
+    fn check(status: Result<u16, String>) -> TargetCheck {
+        TargetCheck {
+            shorturl: "foo".to_string(),
+            target: Some("https://example.com".to_string()),
+            status,
         }
     }
 
-    impl std::io::Write for RWMockCantRead {
-        fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-            todo!()
-        }
+    #[test]
+    fn test_render_check() {
+        assert_eq!(
+            "✓ /foo → https://example.com (200 OK)",
+            render_check(&check(Ok(200)))
+        );
+        assert_eq!(
+            "✓ /foo → https://example.com (301 Moved Permanently)",
+            render_check(&check(Ok(301)))
+        );
+        assert_eq!(
+            "✗ /foo → https://example.com (404 Not Found)",
+            render_check(&check(Ok(404)))
+        );
+        assert_eq!(
+            "✗ /foo → https://example.com (connection refused)",
+            render_check(&check(Err("connection refused".to_string())))
+        );
 
-        fn flush(&mut self) -> Result<()> {
-            todo!()
-        }
+        let no_target = TargetCheck {
+            shorturl: "bar".to_string(),
+            target: None,
+            status: Err("CLI error: not found".to_string()),
+        };
+        assert_eq!("✗ /bar (CLI error: not found)", render_check(&no_target));
     }
 
     #[test]
-    fn test_cannot_read_config() {
-        let mut mock_file = RWMockCantRead {};
+    fn test_display_checks() {
+        let mut result = Vec::new();
+        display_checks(&[check(Ok(200))], &mut result).unwrap();
+        assert_eq!(
+            "✓ /foo → https://example.com (200 OK)\n",
+            String::from_utf8(result).unwrap()
+        );
 
-        let got = read_or_write_config(&mut mock_file);
-        let want = Err(GoToError::CliError("read config file: oh no!".to_string()));
-        assert_eq!(want, got);
+        let mut result = Vec::new();
+        let got = display_checks(&[check(Ok(200)), check(Ok(500))], &mut result);
+        assert_eq!(
+            Err(GoToError::CliError(
+                "1 of 2 targets are unreachable".to_string()
+            )),
+            got
+        );
+        assert_eq!(2, String::from_utf8(result).unwrap().lines().count());
     }
 
-    struct RWMockCantWrite {}
+    #[actix_rt::test]
+    async fn test_check_target() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::HEAD).path("/alive");
 
-    impl std::io::Read for RWMockCantWrite {
-        fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
-            Ok(0)
-        }
+            then.status(204);
+        });
+
+        let http = http_client().unwrap();
+        let got = check_target(&http, &server.url("/alive"), Duration::from_secs(1)).await;
+
+        mock.assert();
+        assert_eq!(Ok(204), got);
     }
 
-    impl std::io::Write for RWMockCantWrite {
-        fn write(&mut self, _buf: &[u8]) -> Result<usize> {
-            Err(Error::new(ErrorKind::Other, "that went terribly wrong!"))
+    #[actix_rt::test]
+    async fn test_check_target_unreachable() {
+        let http = http_client().unwrap();
+        let got = check_target(&http, "http://127.0.0.1:1/dead", Duration::from_secs(1)).await;
+        assert!(got.is_err(), "{:?}", got);
+    }
+
+    fn verified() -> Vec<TargetCheck> {
+        vec![
+            TargetCheck {
+                shorturl: "gh".to_string(),
+                target: Some("https://github.com".to_string()),
+                status: Ok(200),
+            },
+            TargetCheck {
+                shorturl: "dead".to_string(),
+                target: Some("https://dead.example.com".to_string()),
+                status: Err("connection refused".to_string()),
+            },
+        ]
+    }
+
+    #[test]
+    fn test_render_verified() {
+        let want = "\
+ID    TARGET                    STATUS
+gh    https://github.com        200 OK
+dead  https://dead.example.com  connection refused";
+
+        assert_eq!(want, render_verified(&verified(), false));
+    }
+
+    #[test]
+    fn test_render_verified_color() {
+        let got = render_verified(&verified(), true);
+        let lines: Vec<&str> = got.lines().collect();
+
+        assert_eq!("ID    TARGET                    STATUS", lines[0]);
+        assert_eq!(
+            "gh    https://github.com        \x1b[32m200 OK\x1b[0m",
+            lines[1]
+        );
+        assert_eq!(
+            "dead  https://dead.example.com  \x1b[31mconnection refused\x1b[0m",
+            lines[2]
+        );
+    }
+
+    #[test]
+    fn test_display_verified() {
+        let mut result = Vec::new();
+        let got = display_verified(&verified(), false, &mut result);
+
+        assert_eq!(
+            Err(GoToError::CliError("found 1 broken links".to_string())),
+            got
+        );
+        let result = String::from_utf8(result).unwrap();
+        assert!(result.ends_with("1 healthy, 1 broken\n"), "{}", result);
+
+        let mut result = Vec::new();
+        display_verified(&verified()[..1], false, &mut result).unwrap();
+        let result = String::from_utf8(result).unwrap();
+        assert!(result.ends_with("1 healthy, 0 broken\n"), "{}", result);
+    }
+
+    #[test]
+    fn test_write_broken_links() {
+        let mut filepath = std::env::temp_dir();
+        filepath.push("test_write_broken_links.json");
+
+        write_broken_links(&verified(), &filepath).unwrap();
+
+        let data = std::fs::read_to_string(&filepath).unwrap();
+        let got: Vec<BrokenLink> = serde_json::from_str(&data).unwrap();
+        assert_eq!(
+            vec![BrokenLink {
+                id: "dead".to_string(),
+                target: "https://dead.example.com".to_string(),
+                status: "connection refused".to_string(),
+            }],
+            got
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_verify_all() {
+        let server = MockServer::start();
+        let alive = server.mock(|when, then| {
+            when.method(Method::HEAD).path("/alive");
+
+            then.status(200);
+        });
+        let gone = server.mock(|when, then| {
+            when.method(Method::HEAD).path("/gone");
+
+            then.status(404);
+        });
+
+        let entries = ["alive", "gone", "alive"]
+            .iter()
+            .enumerate()
+            .map(|(index, path)| UrlEntry {
+                id: index.to_string(),
+                target: server.url(format!("/{}", path)),
+                clicks: None,
+                created: None,
+                expires: None,
+            })
+            .collect();
+
+        let got = verify_all(http_client().unwrap(), entries, 2, Duration::from_secs(1))
+            .await
+            .unwrap();
+
+        alive.assert_hits(2);
+        gone.assert();
+        let got: Vec<(String, Result<u16, String>)> = got
+            .into_iter()
+            .map(|check| (check.shorturl, check.status))
+            .collect();
+        assert_eq!(
+            vec![
+                ("0".to_string(), Ok(200)),
+                ("1".to_string(), Ok(404)),
+                ("2".to_string(), Ok(200)),
+            ],
+            got
+        );
+    }
+}
+
+/// The maximum number of entries sent to the API in a single bulk request.
+const IMPORT_BATCH_SIZE: usize = 100;
+
+/// A shortened URL to import, as read from a CSV or JSON file.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+struct BulkEntry {
+    id: String,
+    target: String,
+}
+
+/// An entry that couldn't be imported, and why.
+#[derive(serde::Deserialize, Clone, Debug, PartialEq)]
+struct BulkFailure {
+    id: String,
+    reason: String,
+}
+
+/// The outcome of a bulk import, as returned by the API.
+#[derive(serde::Deserialize, Default, Debug, PartialEq)]
+struct BulkReport {
+    created: Vec<String>,
+    updated: Vec<String>,
+    failed: Vec<BulkFailure>,
+}
+
+/// read_import_file reads the entries of a `.csv` or `.json` file.
+fn read_import_file(filepath: &Path) -> Result<Vec<BulkEntry>, GoToError> {
+    let file = std::fs::File::open(filepath)
+        .map_err(|err| GoToError::CliError(format!("open import file: {}", err)))?;
+
+    match filepath.extension().and_then(|ext| ext.to_str()) {
+        Some("csv") => parse_csv_entries(file),
+        Some("json") => parse_json_entries(file),
+        _ => Err(GoToError::CliError(
+            "import file must be a .csv or .json file".to_string(),
+        )),
+    }
+}
+
+/// parse_csv_entries reads entries from CSV data with an `id,target` header.
+fn parse_csv_entries(reader: impl std::io::Read) -> Result<Vec<BulkEntry>, GoToError> {
+    csv::ReaderBuilder::new()
+        .trim(csv::Trim::All)
+        .from_reader(reader)
+        .deserialize()
+        .collect::<Result<Vec<BulkEntry>, csv::Error>>()
+        .map_err(|err| GoToError::CliError(format!("parse csv: {}", err)))
+}
+
+/// parse_json_entries reads entries from a JSON list of `{"id", "target"}`.
+fn parse_json_entries(reader: impl std::io::Read) -> Result<Vec<BulkEntry>, GoToError> {
+    serde_json::from_reader(reader)
+        .map_err(|err| GoToError::CliError(format!("parse json: {}", err)))
+}
+
+/// import sends entries to the API in batches of `IMPORT_BATCH_SIZE`.
+/// Entries whose target isn't a valid URL are reported as failed without
+/// being sent. With `dry_run`, nothing is sent at all.
+async fn import<C: Client>(
+    client: &C,
+    entries: Vec<BulkEntry>,
+    dry_run: bool,
+    mut writer: impl std::io::Write,
+) -> Result<BulkReport, GoToError> {
+    let write_err = |err: std::io::Error| GoToError::CliError(err.to_string());
+
+    let mut report = BulkReport::default();
+    let mut valid = Vec::with_capacity(entries.len());
+    for entry in entries {
+        match url::Url::parse(&entry.target) {
+            Ok(_) => valid.push(entry),
+            Err(err) => report.failed.push(BulkFailure {
+                id: entry.id,
+                reason: format!("invalid target: {}", err),
+            }),
         }
+    }
 
-        fn flush(&mut self) -> Result<()> {
-            Ok(())
+    if dry_run {
+        report.created = valid.into_iter().map(|entry| entry.id).collect();
+    } else {
+        let total = valid.len();
+        let mut imported = 0;
+        for batch in valid.chunks(IMPORT_BATCH_SIZE) {
+            let batch_report = client.bulk_import(batch.to_vec()).await?;
+            imported += batch.len();
+            writeln!(writer, "Imported {}/{}...", imported, total).map_err(write_err)?;
+
+            report.created.extend(batch_report.created);
+            report.updated.extend(batch_report.updated);
+            report.failed.extend(batch_report.failed);
         }
     }
 
+    for failure in &report.failed {
+        writeln!(
+            writer,
+            "Failed to import /{}: {}",
+            failure.id, failure.reason
+        )
+        .map_err(write_err)?;
+    }
+
+    writeln!(
+        writer,
+        "Created: {}, Updated: {}, Failed: {}",
+        report.created.len(),
+        report.updated.len(),
+        report.failed.len()
+    )
+    .map_err(write_err)?;
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod import_tests {
+    use super::*;
+
     #[test]
-    fn test_cannot_write_config() {
-        let mut mock_file = RWMockCantWrite {};
+    fn test_parse_csv_entries() {
+        let data = "id,target\ngh, https://github.com\nhi,http://hi.there\n";
 
-        let got = read_or_write_config(&mut mock_file);
-        let want = Err(GoToError::CliError(
-            "write default config: that went terribly wrong!".to_string(),
-        ));
-        assert_eq!(want, got);
+        let got = parse_csv_entries(data.as_bytes()).unwrap();
+        assert_eq!(
+            vec![
+                BulkEntry {
+                    id: "gh".to_string(),
+                    target: "https://github.com".to_string(),
+                },
+                BulkEntry {
+                    id: "hi".to_string(),
+                    target: "http://hi.there".to_string(),
+                },
+            ],
+            got
+        );
+    }
+
+    #[test]
+    fn test_parse_csv_entries_missing_column() {
+        let data = "id,target\ngh\n";
+
+        let got = parse_csv_entries(data.as_bytes());
+        assert!(format!("{:?}", got).contains("parse csv:"), "{:?}", got);
+    }
+
+    #[test]
+    fn test_parse_json_entries() {
+        let data = r#"[{"id": "gh", "target": "https://github.com"}]"#;
+
+        let got = parse_json_entries(data.as_bytes()).unwrap();
+        assert_eq!(
+            vec![BulkEntry {
+                id: "gh".to_string(),
+                target: "https://github.com".to_string(),
+            }],
+            got
+        );
+    }
+
+    #[test]
+    fn test_read_import_file() {
+        let mut filepath = std::env::temp_dir();
+        filepath.push("test_read_import_file.csv");
+        std::fs::write(&filepath, "id,target\ngh,https://github.com\n").unwrap();
+
+        let got = read_import_file(&filepath).unwrap();
+        assert_eq!(1, got.len());
+
+        let mut filepath = std::env::temp_dir();
+        filepath.push("test_read_import_file.txt");
+        std::fs::write(&filepath, "gh https://github.com\n").unwrap();
+
+        let got = read_import_file(&filepath);
+        assert_eq!(
+            Err(GoToError::CliError(
+                "import file must be a .csv or .json file".to_string()
+            )),
+            got
+        );
     }
 }
 
-#[cfg(test)]
-mod cant_read_config_tests {}
+/// render_export serialises entries for `goto export`:
+/// - YAML is a map of IDs to targets, like the database file of the API,
+/// - JSON is a list of `{"id", "target"}`, like the files of `goto import`,
+/// - CSV has `id,target,clicks,created_at,expires_at` columns.
+fn render_export(entries: &[UrlEntry], format: ExportFormat) -> Result<String, GoToError> {
+    match format {
+        ExportFormat::Yaml => {
+            let targets: IndexMap<&str, &str> = entries
+                .iter()
+                .map(|entry| (entry.id.as_str(), entry.target.as_str()))
+                .collect();
+
+            serde_yaml::to_string(&targets)
+                .map_err(|err| GoToError::CliError(format!("serialise yaml: {}", err)))
+        }
+        ExportFormat::Json => {
+            let entries: Vec<BulkEntry> = entries
+                .iter()
+                .map(|entry| BulkEntry {
+                    id: entry.id.to_owned(),
+                    target: entry.target.to_owned(),
+                })
+                .collect();
+
+            serde_json::to_string_pretty(&entries)
+                .map_err(|err| GoToError::CliError(format!("serialise json: {}", err)))
+        }
+        ExportFormat::Csv => render_csv_export(entries)
+            .map_err(|err| GoToError::CliError(format!("serialise csv: {}", err))),
+    }
+}
 
-#[tokio::main]
-#[cfg(not(tarpaulin_include))]
-async fn main() -> Result<(), GoToError> {
-    let args = Args::from_args();
+fn render_csv_export(entries: &[UrlEntry]) -> Result<String, Box<dyn std::error::Error>> {
+    let mut writer = csv::Writer::from_writer(vec![]);
+    writer.write_record(["id", "target", "clicks", "created_at", "expires_at"])?;
+
+    for entry in entries {
+        writer.write_record([
+            entry.id.to_owned(),
+            entry.target.to_owned(),
+            entry
+                .clicks
+                .map(|clicks| clicks.to_string())
+                .unwrap_or_default(),
+            entry.created.to_owned().unwrap_or_default(),
+            entry.expires.to_owned().unwrap_or_default(),
+        ])?;
+    }
+
+    Ok(String::from_utf8(writer.into_inner()?)?)
+}
+
+/// write_export writes exported data to `output`, or to stdout without it.
+fn write_export(data: &str, output: Option<&Path>) -> Result<(), GoToError> {
+    match output {
+        Some(output) => std::fs::write(output, data)
+            .map_err(|err| GoToError::CliError(format!("write export file: {}", err))),
+        None => {
+            print!("{}", data);
+            Ok(())
+        }
+    }
+}
 
-    let mut filepath = home::home_dir().unwrap();
-    filepath.push(".goto");
-    filepath.push("config.yml");
+#[cfg(test)]
+mod export_tests {
+    use super::*;
 
-    let config = open_or_create_config(&filepath).unwrap();
+    fn entries() -> Vec<UrlEntry> {
+        vec![
+            UrlEntry {
+                id: "gh".to_string(),
+                target: "https://github.com/tsauvajon".to_string(),
+                clicks: Some(12),
+                created: Some("2021-01-01T00:00:00Z".to_string()),
+                expires: None,
+            },
+            UrlEntry {
+                id: "hi".to_string(),
+                target: "http://hi.there".to_string(),
+                clicks: None,
+                created: None,
+                expires: Some("2021-12-31T00:00:00Z".to_string()),
+            },
+        ]
+    }
 
-    let options = CliOptions::new(&args, &config);
-    let api_url = get_api_url(&args, &config);
+    #[test]
+    fn test_render_export_yaml() {
+        let got = render_export(&entries(), ExportFormat::Yaml).unwrap();
+        assert_eq!(
+            "---\ngh: \"https://github.com/tsauvajon\"\nhi: \"http://hi.there\"\n",
+            got
+        );
+    }
 
-    let cli = Cli {
-        options,
-        client: HttpClient::new(api_url),
-    };
+    #[test]
+    fn test_render_export_json() {
+        let got = render_export(&entries(), ExportFormat::Json).unwrap();
+
+        let got = parse_json_entries(got.as_bytes()).unwrap();
+        assert_eq!(
+            vec![
+                BulkEntry {
+                    id: "gh".to_string(),
+                    target: "https://github.com/tsauvajon".to_string(),
+                },
+                BulkEntry {
+                    id: "hi".to_string(),
+                    target: "http://hi.there".to_string(),
+                },
+            ],
+            got
+        );
+    }
 
-    cli.run().await
+    #[test]
+    fn test_render_export_csv() {
+        let got = render_export(&entries(), ExportFormat::Csv).unwrap();
+        assert_eq!(
+            "\
+id,target,clicks,created_at,expires_at
+gh,https://github.com/tsauvajon,12,2021-01-01T00:00:00Z,
+hi,http://hi.there,,,2021-12-31T00:00:00Z
+",
+            got
+        );
+    }
 }
 
-fn get_api_url(args: &Args, config: &Config) -> String {
-    match &args.api_url {
-        Some(api_url) => api_url.to_owned(),
-        None => match config.api_url.to_owned() {
-            Some(api_url) => api_url,
-            None => DEFAULT_API_URL.to_string(),
-        },
+/// display_output prints the output of `Cli::run`: the message of the API
+/// after creating a short URL, which contains the full short URL when the API
+/// knows its public URL, or the long URL a short URL redirects to.
+fn display_output(output: &str, verbose: bool, mut writer: impl std::io::Write) {
+    if verbose {
+        writeln!(writer, "{}", output).unwrap();
     }
 }
 
 #[test]
-fn test_get_api_url() {
-    let mut args = Args {
-        shorturl: String::new(),
-        target: None,
-        api_url: None,
-        silent: false,
-        no_browser: false,
-    };
+fn test_display_output() {
+    let message = "/hi now redirects to http://hi.there (short URL: https://go.company.com/hi)";
 
-    let mut config = Config {
-        api_url: None,
-        silent: None,
-        no_browser: None,
+    let mut result = Vec::new();
+    display_output(message, true, &mut result);
+    assert_eq!(format!("{}\n", message).into_bytes(), result);
+
+    let mut result = Vec::new();
+    display_output(message, false, &mut result);
+    assert_eq!(b"".to_vec(), result);
+}
+
+#[test]
+fn test_display_output_location() {
+    let mut result = Vec::new();
+    display_output("http://hi.there", true, &mut result);
+    assert_eq!(b"http://hi.there\n".to_vec(), result);
+
+    let mut result = Vec::new();
+    display_output("http://hi.there", false, &mut result);
+    assert_eq!(b"".to_vec(), result);
+}
+
+fn display_entries(
+    entries: &[UrlEntry],
+    output: OutputFormat,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let rendered = match output {
+        OutputFormat::Table => render_table(entries),
+        OutputFormat::Json => serde_json::to_string_pretty(entries)
+            .map_err(|err| GoToError::CliError(format!("serialise entries: {}", err)))?,
     };
 
-    // default
-    args.api_url = None;
-    config.api_url = None;
-    let got = get_api_url(&args, &config);
-    assert_eq!(DEFAULT_API_URL, got);
+    writeln!(writer, "{}", rendered).map_err(|err| GoToError::CliError(err.to_string()))
+}
 
-    // both args and config agree
-    args.api_url = Some("a".to_string());
-    config.api_url = Some("a".to_string());
-    let got = get_api_url(&args, &config);
-    assert_eq!("a".to_string(), got);
+fn display_stats(
+    stats: &UrlStats,
+    output: OutputFormat,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let rendered = match output {
+        OutputFormat::Table => render_stats(stats),
+        OutputFormat::Json => serde_json::to_string_pretty(stats)
+            .map_err(|err| GoToError::CliError(format!("serialise stats: {}", err)))?,
+    };
 
-    // args take precendence over config
-    args.api_url = Some("a".to_string());
-    config.api_url = Some("b".to_string());
-    let got = get_api_url(&args, &config);
-    assert_eq!("a".to_string(), got);
+    writeln!(writer, "{}", rendered).map_err(|err| GoToError::CliError(err.to_string()))
+}
 
-    // only args
-    args.api_url = Some("a".to_string());
-    config.api_url = None;
-    let got = get_api_url(&args, &config);
-    assert_eq!("a".to_string(), got);
+/// render_stats formats stats into a two-column table, one field per line.
+fn render_stats(stats: &UrlStats) -> String {
+    let or_dash = |value: &Option<String>| value.to_owned().unwrap_or_else(|| "-".to_string());
+    let rows = [
+        ("Clicks", stats.clicks.to_string()),
+        ("Target", stats.target.to_owned()),
+        ("Created", or_dash(&stats.created)),
+        ("Last Accessed", or_dash(&stats.last_accessed)),
+        ("Expires", or_dash(&stats.expires)),
+    ];
+
+    let width = rows.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+    rows.iter()
+        .map(|(name, value)| format!("{:<width$}  {}", name, value, width = width))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
 
-    // only config
-    args.api_url = None;
-    config.api_url = Some("a".to_string());
-    let got = get_api_url(&args, &config);
-    assert_eq!("a".to_string(), got);
+/// render_table formats entries into a left-aligned table.
+fn render_table(entries: &[UrlEntry]) -> String {
+    let headers = ["ID", "TARGET", "CLICKS", "EXPIRES"];
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.id.to_owned(),
+                entry.target.to_owned(),
+                entry
+                    .clicks
+                    .map(|clicks| clicks.to_string())
+                    .unwrap_or_else(|| "-".to_string()),
+                entry.expires.to_owned().unwrap_or_else(|| "-".to_string()),
+            ]
+        })
+        .collect();
+
+    render_columns(headers, &rows).join("\n")
 }
 
-#[async_trait]
-trait Client {
-    async fn create_new(self, shorturl: String, target: String) -> Result<(), GoToError>;
+/// render_columns left-aligns the cells of each column, and returns the
+/// header line followed by one line per row.
+fn render_columns<const N: usize>(headers: [&str; N], rows: &[[String; N]]) -> Vec<String> {
+    let mut widths = headers.map(|header| header.chars().count());
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let render_row = |cells: Vec<&str>| {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+
+    let mut lines = vec![render_row(headers.to_vec())];
+    for row in rows {
+        lines.push(render_row(row.iter().map(|cell| cell.as_str()).collect()));
+    }
 
-    async fn get_long_url(self, shorturl: String) -> Result<String, GoToError>;
+    lines
 }
 
 #[cfg(test)]
-mod cli_test {
+mod render_tests {
     use super::*;
 
-    struct MockClient {
-        create_new_called_with: Option<(String, String)>,
-        want_create_new_called_with: Option<(String, String)>,
+    fn entries() -> Vec<UrlEntry> {
+        vec![
+            UrlEntry {
+                id: "gh".to_string(),
+                target: "https://github.com/tsauvajon".to_string(),
+                clicks: Some(12),
+                created: None,
+                expires: None,
+            },
+            UrlEntry {
+                id: "linkedin".to_string(),
+                target: "https://linkedin.com/in/tsauvajon".to_string(),
+                clicks: None,
+                created: None,
+                expires: Some("2021-12-31T00:00:00Z".to_string()),
+            },
+        ]
+    }
 
-        get_long_url_called_with: Option<String>,
-        want_get_long_url_called_with: Option<String>,
+    #[test]
+    fn test_render_table() {
+        let want = "\
+ID        TARGET                             CLICKS  EXPIRES
+gh        https://github.com/tsauvajon       12      -
+linkedin  https://linkedin.com/in/tsauvajon  -       2021-12-31T00:00:00Z";
+
+        assert_eq!(want, render_table(&entries()));
+    }
+
+    #[test]
+    fn test_render_table_empty() {
+        assert_eq!("ID  TARGET  CLICKS  EXPIRES", render_table(&[]));
+    }
+
+    #[test]
+    fn test_display_entries_json() {
+        let mut result = Vec::new();
+        display_entries(&entries(), OutputFormat::Json, &mut result).unwrap();
+
+        let got: Vec<UrlEntry> = serde_json::from_slice(&result).unwrap();
+        assert_eq!(entries(), got);
+
+        let got = String::from_utf8(result).unwrap();
+        assert!(got.contains("\"id\": \"gh\""), "{}", got);
+        assert!(got.contains("\"clicks\": 12"), "{}", got);
+    }
+
+    fn stats() -> UrlStats {
+        UrlStats {
+            target: "https://github.com/tsauvajon".to_string(),
+            clicks: 12,
+            created: Some("2021-01-01T00:00:00Z".to_string()),
+            last_accessed: None,
+            expires: None,
+        }
+    }
+
+    #[test]
+    fn test_render_stats() {
+        let want = "\
+Clicks         12
+Target         https://github.com/tsauvajon
+Created        2021-01-01T00:00:00Z
+Last Accessed  -
+Expires        -";
+
+        assert_eq!(want, render_stats(&stats()));
+    }
+
+    #[test]
+    fn test_display_stats_json() {
+        let mut result = Vec::new();
+        display_stats(&stats(), OutputFormat::Json, &mut result).unwrap();
+
+        let got: UrlStats = serde_json::from_slice(&result).unwrap();
+        assert_eq!(stats(), got);
+    }
+
+    #[test]
+    fn test_display_entries_table() {
+        let mut result = Vec::new();
+        display_entries(&entries(), OutputFormat::Table, &mut result).unwrap();
+
+        let want = format!("{}\n", render_table(&entries()));
+        assert_eq!(want, String::from_utf8(result).unwrap());
     }
+}
+
+/// report prints the outcome of a check of `goto doctor`, with its details
+/// if it passed, and returns whether it passed.
+fn report(
+    writer: &mut impl std::io::Write,
+    name: &str,
+    result: Result<String, String>,
+) -> Result<bool, GoToError> {
+    let line = match &result {
+        Ok(details) if details.is_empty() => format!("{}: ✓ OK", name),
+        Ok(details) => format!("{}: ✓ OK ({})", name, details),
+        Err(reason) => format!("{}: ✗ FAIL: {}", name, reason),
+    };
+
+    writeln!(writer, "{}", line).map_err(|err| GoToError::CliError(err.to_string()))?;
+    Ok(result.is_ok())
+}
+
+/// doctor prints the config file, then checks that it is valid and that the
+/// API is reachable and healthy. It fails unless every check passed.
+async fn doctor(args: &Args, mut writer: impl std::io::Write) -> Result<(), GoToError> {
+    let write_err = |err: std::io::Error| GoToError::CliError(err.to_string());
+    let mut passed = true;
+
+    let filepath = config_path(args);
+    writeln!(writer, "Config file: {}", filepath.display()).map_err(write_err)?;
+
+    let config = match std::fs::read_to_string(&filepath) {
+        Err(err) => {
+            passed &= report(&mut writer, "Read the config file", Err(err.to_string()))?;
+            None
+        }
+        Ok(contents) => {
+            writeln!(writer, "{}", contents.trim_end()).map_err(write_err)?;
+
+            let format = ConfigFormat::from_path(&filepath);
+            let name = format!("Parse the config file as {}", format.name());
+            match format.parse(&contents) {
+                Ok(config) => {
+                    passed &= report(&mut writer, &name, Ok(String::new()))?;
+                    Some(config)
+                }
+                Err(err) => {
+                    passed &= report(&mut writer, &name, Err(err))?;
+                    None
+                }
+            }
+        }
+    };
+
+    // keep checking the API with the defaults
+    let config = config.unwrap_or_default();
+    let api_url = get_api_url(args, &config);
+    let timeout = Duration::from_secs(DOCTOR_TIMEOUT_SECS);
+
+    let http = http_client()?;
+    let reachable = check_target(&http, &api_url, timeout)
+        .await
+        .map(|status| format!("HTTP {}", status));
+    passed &= report(&mut writer, &format!("Reach {}", api_url), reachable)?;
+
+    let health = HttpClient::new(api_url, timeout)?
+        .health_check()
+        .await
+        .map(|health| format!("goto {}, {} shortened URLs", health.version, health.entries))
+        .map_err(|err| err.to_string());
+    passed &= report(&mut writer, "Check the health of the API", health)?;
+
+    if passed {
+        Ok(())
+    } else {
+        Err(GoToError::CliError("some checks failed".to_string()))
+    }
+}
+
+#[cfg(test)]
+mod doctor_tests {
+    use super::*;
+    use httpmock::{Method, MockServer};
+    use std::env::temp_dir;
+
+    fn args(config: &Path) -> Args {
+        Args::parse_from(["goto", "--config", config.to_str().unwrap(), "doctor"])
+    }
+
+    #[test]
+    fn test_report() {
+        let mut result = Vec::new();
+        assert!(report(&mut result, "Check", Ok(String::new())).unwrap());
+        assert!(report(&mut result, "Check", Ok("all good".to_string())).unwrap());
+        assert!(!report(&mut result, "Check", Err("oh no".to_string())).unwrap());
+
+        assert_eq!(
+            "Check: ✓ OK\nCheck: ✓ OK (all good)\nCheck: ✗ FAIL: oh no\n",
+            String::from_utf8(result).unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_doctor() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(Method::HEAD).path("/");
+
+            then.status(200);
+        });
+        server.mock(|when, then| {
+            when.method(Method::GET).path("/health");
+
+            then.status(200)
+                .body(r#"{"status": "ok", "version": "2.0.0", "entries": 3}"#);
+        });
+
+        let config = temp_dir().join("test_doctor_config.yml");
+        std::fs::write(&config, format!("api_url: {}\n", server.base_url())).unwrap();
+
+        let mut result = Vec::new();
+        let res = doctor(&args(&config), &mut result).await;
+        let result = String::from_utf8(result).unwrap();
+
+        assert_eq!(Ok(()), res, "{}", result);
+        assert_eq!(
+            format!(
+                "Config file: {config}\n\
+                 api_url: {api}\n\
+                 Parse the config file as YAML: ✓ OK\n\
+                 Reach {api}: ✓ OK (HTTP 200)\n\
+                 Check the health of the API: ✓ OK (goto 2.0.0, 3 shortened URLs)\n",
+                config = config.display(),
+                api = server.base_url()
+            ),
+            result
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_doctor_broken() {
+        let config = temp_dir().join("test_doctor_broken_config.toml");
+        std::fs::write(&config, "api_url: not toml\n").unwrap();
+        // nothing listens on port 1
+        let args = Args::parse_from([
+            "goto",
+            "--config",
+            config.to_str().unwrap(),
+            "--api",
+            "http://127.0.0.1:1",
+            "doctor",
+        ]);
+
+        let mut result = Vec::new();
+        let res = doctor(&args, &mut result).await;
+        let result = String::from_utf8(result).unwrap();
+
+        assert_eq!(
+            Err(GoToError::CliError("some checks failed".to_string())),
+            res
+        );
+        assert!(
+            result.contains("Parse the config file as TOML: ✗ FAIL: "),
+            "{}",
+            result
+        );
+        assert!(
+            result.contains("Reach http://127.0.0.1:1: ✗ FAIL: "),
+            "{}",
+            result
+        );
+        assert!(
+            result.contains("Check the health of the API: ✗ FAIL: "),
+            "{}",
+            result
+        );
+    }
+}
+
+/// ping calls the health endpoint of the API `count` times, waiting
+/// `interval` between calls, and prints how long each call took followed by
+/// the minimum, average and maximum. It stops at the first failed call.
+async fn ping<C: Client>(
+    client: &C,
+    count: u64,
+    interval: Duration,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let write_err = |err: std::io::Error| GoToError::CliError(err.to_string());
+    let mut times = Vec::new();
+
+    for i in 1..=count {
+        if i > 1 {
+            tokio::time::sleep(interval).await;
+        }
+
+        let start = tokio::time::Instant::now();
+        client.health_check().await?;
+        let elapsed = start.elapsed();
+
+        writeln!(writer, "Ping {}: {}ms", i, elapsed.as_millis()).map_err(write_err)?;
+        times.push(elapsed);
+    }
+
+    writeln!(writer, "{}", render_ping_stats(&times)).map_err(write_err)
+}
+
+/// render_ping_stats formats the minimum, average and maximum of `times`.
+fn render_ping_stats(times: &[Duration]) -> String {
+    let min = times.iter().min().copied().unwrap_or_default();
+    let max = times.iter().max().copied().unwrap_or_default();
+    let avg = match times.len() {
+        0 => Duration::ZERO,
+        len => times.iter().sum::<Duration>() / len as u32,
+    };
+
+    format!(
+        "Stats: min={}ms avg={}ms max={}ms",
+        min.as_millis(),
+        avg.as_millis(),
+        max.as_millis()
+    )
+}
+
+#[cfg(test)]
+mod ping_tests {
+    use super::*;
+    use httpmock::{Method, MockServer};
+
+    #[test]
+    fn test_render_ping_stats() {
+        let times = [12, 8, 10].map(Duration::from_millis);
+        assert_eq!(
+            "Stats: min=8ms avg=10ms max=12ms",
+            render_ping_stats(&times)
+        );
+        assert_eq!("Stats: min=0ms avg=0ms max=0ms", render_ping_stats(&[]));
+    }
+
+    #[test]
+    fn test_ping_args() {
+        let args = Args::parse_from(["goto", "ping"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Ping {
+                count: 4,
+                interval: 1000
+            })
+        ));
+
+        assert!(Args::try_parse_from(["goto", "ping", "--count", "0"]).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn test_ping() {
+        let server = MockServer::start();
+        let health = server.mock(|when, then| {
+            when.method(Method::GET).path("/health");
+
+            then.status(200)
+                .delay(Duration::from_millis(5))
+                .body(r#"{"status": "ok", "version": "2.0.0", "entries": 3}"#);
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_secs(5)).unwrap();
+        let mut result = Vec::new();
+        let res = ping(&client, 3, Duration::from_millis(1), &mut result).await;
+        let result = String::from_utf8(result).unwrap();
+
+        assert_eq!(Ok(()), res, "{}", result);
+        health.assert_hits(3);
+
+        let lines: Vec<&str> = result.lines().collect();
+        assert_eq!(4, lines.len(), "{}", result);
+        for (i, line) in lines[..3].iter().enumerate() {
+            let millis = line
+                .strip_prefix(&format!("Ping {}: ", i + 1))
+                .and_then(|line| line.strip_suffix("ms"))
+                .and_then(|millis| millis.parse::<u64>().ok())
+                .unwrap_or_else(|| panic!("unexpected line {:?}", line));
+            assert!((5..5000).contains(&millis), "{}", result);
+        }
+        assert!(lines[3].starts_with("Stats: min="), "{}", result);
+    }
+
+    #[actix_rt::test]
+    async fn test_ping_unhealthy() {
+        let server = MockServer::start();
+        let health = server.mock(|when, then| {
+            when.method(Method::GET).path("/health");
+
+            then.status(500).body("oh no");
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_secs(5)).unwrap();
+        let mut result = Vec::new();
+        let res = ping(&client, 3, Duration::from_millis(1), &mut result).await;
+
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 500,
+                body: "oh no".to_string()
+            }),
+            res
+        );
+        health.assert_hits(1);
+        assert!(result.is_empty());
+    }
+}
+
+/// generate_completions returns the completion script of the CLI for `shell`.
+fn generate_completions(shell: Shell) -> String {
+    let mut buf = Vec::new();
+    clap_complete::generate(shell, &mut Args::command(), "goto", &mut buf);
+    String::from_utf8(buf).expect("completions are valid utf8")
+}
+
+#[test]
+fn test_args_definition() {
+    Args::command().debug_assert();
+}
+
+#[test]
+fn test_generate_completions() {
+    for shell in &[Shell::Bash, Shell::Zsh, Shell::Fish] {
+        let got = generate_completions(*shell);
+        assert!(got.contains("delete"), "{}", got);
+        assert!(got.contains("list"), "{}", got);
+        assert!(got.contains("stats"), "{}", got);
+        assert!(got.contains("completions"), "{}", got);
+        assert!(got.contains("check"), "{}", got);
+        assert!(got.contains("verify"), "{}", got);
+        assert!(got.contains("import"), "{}", got);
+        assert!(got.contains("export"), "{}", got);
+        assert!(got.contains("no-open-browser"), "{}", got);
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+fn open_location(loc: &str, browser: bool) {
+    if browser {
+        webbrowser::open(loc).unwrap();
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Default, PartialEq, Debug)]
+struct Config {
+    api_url: Option<String>,
+    silent: Option<bool>,
+    no_browser: Option<bool>,
+    timeout: Option<u64>,
+    max_retries: Option<u32>,
+    retry_base_delay_ms: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    admin_password: Option<String>,
+}
+
+impl Config {
+    /// Settings that can be read and changed with `goto config`.
+    const KEYS: &'static [&'static str] = &[
+        "api_url",
+        "silent",
+        "no_browser",
+        "timeout",
+        "max_retries",
+        "retry_base_delay_ms",
+        "admin_password",
+    ];
+
+    /// get returns the value of a setting, or None if it isn't set.
+    fn get(&self, key: &str) -> Result<Option<String>, GoToError> {
+        let value = match key {
+            "api_url" => self.api_url.clone(),
+            "silent" => self.silent.map(|value| value.to_string()),
+            "no_browser" => self.no_browser.map(|value| value.to_string()),
+            "timeout" => self.timeout.map(|value| value.to_string()),
+            "max_retries" => self.max_retries.map(|value| value.to_string()),
+            "retry_base_delay_ms" => self.retry_base_delay_ms.map(|value| value.to_string()),
+            "admin_password" => self.admin_password.clone(),
+            _ => return Err(unknown_config_key(key)),
+        };
+        Ok(value)
+    }
+
+    /// set changes a setting, parsing `value` to the type of the setting.
+    fn set(&mut self, key: &str, value: &str) -> Result<(), GoToError> {
+        fn parse<T: std::str::FromStr>(key: &str, value: &str) -> Result<Option<T>, GoToError>
+        where
+            T::Err: std::fmt::Display,
+        {
+            value.parse().map(Some).map_err(|err| {
+                GoToError::CliError(format!("invalid value {} for {}: {}", value, key, err))
+            })
+        }
+
+        match key {
+            "api_url" => self.api_url = Some(value.to_string()),
+            "silent" => self.silent = parse(key, value)?,
+            "no_browser" => self.no_browser = parse(key, value)?,
+            "timeout" => self.timeout = parse(key, value)?,
+            "max_retries" => self.max_retries = parse(key, value)?,
+            "retry_base_delay_ms" => self.retry_base_delay_ms = parse(key, value)?,
+            "admin_password" => self.admin_password = Some(value.to_string()),
+            _ => return Err(unknown_config_key(key)),
+        }
+        Ok(())
+    }
+}
+
+fn unknown_config_key(key: &str) -> GoToError {
+    GoToError::CliError(format!(
+        "unknown setting {}, available settings: {}",
+        key,
+        Config::KEYS.join(", ")
+    ))
+}
+
+/// get_setting runs `goto config get`: it prints a setting of the config
+/// file, or of the default config if there is no config file yet.
+fn get_setting(
+    filepath: &Path,
+    key: &str,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let config = read_config(filepath)?.unwrap_or_else(default_config);
+
+    match config.get(key)? {
+        Some(value) => {
+            writeln!(writer, "{}", value).map_err(|err| GoToError::CliError(err.to_string()))
+        }
+        None => Err(GoToError::CliError(format!("{} is not set", key))),
+    }
+}
+
+/// set_setting runs `goto config set`: it changes a setting and writes the
+/// config file back.
+fn set_setting(filepath: &Path, key: &str, value: &str) -> Result<(), GoToError> {
+    let mut config = open_or_create_config(filepath)?;
+    config.set(key, value)?;
+
+    let data = ConfigFormat::from_path(filepath)
+        .serialise(&config)
+        .map_err(|err| GoToError::CliError(format!("serialise config: {}", err)))?;
+    std::fs::write(filepath, data)
+        .map_err(|err| GoToError::CliError(format!("write config file: {}", err)))
+}
+
+/// ConfigSource tells where the value of a setting comes from.
+#[derive(serde::Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+enum ConfigSource {
+    Arg,
+    ConfigFile,
+    EnvVar,
+    Default,
+}
+
+impl std::fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ConfigSource::Arg => write!(f, "args"),
+            ConfigSource::ConfigFile => write!(f, "config file"),
+            ConfigSource::EnvVar => write!(f, "env var"),
+            ConfigSource::Default => write!(f, "default"),
+        }
+    }
+}
+
+/// ResolvedConfig is the value of a setting in use, merged from the args, the
+/// environment variables and the config file.
+#[derive(serde::Serialize, Clone, Debug, PartialEq)]
+struct ResolvedConfig {
+    field: String,
+    value: String,
+    source: ConfigSource,
+}
+
+/// resolve_config returns the settings in use: the args and their environment
+/// variables take precedence over the config file, which takes precedence
+/// over the defaults.
+fn resolve_config(args: &Args, matches: &ArgMatches, config: &Config) -> Vec<ResolvedConfig> {
+    fn resolve<T: ToString>(
+        matches: &ArgMatches,
+        field: &str,
+        arg: Option<T>,
+        config: Option<T>,
+        default: T,
+    ) -> ResolvedConfig {
+        let arg_source = match matches.value_source(field) {
+            Some(ValueSource::CommandLine) => Some(ConfigSource::Arg),
+            Some(ValueSource::EnvVariable) => Some(ConfigSource::EnvVar),
+            _ => None,
+        };
+
+        let (value, source) = match (arg_source, arg, config) {
+            (Some(source), Some(value), _) => (value, source),
+            (_, _, Some(value)) => (value, ConfigSource::ConfigFile),
+            _ => (default, ConfigSource::Default),
+        };
+        ResolvedConfig {
+            field: field.to_string(),
+            value: value.to_string(),
+            source,
+        }
+    }
+
+    vec![
+        resolve(
+            matches,
+            "api_url",
+            args.api_url.clone(),
+            config.api_url.clone(),
+            DEFAULT_API_URL.to_string(),
+        ),
+        resolve(matches, "silent", Some(args.silent), config.silent, false),
+        resolve(
+            matches,
+            "no_browser",
+            Some(args.no_browser),
+            config.no_browser,
+            false,
+        ),
+        resolve(
+            matches,
+            "timeout",
+            args.timeout,
+            config.timeout,
+            DEFAULT_TIMEOUT_SECS,
+        ),
+        resolve(
+            matches,
+            "max_retries",
+            args.max_retries,
+            config.max_retries,
+            DEFAULT_MAX_RETRIES,
+        ),
+        resolve(
+            matches,
+            "retry_base_delay_ms",
+            args.retry_base_delay_ms,
+            config.retry_base_delay_ms,
+            DEFAULT_RETRY_BASE_DELAY_MS,
+        ),
+    ]
+}
+
+/// render_resolved_config formats the settings in use.
+fn render_resolved_config(
+    resolved: &[ResolvedConfig],
+    format: ShowFormat,
+) -> Result<String, GoToError> {
+    match format {
+        ShowFormat::Table => {
+            let rows: Vec<[String; 3]> = resolved
+                .iter()
+                .map(|setting| {
+                    [
+                        setting.field.to_owned(),
+                        setting.value.to_owned(),
+                        setting.source.to_string(),
+                    ]
+                })
+                .collect();
+            Ok(render_columns(["SETTING", "VALUE", "SOURCE"], &rows).join("\n"))
+        }
+        ShowFormat::Json => serde_json::to_string_pretty(resolved)
+            .map_err(|err| GoToError::CliError(format!("serialise config: {}", err))),
+        ShowFormat::Yaml => serde_yaml::to_string(resolved)
+            .map(|yaml| yaml.trim_end().to_string())
+            .map_err(|err| GoToError::CliError(format!("serialise config: {}", err))),
+    }
+}
+
+/// show_config runs `goto config show`: it prints the settings in use.
+fn show_config(
+    args: &Args,
+    matches: &ArgMatches,
+    format: ShowFormat,
+    mut writer: impl std::io::Write,
+) -> Result<(), GoToError> {
+    let config = read_config(&config_path(args))?.unwrap_or_default();
+    let resolved = resolve_config(args, matches, &config);
+    let output = render_resolved_config(&resolved, format)?;
+    writeln!(writer, "{}", output).map_err(|err| GoToError::CliError(err.to_string()))
+}
+
+/// ConfigFormat is the format of the config file, picked from its extension:
+/// `.toml` files are TOML documents, any other file is a YAML document.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum ConfigFormat {
+    Yaml,
+    Toml,
+}
+
+impl ConfigFormat {
+    fn name(self) -> &'static str {
+        match self {
+            ConfigFormat::Yaml => "YAML",
+            ConfigFormat::Toml => "TOML",
+        }
+    }
+
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("toml") => ConfigFormat::Toml,
+            _ => ConfigFormat::Yaml,
+        }
+    }
+
+    fn parse(self, contents: &str) -> Result<Config, String> {
+        match self {
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(|err| err.to_string()),
+            ConfigFormat::Toml => toml::from_str(contents).map_err(|err| err.to_string()),
+        }
+    }
+
+    fn serialise(self, config: &Config) -> Result<String, String> {
+        match self {
+            ConfigFormat::Yaml => serde_yaml::to_string(config).map_err(|err| err.to_string()),
+            ConfigFormat::Toml => toml::to_string(config).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// read_config parses the config file without creating it: it returns None
+/// if the file doesn't exist or is empty.
+fn read_config(filepath: &Path) -> Result<Option<Config>, GoToError> {
+    let contents = match std::fs::read_to_string(filepath) {
+        Ok(contents) => contents,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(GoToError::CliError(format!("read config file: {}", err))),
+    };
+    if contents.is_empty() {
+        return Ok(None);
+    }
+
+    ConfigFormat::from_path(filepath)
+        .parse(&contents)
+        .map(Some)
+        .map_err(|err| GoToError::CliError(format!("parse config data: {}", err)))
+}
+
+/// default_config is the config written to new config files.
+fn default_config() -> Config {
+    Config {
+        silent: Some(false),
+        no_browser: Some(false),
+        api_url: Some(DEFAULT_API_URL.to_string()),
+        timeout: Some(DEFAULT_TIMEOUT_SECS),
+        max_retries: Some(DEFAULT_MAX_RETRIES),
+        retry_base_delay_ms: Some(DEFAULT_RETRY_BASE_DELAY_MS),
+        admin_password: None,
+    }
+}
+
+fn open_or_create_config(filepath: &Path) -> Result<Config, GoToError> {
+    let _ = std::fs::create_dir_all(filepath.parent().unwrap());
+
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .read(true)
+        .truncate(false)
+        .open(filepath)
+        .map_err(|err| GoToError::CliError(format!("open config file: {}", err)))?;
+
+    read_or_write_config(file, ConfigFormat::from_path(filepath))
+}
+
+fn read_or_write_config(
+    mut file: impl std::io::Read + std::io::Write,
+    format: ConfigFormat,
+) -> Result<Config, GoToError> {
+    let mut buf = String::new();
+    match file.read_to_string(&mut buf) {
+        Err(err) => Err(GoToError::CliError(format!("read config file: {}", err))),
+        Ok(len) => {
+            if len == 0 {
+                let default = default_config();
+
+                let data = format.serialise(&default).map_err(|err| {
+                    GoToError::CliError(format!("serialise default config: {}", err))
+                })?;
+                file.write_all(data.as_bytes())
+                    .map_err(|err| GoToError::CliError(format!("write default config: {}", err)))?;
+
+                Ok(default)
+            } else {
+                format
+                    .parse(&buf)
+                    .map_err(|err| GoToError::CliError(format!("parse config data: {}", err)))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod config_tests {
+    use std::env::temp_dir;
+    use std::fs::File;
+    use std::io::{Cursor, Error, Read, Result, Write};
+
+    use super::*;
+
+    #[test]
+    fn test_create_config_when_missing() {
+        let mut data: Vec<u8> = Vec::new();
+        let mut mock_file = Cursor::new(&mut data);
+
+        read_or_write_config(&mut mock_file, ConfigFormat::Yaml).unwrap();
+
+        let got = String::from_utf8(data).unwrap();
+        assert!(got.contains("silent: false"), "{}", got);
+        assert!(got.contains("no_browser: false"), "{}", got);
+        assert!(got.contains("api_url: \"http://"), "{}", got);
+        assert!(got.contains("timeout: 30"), "{}", got);
+    }
+
+    #[test]
+    fn test_read_existing_config() {
+        let mut data: Vec<u8> = Vec::from("silent: true\napi_url: \"hello\"");
+        let mut mock_file = Cursor::new(&mut data);
+
+        let got = read_or_write_config(&mut mock_file, ConfigFormat::Yaml).unwrap();
+
+        assert_eq!(Some(true), got.silent);
+        assert_eq!(None, got.no_browser);
+        assert_eq!(Some("hello".to_string()), got.api_url);
+        assert_eq!(
+            "silent: true\napi_url: \"hello\"".to_string(),
+            String::from_utf8(data).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_create_toml_config_when_missing() {
+        let mut data: Vec<u8> = Vec::new();
+        let mut mock_file = Cursor::new(&mut data);
+
+        let created = read_or_write_config(&mut mock_file, ConfigFormat::Toml).unwrap();
+
+        let got = String::from_utf8(data).unwrap();
+        assert!(got.contains("silent = false"), "{}", got);
+        assert!(got.contains("no_browser = false"), "{}", got);
+        assert!(got.contains("api_url = \"http://"), "{}", got);
+        assert!(got.contains("timeout = 30"), "{}", got);
+
+        let mut mock_file = Cursor::new(got.into_bytes());
+        let read = read_or_write_config(&mut mock_file, ConfigFormat::Toml).unwrap();
+        assert_eq!(created, read);
+    }
+
+    #[test]
+    fn test_read_existing_toml_config() {
+        let mut data: Vec<u8> = Vec::from("silent = true\napi_url = \"hello\"\n");
+        let mut mock_file = Cursor::new(&mut data);
+
+        let got = read_or_write_config(&mut mock_file, ConfigFormat::Toml).unwrap();
+
+        assert_eq!(Some(true), got.silent);
+        assert_eq!(None, got.no_browser);
+        assert_eq!(Some("hello".to_string()), got.api_url);
+    }
+
+    #[test]
+    fn test_config_format() {
+        assert_eq!(
+            ConfigFormat::Toml,
+            ConfigFormat::from_path(Path::new("config.toml"))
+        );
+        assert_eq!(
+            ConfigFormat::Yaml,
+            ConfigFormat::from_path(Path::new("config.yml"))
+        );
+        assert_eq!(
+            ConfigFormat::Yaml,
+            ConfigFormat::from_path(Path::new("config"))
+        );
+    }
+
+    #[test]
+    fn test_open_toml_config() {
+        let mut filepath = temp_dir();
+        filepath.push("test_open_toml_config.toml");
+
+        let mut file = File::create(&filepath).unwrap();
+        file.write_all(b"api_url = \"http://hello.world\"\nno_browser = true\n")
+            .unwrap();
+
+        let got = open_or_create_config(&filepath).unwrap();
+        assert_eq!(Some("http://hello.world".to_string()), got.api_url);
+        assert_eq!(Some(true), got.no_browser);
+    }
+
+    #[test]
+    fn test_create_config() {
+        let mut filepath = temp_dir();
+        filepath.push("test_create_config.yml");
+
+        let got = open_or_create_config(&filepath);
+        assert!(got.is_ok());
+
+        let mut file = File::open(&filepath).unwrap();
+        let mut content = String::new();
+        file.read_to_string(&mut content).unwrap();
+
+        assert!(content.contains("api_url"));
+    }
+
+    #[test]
+    fn test_open_config() {
+        let mut filepath = temp_dir();
+        filepath.push("test_existing_config.yml");
+
+        let mut file = File::create(&filepath).unwrap();
+        file.write_all(b"api_url: \"http://hello.world\"\n")
+            .unwrap();
+
+        let got = open_or_create_config(&filepath).unwrap();
+        assert_eq!(Some("http://hello.world".to_string()), got.api_url);
+    }
+
+    #[test]
+    fn test_open_config_invalid_data() {
+        let mut filepath = temp_dir();
+        filepath.push("contains_invalid_data");
+
+        let mut file = File::create(&filepath).unwrap();
+        file.write_all(b"what is this... it doesn't look like valid YAML!{ }}}} P{{")
+            .unwrap();
+
+        let got = open_or_create_config(&filepath);
+        assert!(got.is_err());
+
+        let err = got.err().unwrap();
+        assert!(
+            format!("{:?}", err).contains("parse config data:"),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_open_config_wrong_file() {
+        let mut filepath = temp_dir();
+        filepath.push("{}///////\\\\\\////");
+
+        let got = open_or_create_config(&filepath);
+        assert!(got.is_err());
+
+        let err = got.err().unwrap();
+        assert!(
+            format!("{:?}", err).contains("open config file:"),
+            "{:?}",
+            err
+        );
+    }
+
+    struct RWMockCantRead {}
+
+    impl std::io::Read for RWMockCantRead {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+            Err(Error::other("oh no!"))
+        }
+    }
+
+    impl std::io::Write for RWMockCantRead {
+        fn write(&mut self, _buf: &[u8]) -> Result<usize> {
+            todo!()
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            todo!()
+        }
+    }
+
+    #[test]
+    fn test_cannot_read_config() {
+        let mut mock_file = RWMockCantRead {};
+
+        let got = read_or_write_config(&mut mock_file, ConfigFormat::Yaml);
+        let want = Err(GoToError::CliError("read config file: oh no!".to_string()));
+        assert_eq!(want, got);
+    }
+
+    struct RWMockCantWrite {}
+
+    impl std::io::Read for RWMockCantWrite {
+        fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+            Ok(0)
+        }
+    }
+
+    impl std::io::Write for RWMockCantWrite {
+        fn write(&mut self, _buf: &[u8]) -> Result<usize> {
+            Err(Error::other("that went terribly wrong!"))
+        }
+
+        fn flush(&mut self) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_cannot_write_config() {
+        let mut mock_file = RWMockCantWrite {};
+
+        let got = read_or_write_config(&mut mock_file, ConfigFormat::Yaml);
+        let want = Err(GoToError::CliError(
+            "write default config: that went terribly wrong!".to_string(),
+        ));
+        assert_eq!(want, got);
+    }
+}
+
+#[cfg(test)]
+mod cant_read_config_tests {}
+
+#[cfg(test)]
+mod configure_tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    #[test]
+    fn test_configure_set() {
+        let mut filepath = temp_dir();
+        filepath.push("test_configure_set.yml");
+        std::fs::write(
+            &filepath,
+            "api_url: \"http://hello.world\"\nsilent: true\ntimeout: 5\n",
+        )
+        .unwrap();
+
+        let set = |key: &str, value: &str| set_setting(&filepath, key, value);
+        set("api_url", "http://myserver:8080").unwrap();
+        set("no_browser", "true").unwrap();
+
+        let got = open_or_create_config(&filepath).unwrap();
+        let want = Config {
+            api_url: Some("http://myserver:8080".to_string()),
+            silent: Some(true),
+            no_browser: Some(true),
+            timeout: Some(5),
+            ..Config::default()
+        };
+        assert_eq!(want, got);
+
+        let err = set("silent", "yes").unwrap_err();
+        assert_eq!(
+            "CLI error: invalid value yes for silent: provided string was not `true` or `false`",
+            err.to_string()
+        );
+        let err = set("force_replace", "true").unwrap_err();
+        assert_eq!(
+            "CLI error: unknown setting force_replace, available settings: api_url, silent, no_browser, timeout, max_retries, retry_base_delay_ms, admin_password",
+            err.to_string()
+        );
+        assert_eq!(want, open_or_create_config(&filepath).unwrap());
+    }
+
+    #[test]
+    fn test_configure_get() {
+        let mut filepath = temp_dir();
+        filepath.push("test_configure_get.toml");
+        std::fs::write(&filepath, "api_url = \"http://hello.world\"\n").unwrap();
+
+        let get = |key: &str| {
+            let mut output = Vec::new();
+            get_setting(&filepath, key, &mut output).map(|_| String::from_utf8(output).unwrap())
+        };
+        assert_eq!(Ok("http://hello.world\n".to_string()), get("api_url"));
+        assert_eq!(
+            "CLI error: silent is not set",
+            get("silent").unwrap_err().to_string()
+        );
+        assert!(get("nope").is_err());
+
+        // reading a missing config file gives the defaults, without creating it
+        std::fs::remove_file(&filepath).unwrap();
+        assert_eq!(Ok(format!("{}\n", DEFAULT_API_URL)), get("api_url"));
+        assert!(!filepath.exists());
+    }
+
+    #[test]
+    fn test_show_config_missing_file() {
+        let mut filepath = temp_dir();
+        filepath.push("test_show_config_missing_file.yml");
+        let _ = std::fs::remove_file(&filepath);
+
+        let matches = Args::command()
+            .try_get_matches_from([
+                "goto",
+                "--config",
+                filepath.to_str().unwrap(),
+                "config",
+                "show",
+            ])
+            .unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let mut output = Vec::new();
+        show_config(&args, &matches, ShowFormat::Json, &mut output).unwrap();
+
+        let got: serde_json::Value = serde_json::from_slice(&output).unwrap();
+        assert_eq!("default", got[0]["source"], "{}", got);
+        assert!(!filepath.exists());
+    }
+
+    #[test]
+    fn test_resolve_config() {
+        let matches = Args::command()
+            .try_get_matches_from(["goto", "--timeout", "3", "--silent", "config", "show"])
+            .unwrap();
+        let args = Args::from_arg_matches(&matches).unwrap();
+        let config = Config {
+            api_url: Some("http://hello.world".to_string()),
+            timeout: Some(5),
+            no_browser: Some(true),
+            ..Config::default()
+        };
+
+        let got: Vec<(String, String, ConfigSource)> = resolve_config(&args, &matches, &config)
+            .into_iter()
+            .map(|setting| (setting.field, setting.value, setting.source))
+            .collect();
+        let want = [
+            ("api_url", "http://hello.world", ConfigSource::ConfigFile),
+            ("silent", "true", ConfigSource::Arg),
+            ("no_browser", "true", ConfigSource::ConfigFile),
+            ("timeout", "3", ConfigSource::Arg),
+            ("max_retries", "3", ConfigSource::Default),
+            ("retry_base_delay_ms", "100", ConfigSource::Default),
+        ];
+        let want: Vec<(String, String, ConfigSource)> = want
+            .iter()
+            .map(|(field, value, source)| (field.to_string(), value.to_string(), *source))
+            .collect();
+        assert_eq!(want, got);
+    }
+
+    #[test]
+    fn test_render_resolved_config() {
+        let resolved = vec![
+            ResolvedConfig {
+                field: "api_url".to_string(),
+                value: "http://hello.world".to_string(),
+                source: ConfigSource::EnvVar,
+            },
+            ResolvedConfig {
+                field: "timeout".to_string(),
+                value: "30".to_string(),
+                source: ConfigSource::Default,
+            },
+        ];
+
+        assert_eq!(
+            "SETTING  VALUE               SOURCE\n\
+             api_url  http://hello.world  env var\n\
+             timeout  30                  default",
+            render_resolved_config(&resolved, ShowFormat::Table).unwrap()
+        );
+        assert_eq!(
+            "---\n\
+             - field: api_url\n  value: \"http://hello.world\"\n  source: env_var\n\
+             - field: timeout\n  value: \"30\"\n  source: default",
+            render_resolved_config(&resolved, ShowFormat::Yaml).unwrap()
+        );
+        let json = render_resolved_config(&resolved, ShowFormat::Json).unwrap();
+        let got: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            serde_json::json!([
+                {"field": "api_url", "value": "http://hello.world", "source": "env_var"},
+                {"field": "timeout", "value": "30", "source": "default"},
+            ]),
+            got
+        );
+    }
+
+    #[test]
+    fn test_parse_config_command() {
+        let args = Args::parse_from(["goto", "config", "set", "api_url", "http://myserver:8080"]);
+        assert!(matches!(
+            args.command,
+            Some(Command::Config {
+                action: ConfigAction::Set { key, value }
+            }) if key == "api_url" && value == "http://myserver:8080"
+        ));
+    }
+}
+
+#[tokio::main]
+#[cfg(not(tarpaulin_include))]
+async fn main() {
+    // returning the error from main would print its Debug representation
+    if let Err(err) = run().await {
+        eprintln!("{}", err);
+        std::process::exit(1);
+    }
+}
+
+#[cfg(not(tarpaulin_include))]
+async fn run() -> Result<(), GoToError> {
+    let matches = Args::command().get_matches();
+    let args = Args::from_arg_matches(&matches).unwrap_or_else(|err| err.exit());
+    if args.shorturl.is_none() && args.command.is_none() {
+        return Err(GoToError::CliError("missing shortened URL".to_string()));
+    }
+
+    if let Some(Command::Doctor) = args.command {
+        return doctor(&args, std::io::stdout()).await;
+    }
+
+    if let Some(Command::Config { action }) = &args.command {
+        return match action {
+            ConfigAction::Show { format } => {
+                show_config(&args, &matches, *format, std::io::stdout())
+            }
+            ConfigAction::Get { key } => get_setting(&config_path(&args), key, std::io::stdout()),
+            ConfigAction::Set { key, value } => set_setting(&config_path(&args), key, value),
+        };
+    }
+
+    let config = open_or_create_config(&config_path(&args))?;
+
+    let options = CliOptions::new(&args, &config);
+    let verbose = options.verbose;
+    let api_url = get_api_url(&args, &config);
+    let timeout = get_timeout(&args, &config);
+    let (max_retries, retry_base_delay) = get_retries(&args, &config);
+
+    let cli = Cli {
+        options,
+        client: HttpClient::new(api_url, timeout)?
+            .with_retries(max_retries, retry_base_delay)
+            .with_admin_password(get_admin_password(&args, &config)),
+    };
+
+    if let Some(output) = cli.run().await? {
+        display_output(&output, verbose, &mut std::io::stdout());
+    }
+
+    Ok(())
+}
+
+/// config_path returns the path of the config file given in the args, or
+/// `~/.goto/config.yml`.
+fn config_path(args: &Args) -> PathBuf {
+    match &args.config {
+        Some(filepath) => filepath.to_owned(),
+        None => {
+            let mut filepath = home::home_dir().unwrap();
+            filepath.push(".goto");
+            filepath.push("config.yml");
+            filepath
+        }
+    }
+}
+
+fn get_api_url(args: &Args, config: &Config) -> String {
+    match &args.api_url {
+        Some(api_url) => api_url.to_owned(),
+        None => match config.api_url.to_owned() {
+            Some(api_url) => api_url,
+            None => DEFAULT_API_URL.to_string(),
+        },
+    }
+}
+
+/// get_timeout returns how long to wait for the API: the timeout given in the
+/// args, then the one in the config, then `DEFAULT_TIMEOUT_SECS`.
+fn get_timeout(args: &Args, config: &Config) -> Duration {
+    let timeout = args
+        .timeout
+        .or(config.timeout)
+        .unwrap_or(DEFAULT_TIMEOUT_SECS);
+
+    Duration::from_secs(timeout)
+}
+
+/// get_retries returns how many times to retry transient errors, and how long
+/// to wait before the first retry. The args take precedence over the config.
+fn get_retries(args: &Args, config: &Config) -> (u32, Duration) {
+    let max_retries = args
+        .max_retries
+        .or(config.max_retries)
+        .unwrap_or(DEFAULT_MAX_RETRIES);
+    let base_delay_ms = args
+        .retry_base_delay_ms
+        .or(config.retry_base_delay_ms)
+        .unwrap_or(DEFAULT_RETRY_BASE_DELAY_MS);
+
+    (max_retries, Duration::from_millis(base_delay_ms))
+}
+
+/// get_admin_password returns the password of the admin user of the API, from
+/// the args or else the config, if any.
+fn get_admin_password(args: &Args, config: &Config) -> Option<String> {
+    args.admin_password
+        .clone()
+        .or_else(|| config.admin_password.clone())
+}
+
+#[test]
+fn test_get_api_url() {
+    let mut args = Args {
+        shorturl: None,
+        target: None,
+        api_url: None,
+        config: None,
+        silent: false,
+        no_browser: false,
+        timeout: None,
+        max_retries: None,
+        retry_base_delay_ms: None,
+        admin_password: None,
+        max_clicks: None,
+        command: None,
+    };
+
+    let mut config = Config {
+        api_url: None,
+        silent: None,
+        no_browser: None,
+        timeout: None,
+        max_retries: None,
+        retry_base_delay_ms: None,
+        admin_password: None,
+    };
+
+    // default
+    args.api_url = None;
+    config.api_url = None;
+    let got = get_api_url(&args, &config);
+    assert_eq!(DEFAULT_API_URL, got);
+
+    // both args and config agree
+    args.api_url = Some("a".to_string());
+    config.api_url = Some("a".to_string());
+    let got = get_api_url(&args, &config);
+    assert_eq!("a".to_string(), got);
+
+    // args take precendence over config
+    args.api_url = Some("a".to_string());
+    config.api_url = Some("b".to_string());
+    let got = get_api_url(&args, &config);
+    assert_eq!("a".to_string(), got);
+
+    // only args
+    args.api_url = Some("a".to_string());
+    config.api_url = None;
+    let got = get_api_url(&args, &config);
+    assert_eq!("a".to_string(), got);
+
+    // only config
+    args.api_url = None;
+    config.api_url = Some("a".to_string());
+    let got = get_api_url(&args, &config);
+    assert_eq!("a".to_string(), got);
+}
+
+#[test]
+fn test_get_timeout() {
+    let mut args = Args {
+        shorturl: None,
+        target: None,
+        api_url: None,
+        config: None,
+        silent: false,
+        no_browser: false,
+        timeout: None,
+        max_retries: None,
+        retry_base_delay_ms: None,
+        admin_password: None,
+        max_clicks: None,
+        command: None,
+    };
+
+    let mut config = Config {
+        api_url: None,
+        silent: None,
+        no_browser: None,
+        timeout: None,
+        max_retries: None,
+        retry_base_delay_ms: None,
+        admin_password: None,
+    };
+
+    // default
+    let got = get_timeout(&args, &config);
+    assert_eq!(Duration::from_secs(DEFAULT_TIMEOUT_SECS), got);
+
+    // only config
+    config.timeout = Some(5);
+    let got = get_timeout(&args, &config);
+    assert_eq!(Duration::from_secs(5), got);
+
+    // args take precedence over config
+    args.timeout = Some(1);
+    let got = get_timeout(&args, &config);
+    assert_eq!(Duration::from_secs(1), got);
+}
+
+#[test]
+fn test_get_retries() {
+    let mut args = Args {
+        shorturl: None,
+        target: None,
+        api_url: None,
+        config: None,
+        silent: false,
+        no_browser: false,
+        timeout: None,
+        max_retries: None,
+        retry_base_delay_ms: None,
+        admin_password: None,
+        max_clicks: None,
+        command: None,
+    };
+
+    let mut config = Config {
+        api_url: None,
+        silent: None,
+        no_browser: None,
+        timeout: None,
+        max_retries: None,
+        retry_base_delay_ms: None,
+        admin_password: None,
+    };
+
+    // default
+    let got = get_retries(&args, &config);
+    assert_eq!(
+        (
+            DEFAULT_MAX_RETRIES,
+            Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS)
+        ),
+        got
+    );
+
+    // only config
+    config.max_retries = Some(5);
+    config.retry_base_delay_ms = Some(10);
+    let got = get_retries(&args, &config);
+    assert_eq!((5, Duration::from_millis(10)), got);
+
+    // args take precedence over config
+    args.max_retries = Some(0);
+    args.retry_base_delay_ms = Some(1);
+    let got = get_retries(&args, &config);
+    assert_eq!((0, Duration::from_millis(1)), got);
+}
+
+#[async_trait]
+trait Client {
+    async fn create_new(
+        &self,
+        shorturl: String,
+        target: String,
+        max_clicks: Option<u64>,
+    ) -> Result<String, GoToError>;
+
+    async fn get_long_url(&self, shorturl: String) -> Result<String, GoToError>;
+
+    /// resolve_url reads the target of a shortened URL without following
+    /// it, so it isn't counted as a click nor shows a preview.
+    async fn resolve_url(&self, shorturl: String) -> Result<String, GoToError>;
+
+    async fn update_url(&self, shorturl: String, target: String) -> Result<String, GoToError>;
+
+    async fn delete_url(&self, shorturl: String) -> Result<(), GoToError>;
+
+    async fn list_urls(&self) -> Result<Vec<UrlEntry>, GoToError>;
+
+    async fn get_stats(&self, shorturl: String) -> Result<UrlStats, GoToError>;
+
+    async fn bulk_import(&self, entries: Vec<BulkEntry>) -> Result<BulkReport, GoToError>;
+
+    async fn health_check(&self) -> Result<Health, GoToError>;
+}
+
+#[cfg(test)]
+mod cli_test {
+    use super::*;
+    use std::sync::Mutex;
+
+    struct MockClient {
+        create_new_called_with: Mutex<Option<(String, String, Option<u64>)>>,
+        want_create_new_called_with: Option<(String, String, Option<u64>)>,
+
+        get_long_url_called_with: Mutex<Option<String>>,
+        want_get_long_url_called_with: Option<String>,
+
+        resolve_url_called_with: Mutex<Option<String>>,
+        want_resolve_url_called_with: Option<String>,
+
+        delete_url_called_with: Mutex<Option<String>>,
+        want_delete_url_called_with: Option<String>,
+
+        update_url_called_with: Mutex<Option<(String, String)>>,
+        want_update_url_called_with: Option<(String, String)>,
+
+        get_stats_called_with: Mutex<Option<String>>,
+        want_get_stats_called_with: Option<String>,
+
+        bulk_import_called_with: Mutex<Vec<usize>>,
+        want_bulk_import_called_with: Vec<usize>,
+
+        create_new_err: Mutex<Option<GoToError>>,
+
+        calls: Mutex<Vec<&'static str>>,
+        want_calls: Option<Vec<&'static str>>,
+    }
+
+    impl MockClient {
+        fn new() -> Self {
+            MockClient {
+                create_new_called_with: Mutex::new(None),
+                want_create_new_called_with: None,
+
+                get_long_url_called_with: Mutex::new(None),
+                want_get_long_url_called_with: None,
+
+                resolve_url_called_with: Mutex::new(None),
+                want_resolve_url_called_with: None,
+
+                delete_url_called_with: Mutex::new(None),
+                want_delete_url_called_with: None,
+
+                update_url_called_with: Mutex::new(None),
+                want_update_url_called_with: None,
+
+                get_stats_called_with: Mutex::new(None),
+                want_get_stats_called_with: None,
+
+                bulk_import_called_with: Mutex::new(vec![]),
+                want_bulk_import_called_with: vec![],
+
+                create_new_err: Mutex::new(None),
+
+                calls: Mutex::new(vec![]),
+                want_calls: None,
+            }
+        }
+    }
+
+    #[async_trait]
+    impl Client for MockClient {
+        async fn create_new(
+            &self,
+            shorturl: String,
+            target: String,
+            max_clicks: Option<u64>,
+        ) -> Result<String, GoToError> {
+            self.calls.lock().unwrap().push("create_new");
+            *self.create_new_called_with.lock().unwrap() = Some((shorturl, target, max_clicks));
+            match self.create_new_err.lock().unwrap().take() {
+                Some(err) => Err(err),
+                None => Ok(String::new()),
+            }
+        }
+
+        async fn get_long_url(&self, shorturl: String) -> Result<String, GoToError> {
+            self.calls.lock().unwrap().push("get_long_url");
+            let target = format!("http://{}.com", shorturl);
+            *self.get_long_url_called_with.lock().unwrap() = Some(shorturl);
+            Ok(target)
+        }
+
+        async fn resolve_url(&self, shorturl: String) -> Result<String, GoToError> {
+            self.calls.lock().unwrap().push("resolve_url");
+            let target = format!("http://{}.com", shorturl);
+            *self.resolve_url_called_with.lock().unwrap() = Some(shorturl);
+            Ok(target)
+        }
+
+        async fn update_url(&self, shorturl: String, target: String) -> Result<String, GoToError> {
+            self.calls.lock().unwrap().push("update_url");
+            *self.update_url_called_with.lock().unwrap() = Some((shorturl, target));
+            Ok(String::new())
+        }
+
+        async fn delete_url(&self, shorturl: String) -> Result<(), GoToError> {
+            self.calls.lock().unwrap().push("delete_url");
+            *self.delete_url_called_with.lock().unwrap() = Some(shorturl);
+            Ok(())
+        }
+
+        async fn list_urls(&self) -> Result<Vec<UrlEntry>, GoToError> {
+            Ok(vec![])
+        }
+
+        async fn get_stats(&self, shorturl: String) -> Result<UrlStats, GoToError> {
+            *self.get_stats_called_with.lock().unwrap() = Some(shorturl);
+            Ok(UrlStats {
+                target: String::new(),
+                clicks: 0,
+                created: None,
+                last_accessed: None,
+                expires: None,
+            })
+        }
+
+        async fn bulk_import(&self, entries: Vec<BulkEntry>) -> Result<BulkReport, GoToError> {
+            self.bulk_import_called_with
+                .lock()
+                .unwrap()
+                .push(entries.len());
+            Ok(BulkReport {
+                created: entries.into_iter().map(|entry| entry.id).collect(),
+                updated: vec![],
+                failed: vec![],
+            })
+        }
+
+        async fn health_check(&self) -> Result<Health, GoToError> {
+            Ok(Health {
+                version: String::new(),
+                entries: 0,
+            })
+        }
+    }
+
+    impl Drop for MockClient {
+        fn drop(&mut self) {
+            let want = self.want_create_new_called_with.as_ref();
+            let got = self.create_new_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_get_long_url_called_with.as_ref();
+            let got = self.get_long_url_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_resolve_url_called_with.as_ref();
+            let got = self.resolve_url_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_delete_url_called_with.as_ref();
+            let got = self.delete_url_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_update_url_called_with.as_ref();
+            let got = self.update_url_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_get_stats_called_with.as_ref();
+            let got = self.get_stats_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            assert_eq!(
+                self.want_bulk_import_called_with,
+                *self.bulk_import_called_with.get_mut().unwrap()
+            );
+
+            if let Some(want) = self.want_calls.as_ref() {
+                assert_eq!(want, self.calls.get_mut().unwrap());
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_create_new() {
+        let mut client = MockClient::new();
+        client.want_create_new_called_with =
+            Some(("hello".to_string(), "http://world".to_string(), None));
+
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: "hello".to_string(),
+                target: Some("http://world".to_string()),
+                command: None,
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        let got = cli.run().await;
+        assert_eq!(Ok(Some(String::new())), got);
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_create_new_max_clicks() {
+        let mut client = MockClient::new();
+        client.want_create_new_called_with =
+            Some(("once".to_string(), "http://world".to_string(), Some(1)));
+
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: "once".to_string(),
+                target: Some("http://world".to_string()),
+                command: None,
+                max_clicks: Some(1),
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        let got = cli.run().await;
+        assert_eq!(Ok(Some(String::new())), got);
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_get_long_url() {
+        let mut client = MockClient::new();
+        client.want_get_long_url_called_with = Some("hi".to_string());
+
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: "hi".to_string(),
+                target: None,
+                command: None,
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        let got = cli.run().await;
+        assert_eq!(Ok(Some("http://hi.com".to_string())), got);
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_delete_url() {
+        let mut client = MockClient::new();
+        client.want_delete_url_called_with = Some("bye".to_string());
+
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Delete {
+                    shorturl: "bye".to_string(),
+                }),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        let got = cli.run().await;
+        assert_eq!(Ok(None), got);
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_get_stats() {
+        let mut client = MockClient::new();
+        client.want_get_stats_called_with = Some("popular".to_string());
+
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Stats {
+                    shorturl: "popular".to_string(),
+                    output: OutputFormat::Json,
+                }),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        let got = cli.run().await;
+        assert_eq!(Ok(None), got);
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_without_api() {
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Doctor),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client: MockClient::new(),
+        };
+
+        let got = cli.run().await;
+        assert_eq!(
+            Err(GoToError::CliError(
+                "doctor and config don't use the API".to_string()
+            )),
+            got
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_import() {
+        let mut client = MockClient::new();
+        client.want_bulk_import_called_with = vec![100, 100, 49];
+
+        let mut entries: Vec<BulkEntry> = (0..249)
+            .map(|i| BulkEntry {
+                id: i.to_string(),
+                target: format!("http://{}.com", i),
+            })
+            .collect();
+        entries.push(BulkEntry {
+            id: "invalid".to_string(),
+            target: "not a url".to_string(),
+        });
+
+        let mut output = Vec::new();
+        let got = import(&client, entries, false, &mut output).await.unwrap();
+
+        assert_eq!(249, got.created.len());
+        assert_eq!(
+            vec![BulkFailure {
+                id: "invalid".to_string(),
+                reason: "invalid target: relative URL without a base".to_string(),
+            }],
+            got.failed
+        );
+
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.contains("Imported 100/249...\n"), "{}", output);
+        assert!(output.contains("Imported 249/249...\n"), "{}", output);
+        assert!(
+            output.contains("Failed to import /invalid: invalid target"),
+            "{}",
+            output
+        );
+        assert!(
+            output.ends_with("Created: 249, Updated: 0, Failed: 1\n"),
+            "{}",
+            output
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_batch() {
+        let mut client = MockClient::new();
+        *client.create_new_err.get_mut().unwrap() = Some(GoToError::Http {
+            status: 400,
+            body: "invalid target".to_string(),
+        });
+        client.want_create_new_called_with =
+            Some(("baz".to_string(), "https://qux.com".to_string(), None));
+        client.want_update_url_called_with =
+            Some(("foo".to_string(), "https://new.bar.com".to_string()));
+        client.want_delete_url_called_with = Some("baz".to_string());
+        client.want_calls = Some(vec!["create_new", "create_new", "update_url", "delete_url"]);
+
+        let input = "# set up the short URLs\n\
+                     create foo https://bar.com\n\
+                     create baz https://qux.com\n\
+                     \n\
+                     update foo https://new.bar.com\n\
+                     \x20 delete   baz \n\
+                     delete\n\
+                     rename foo bar\n";
+        let http = http_client().unwrap();
+        let mut output = Vec::new();
+        let got = batch(
+            &client,
+            &http,
+            std::io::Cursor::new(input.as_bytes()),
+            &mut output,
+        )
+        .await;
+
+        assert_eq!(
+            Err(GoToError::CliError("3 of 6 commands failed".to_string())),
+            got
+        );
+        assert_eq!(
+            "line 2: HTTP 400: invalid target\n\
+             \n\
+             \n\
+             deleted /baz\n\
+             line 7: CLI error: usage: delete <id>\n\
+             line 8: CLI error: unknown command: rename\n\
+             Processed: 6, Errors: 3\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_batch_empty() {
+        let client = MockClient::new();
+        let http = http_client().unwrap();
+        let mut output = Vec::new();
+        let got = batch(
+            &client,
+            &http,
+            std::io::Cursor::new(&b"# nothing\n\n"[..]),
+            &mut output,
+        )
+        .await;
+
+        assert_eq!(Ok(()), got);
+        assert_eq!(
+            "Processed: 0, Errors: 0\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_import_dry_run() {
+        let mut client = MockClient::new();
+
+        let entries = vec![BulkEntry {
+            id: "gh".to_string(),
+            target: "https://github.com".to_string(),
+        }];
+
+        let mut output = Vec::new();
+        let got = import(&client, entries, true, &mut output).await.unwrap();
+
+        assert_eq!(vec!["gh".to_string()], got.created);
+        assert_eq!(
+            "Created: 1, Updated: 0, Failed: 0\n",
+            String::from_utf8(output).unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_cli_rename() {
+        let mut client = MockClient::new();
+        client.want_resolve_url_called_with = Some("old".to_string());
+        client.want_create_new_called_with =
+            Some(("new".to_string(), "http://old.com".to_string(), None));
+        client.want_delete_url_called_with = Some("old".to_string());
+        client.want_calls = Some(vec!["resolve_url", "create_new", "delete_url"]);
 
-    impl MockClient {
-        fn new() -> Self {
-            MockClient {
-                create_new_called_with: None,
-                want_create_new_called_with: None,
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Rename {
+                    old: "old".to_string(),
+                    new: "new".to_string(),
+                    force: false,
+                }),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
 
-                get_long_url_called_with: None,
-                want_get_long_url_called_with: None,
-            }
-        }
+        let got = cli.run().await;
+        assert_eq!(Ok(None), got);
     }
 
-    #[async_trait]
-    impl Client for MockClient {
-        async fn create_new(mut self, shorturl: String, target: String) -> Result<(), GoToError> {
-            self.create_new_called_with = Some((shorturl, target));
-            Ok(())
-        }
+    #[actix_rt::test]
+    async fn test_cli_rename_already_exists() {
+        let mut client = MockClient::new();
+        client.create_new_err = Mutex::new(Some(GoToError::Http {
+            status: 409,
+            body: "already registered".to_string(),
+        }));
+        client.want_resolve_url_called_with = Some("old".to_string());
+        client.want_create_new_called_with =
+            Some(("new".to_string(), "http://old.com".to_string(), None));
+        client.want_calls = Some(vec!["resolve_url", "create_new"]);
 
-        async fn get_long_url(mut self, shorturl: String) -> Result<String, GoToError> {
-            self.get_long_url_called_with = Some(shorturl);
-            Ok(String::new())
-        }
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Rename {
+                    old: "old".to_string(),
+                    new: "new".to_string(),
+                    force: false,
+                }),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        let got = cli.run().await;
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 409,
+                body: "already registered".to_string()
+            }),
+            got
+        );
     }
 
-    impl Drop for MockClient {
-        fn drop(&mut self) {
-            let want = self.want_create_new_called_with.as_ref();
-            let got = self.create_new_called_with.as_ref();
-            assert_eq!(want, got);
+    #[actix_rt::test]
+    async fn test_cli_rename_existing_target() {
+        let mut client = MockClient::new();
+        client.create_new_err = Mutex::new(Some(GoToError::ApiError(
+            "/old already maps to http://old.com".to_string(),
+        )));
+        client.want_resolve_url_called_with = Some("old".to_string());
+        client.want_create_new_called_with =
+            Some(("new".to_string(), "http://old.com".to_string(), None));
+        client.want_calls = Some(vec!["resolve_url", "create_new"]);
 
-            let want = self.want_get_long_url_called_with.as_ref();
-            let got = self.get_long_url_called_with.as_ref();
-            assert_eq!(want, got);
-        }
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Rename {
+                    old: "old".to_string(),
+                    new: "new".to_string(),
+                    force: true,
+                }),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client,
+        };
+
+        // old must not be deleted: new was never created
+        let got = cli.run().await;
+        assert_eq!(
+            Err(GoToError::ApiError(
+                "/old already maps to http://old.com".to_string()
+            )),
+            got
+        );
     }
 
     #[actix_rt::test]
-    async fn test_cli_create_new() {
+    async fn test_cli_rename_force() {
         let mut client = MockClient::new();
+        client.create_new_err = Mutex::new(Some(GoToError::Http {
+            status: 409,
+            body: "already registered".to_string(),
+        }));
+        client.want_resolve_url_called_with = Some("old".to_string());
         client.want_create_new_called_with =
-            Some(("hello".to_string(), "http://world".to_string()));
+            Some(("new".to_string(), "http://old.com".to_string(), None));
+        client.want_update_url_called_with =
+            Some(("new".to_string(), "http://old.com".to_string()));
+        client.want_delete_url_called_with = Some("old".to_string());
+        client.want_calls = Some(vec![
+            "resolve_url",
+            "create_new",
+            "update_url",
+            "delete_url",
+        ]);
 
         let cli = Cli {
             options: CliOptions {
-                shorturl: "hello".to_string(),
-                target: Some("http://world".to_string()),
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Rename {
+                    old: "old".to_string(),
+                    new: "new".to_string(),
+                    force: true,
+                }),
+                max_clicks: None,
                 verbose: false,
                 open_browser: false,
             },
@@ -619,18 +3776,24 @@ mod cli_test {
         };
 
         let got = cli.run().await;
-        assert_eq!(Ok(()), got);
+        assert_eq!(Ok(None), got);
     }
 
     #[actix_rt::test]
-    async fn test_cli_get_long_url() {
+    async fn test_cli_rename_to_itself() {
         let mut client = MockClient::new();
-        client.want_get_long_url_called_with = Some("hi".to_string());
+        client.want_calls = Some(vec![]);
 
         let cli = Cli {
             options: CliOptions {
-                shorturl: "hi".to_string(),
+                shorturl: String::new(),
                 target: None,
+                command: Some(Command::Rename {
+                    old: "same".to_string(),
+                    new: "same".to_string(),
+                    force: true,
+                }),
+                max_clicks: None,
                 verbose: false,
                 open_browser: false,
             },
@@ -638,55 +3801,129 @@ mod cli_test {
         };
 
         let got = cli.run().await;
-        assert_eq!(Ok(()), got);
+        assert_eq!(
+            Err(GoToError::CliError(
+                "cannot rename /same to itself".to_string()
+            )),
+            got
+        );
     }
 }
 
 #[cfg(test)]
 mod cli_errors_test {
     use super::*;
+    use std::sync::Mutex;
 
     struct MockClient {
-        create_new_called_with: Option<(String, String)>,
-        want_create_new_called_with: Option<(String, String)>,
+        create_new_called_with: Mutex<Option<(String, String, Option<u64>)>>,
+        want_create_new_called_with: Option<(String, String, Option<u64>)>,
 
-        get_long_url_called_with: Option<String>,
+        get_long_url_called_with: Mutex<Option<String>>,
         want_get_long_url_called_with: Option<String>,
+
+        delete_url_called_with: Mutex<Option<String>>,
+        want_delete_url_called_with: Option<String>,
+
+        get_stats_called_with: Mutex<Option<String>>,
+        want_get_stats_called_with: Option<String>,
     }
 
     impl MockClient {
         fn new() -> Self {
             MockClient {
-                create_new_called_with: None,
+                create_new_called_with: Mutex::new(None),
                 want_create_new_called_with: None,
 
-                get_long_url_called_with: None,
+                get_long_url_called_with: Mutex::new(None),
                 want_get_long_url_called_with: None,
+
+                delete_url_called_with: Mutex::new(None),
+                want_delete_url_called_with: None,
+
+                get_stats_called_with: Mutex::new(None),
+                want_get_stats_called_with: None,
             }
         }
     }
 
     #[async_trait]
     impl Client for MockClient {
-        async fn create_new(mut self, shorturl: String, target: String) -> Result<(), GoToError> {
-            self.create_new_called_with = Some((shorturl, target));
-            Ok(())
+        async fn create_new(
+            &self,
+            shorturl: String,
+            target: String,
+            max_clicks: Option<u64>,
+        ) -> Result<String, GoToError> {
+            *self.create_new_called_with.lock().unwrap() = Some((shorturl, target, max_clicks));
+            Ok(String::new())
+        }
+
+        async fn get_long_url(&self, shorturl: String) -> Result<String, GoToError> {
+            *self.get_long_url_called_with.lock().unwrap() = Some(shorturl);
+            Ok(String::new())
+        }
+
+        async fn resolve_url(&self, _shorturl: String) -> Result<String, GoToError> {
+            Ok(String::new())
         }
 
-        async fn get_long_url(mut self, shorturl: String) -> Result<String, GoToError> {
-            self.get_long_url_called_with = Some(shorturl);
+        async fn update_url(
+            &self,
+            _shorturl: String,
+            _target: String,
+        ) -> Result<String, GoToError> {
             Ok(String::new())
         }
+
+        async fn delete_url(&self, shorturl: String) -> Result<(), GoToError> {
+            *self.delete_url_called_with.lock().unwrap() = Some(shorturl);
+            Ok(())
+        }
+
+        async fn list_urls(&self) -> Result<Vec<UrlEntry>, GoToError> {
+            Ok(vec![])
+        }
+
+        async fn get_stats(&self, shorturl: String) -> Result<UrlStats, GoToError> {
+            *self.get_stats_called_with.lock().unwrap() = Some(shorturl);
+            Ok(UrlStats {
+                target: String::new(),
+                clicks: 0,
+                created: None,
+                last_accessed: None,
+                expires: None,
+            })
+        }
+
+        async fn bulk_import(&self, _entries: Vec<BulkEntry>) -> Result<BulkReport, GoToError> {
+            Ok(BulkReport::default())
+        }
+
+        async fn health_check(&self) -> Result<Health, GoToError> {
+            Ok(Health {
+                version: String::new(),
+                entries: 0,
+            })
+        }
     }
 
     impl Drop for MockClient {
         fn drop(&mut self) {
             let want = self.want_create_new_called_with.as_ref();
-            let got = self.create_new_called_with.as_ref();
+            let got = self.create_new_called_with.get_mut().unwrap().as_ref();
             assert_eq!(want, got);
 
             let want = self.want_get_long_url_called_with.as_ref();
-            let got = self.get_long_url_called_with.as_ref();
+            let got = self.get_long_url_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_delete_url_called_with.as_ref();
+            let got = self.delete_url_called_with.get_mut().unwrap().as_ref();
+            assert_eq!(want, got);
+
+            let want = self.want_get_stats_called_with.as_ref();
+            let got = self.get_stats_called_with.get_mut().unwrap().as_ref();
             assert_eq!(want, got);
         }
     }
@@ -695,18 +3932,20 @@ mod cli_errors_test {
     async fn test_cli_create_new() {
         let mut client = MockClient::new();
         client.want_create_new_called_with =
-            Some(("hello".to_string(), "http://world".to_string()));
+            Some(("hello".to_string(), "http://world".to_string(), None));
 
         let cli = Cli {
             options: CliOptions {
                 shorturl: "hello".to_string(),
                 target: Some("http://world".to_string()),
+                command: None,
+                max_clicks: None,
                 verbose: false,
                 open_browser: false,
             },
             client,
         };
-        cli.run().await.unwrap()
+        assert_eq!(Ok(Some(String::new())), cli.run().await);
     }
 
     #[actix_rt::test]
@@ -718,100 +3957,345 @@ mod cli_errors_test {
             options: CliOptions {
                 shorturl: "hi".to_string(),
                 target: None,
+                command: None,
+                max_clicks: None,
                 verbose: false,
                 open_browser: false,
             },
             client,
         };
-        cli.run().await.unwrap()
+        assert_eq!(Ok(Some(String::new())), cli.run().await);
     }
 }
 
 struct HttpClient {
     base_url: String,
+    http: reqwest::Client,
+    max_retries: u32,
+    retry_base_delay: Duration,
+    /// Password of `ADMIN_USER`, sent to the admin routes.
+    admin_password: Option<String>,
+    /// Whether the API has the JSON `api/shorten` endpoint, checked on first
+    /// use.
+    shorten: tokio::sync::OnceCell<bool>,
 }
 
 impl HttpClient {
-    fn new(base_url: String) -> Self {
-        Self { base_url }
+    /// new creates a client for the API at `base_url`. Its connections are
+    /// reused across calls, and requests fail after `timeout`.
+    fn new(base_url: String, timeout: Duration) -> Result<Self, GoToError> {
+        Ok(Self {
+            base_url,
+            http: http_client_builder().timeout(timeout).build()?,
+            max_retries: 0,
+            retry_base_delay: Duration::from_millis(DEFAULT_RETRY_BASE_DELAY_MS),
+            admin_password: None,
+            shorten: tokio::sync::OnceCell::new(),
+        })
     }
-}
 
-#[async_trait]
-impl Client for HttpClient {
-    async fn create_new(self, shorturl: String, target: String) -> Result<(), GoToError> {
-        let client = HyperClient::new();
+    /// with_retries retries transient errors up to `max_retries` times,
+    /// waiting `base_delay` before the first retry and twice as long before
+    /// each following one.
+    fn with_retries(mut self, max_retries: u32, base_delay: Duration) -> Self {
+        self.max_retries = max_retries;
+        self.retry_base_delay = base_delay;
+        self
+    }
 
-        let uri = format!("{}/{}", self.base_url, shorturl).parse::<Uri>()?;
+    /// with_admin_password authenticates the requests to the admin routes of
+    /// the API, such as deleting and importing shortened URLs, as `ADMIN_USER`.
+    fn with_admin_password(mut self, admin_password: Option<String>) -> Self {
+        self.admin_password = admin_password;
+        self
+    }
 
-        use hyper::{Body, Method, Request};
-        let req = Request::builder()
-            .method(Method::POST)
-            .uri(uri)
-            .body(Body::from(target))
-            .or_else(|err| Err(GoToError::CliError(err.to_string())))?;
+    /// admin adds the admin credentials, if any, to a request to an admin
+    /// route.
+    fn admin(&self, request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+        match &self.admin_password {
+            Some(password) => request.basic_auth(ADMIN_USER, Some(password)),
+            None => request,
+        }
+    }
 
-        let resp = client
-            .request(req)
-            .await
-            .or_else(|err| Err(GoToError::ApiError(err.to_string())))?;
+    /// send sends the request built by `request`, building it again to retry
+    /// when the connection failed or the API was unavailable.
+    async fn send(
+        &self,
+        request: impl Fn() -> reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, GoToError> {
+        let mut delay = self.retry_base_delay;
+        let mut retries = 0;
+        loop {
+            let res = request().send().await;
+            let transient = match &res {
+                Ok(resp) => resp.status() == reqwest::StatusCode::SERVICE_UNAVAILABLE,
+                Err(err) => err.is_connect(),
+            };
+
+            if !transient || retries >= self.max_retries {
+                return Ok(res?);
+            }
 
-        let is_server_error = resp.status().is_server_error();
-        let is_client_error = resp.status().is_client_error();
-        if is_server_error || is_client_error {
-            use hyper::body::HttpBody as _;
-            let body = resp.into_body().data().await.unwrap().unwrap().to_vec();
-            let body = String::from_utf8(body)?;
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+            retries += 1;
+        }
+    }
 
-            if is_server_error {
-                return Err(GoToError::ApiError(body));
-            } else {
-                return Err(GoToError::CliError(body));
-            }
+    /// supports_shorten tells whether the API advertises the `api/shorten`
+    /// endpoint in its capabilities. Older APIs don't.
+    async fn supports_shorten(&self) -> bool {
+        *self
+            .shorten
+            .get_or_init(|| async {
+                let resp = match self.url("") {
+                    Ok(url) => self.http.head(url).send().await,
+                    Err(_) => return false,
+                };
+
+                resp.ok()
+                    .and_then(|resp| resp.headers().get(CAPABILITIES_HEADER).cloned())
+                    .and_then(|capabilities| capabilities.to_str().ok().map(str::to_string))
+                    .map(|capabilities| {
+                        capabilities
+                            .split(',')
+                            .any(|capability| capability.trim() == "shorten")
+                    })
+                    .unwrap_or(false)
+            })
+            .await
+    }
+
+    /// shorten creates a shortened URL with the JSON `api/shorten` endpoint.
+    /// Older APIs may answer with another shortened URL redirecting to the
+    /// same target instead of the requested one, which is an error.
+    async fn shorten(&self, request: ShortenRequest) -> Result<String, GoToError> {
+        let url = self.url("api/shorten")?;
+
+        let resp = self
+            .send(|| self.http.post(url.clone()).json(&request))
+            .await?;
+        let body = check_status(resp).await?.bytes().await?;
+
+        let created: ShortenResponse = serde_json::from_slice(&body)
+            .map_err(|err| GoToError::ApiError(format!("parse shortened URL: {}", err)))?;
+        if matches!(&request.id, Some(id) if *id != created.id) {
+            return Err(GoToError::ApiError(format!(
+                "/{} already maps to {}",
+                created.id, created.target
+            )));
         }
 
-        Ok(())
+        Ok(format!(
+            "{} now redirects to {}",
+            created.short_url, created.target
+        ))
+    }
+
+    /// url joins `path` to the base URL of the API.
+    fn url(&self, path: &str) -> Result<reqwest::Url, GoToError> {
+        Ok(reqwest::Url::parse(&format!("{}/{}", self.base_url, path))?)
     }
+}
 
-    async fn get_long_url(self, shorturl: String) -> Result<String, GoToError> {
-        let client = HyperClient::new();
-        let uri = format!("{}/{}", self.base_url, shorturl).parse::<Uri>()?;
+/// http_client_builder prepares a reqwest client that doesn't follow
+/// redirections, so the CLI can read the target of a shortened URL itself.
+fn http_client_builder() -> reqwest::ClientBuilder {
+    reqwest::Client::builder().redirect(reqwest::redirect::Policy::none())
+}
 
-        let resp = client
-            .get(uri)
-            .await
-            .or_else(|err| Err(GoToError::ApiError(err.to_string())))?;
+/// http_client builds a client with `http_client_builder`.
+fn http_client() -> Result<reqwest::Client, GoToError> {
+    Ok(http_client_builder().build()?)
+}
 
-        if !resp.status().is_redirection() {
-            let is_server_error = resp.status().is_server_error();
-            let is_client_error = resp.status().is_client_error();
-            if is_server_error || is_client_error {
-                use hyper::body::HttpBody as _;
-                let body = resp.into_body().data().await.unwrap().unwrap().to_vec();
-                let body = String::from_utf8(body)?;
-
-                if is_server_error {
-                    return Err(GoToError::ApiError(body));
-                } else {
-                    return Err(GoToError::CliError(body));
-                }
-            }
+/// read_body reads the body of a response, which must be valid utf8.
+async fn read_body(resp: reqwest::Response) -> Result<String, GoToError> {
+    let body = resp.bytes().await?;
+    Ok(String::from_utf8(body.to_vec())?)
+}
+
+/// check_status turns client and server error responses into a
+/// `GoToError::Http` holding the status and the response body.
+async fn check_status(resp: reqwest::Response) -> Result<reqwest::Response, GoToError> {
+    let status = resp.status();
+    if status.is_client_error() || status.is_server_error() {
+        Err(GoToError::Http {
+            status: status.as_u16(),
+            body: read_body(resp).await?,
+        })
+    } else {
+        Ok(resp)
+    }
+}
+
+#[async_trait]
+impl Client for HttpClient {
+    async fn create_new(
+        &self,
+        shorturl: String,
+        target: String,
+        max_clicks: Option<u64>,
+    ) -> Result<String, GoToError> {
+        if self.supports_shorten().await {
+            return self
+                .shorten(ShortenRequest {
+                    id: Some(shorturl).filter(|shorturl| !shorturl.is_empty()),
+                    target,
+                    max_clicks,
+                })
+                .await;
+        }
 
+        let mut url = self.url(&shorturl)?;
+        if let Some(max_clicks) = max_clicks {
+            url.query_pairs_mut()
+                .append_pair("max_clicks", &max_clicks.to_string());
+        }
+
+        let resp = self
+            .send(|| {
+                self.http
+                    .request(reqwest::Method::POST, url.clone())
+                    .body(target.clone())
+            })
+            .await?;
+
+        let message = read_body(check_status(resp).await?).await?;
+        // older APIs answer with the existing shortened URL of the target
+        if !shorturl.is_empty()
+            && message.contains(" already maps to ")
+            && !message.starts_with(&format!("/{} ", shorturl))
+        {
+            return Err(GoToError::ApiError(message));
+        }
+
+        Ok(message)
+    }
+
+    async fn get_long_url(&self, shorturl: String) -> Result<String, GoToError> {
+        let url = self.url(&shorturl)?;
+
+        let resp = check_status(self.send(|| self.http.get(url.clone())).await?).await?;
+        if !resp.status().is_redirection() {
             return Err(GoToError::NoRedirection);
         }
 
         let location = resp
             .headers()
-            .get("location")
+            .get(reqwest::header::LOCATION)
             .ok_or(GoToError::NoRedirection)?;
 
         Ok(location.to_str()?.to_string())
     }
+
+    async fn resolve_url(&self, shorturl: String) -> Result<String, GoToError> {
+        let url = self.url(&shorturl)?;
+
+        let resp = self.send(|| self.http.head(url.clone())).await?;
+        let status = resp.status();
+        // responses to HEAD requests have no body to tell what went wrong
+        if status.is_client_error() || status.is_server_error() {
+            return Err(GoToError::Http {
+                status: status.as_u16(),
+                body: status.canonical_reason().unwrap_or_default().to_lowercase(),
+            });
+        }
+
+        let location = resp
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .ok_or(GoToError::NoRedirection)?;
+
+        Ok(location.to_str()?.to_string())
+    }
+
+    async fn update_url(&self, shorturl: String, target: String) -> Result<String, GoToError> {
+        let url = self.url(&shorturl)?;
+
+        let resp = self
+            .send(|| {
+                self.http
+                    .request(reqwest::Method::PATCH, url.clone())
+                    .body(target.clone())
+            })
+            .await?;
+
+        read_body(check_status(resp).await?).await
+    }
+
+    async fn delete_url(&self, shorturl: String) -> Result<(), GoToError> {
+        let url = self.url(&shorturl)?;
+
+        check_status(
+            self.send(|| self.admin(self.http.delete(url.clone())))
+                .await?,
+        )
+        .await?;
+        Ok(())
+    }
+
+    async fn list_urls(&self) -> Result<Vec<UrlEntry>, GoToError> {
+        let mut entries = Vec::new();
+        let mut cursor = None;
+        loop {
+            let mut url = self.url("api/urls")?;
+            url.query_pairs_mut().append_pair("limit", "1000");
+            if let Some(cursor) = &cursor {
+                url.query_pairs_mut().append_pair("cursor", cursor);
+            }
+
+            let resp = check_status(self.send(|| self.http.get(url.clone())).await?).await?;
+            let body = resp.bytes().await?;
+            let page: UrlPage = serde_json::from_slice(&body)
+                .map_err(|err| GoToError::ApiError(format!("parse entries: {}", err)))?;
+
+            entries.extend(page.items);
+            match page.next_cursor {
+                Some(next_cursor) => cursor = Some(next_cursor),
+                None => return Ok(entries),
+            }
+        }
+    }
+
+    async fn get_stats(&self, shorturl: String) -> Result<UrlStats, GoToError> {
+        let url = self.url(&format!("api/{}/stats", shorturl))?;
+
+        let resp = check_status(self.send(|| self.http.get(url.clone())).await?).await?;
+        let body = resp.bytes().await?;
+
+        serde_json::from_slice(&body)
+            .map_err(|err| GoToError::ApiError(format!("parse stats: {}", err)))
+    }
+
+    async fn bulk_import(&self, entries: Vec<BulkEntry>) -> Result<BulkReport, GoToError> {
+        let url = self.url("api/bulk")?;
+
+        let resp = self
+            .send(|| self.admin(self.http.post(url.clone())).json(&entries))
+            .await?;
+        let body = check_status(resp).await?.bytes().await?;
+
+        serde_json::from_slice(&body)
+            .map_err(|err| GoToError::ApiError(format!("parse bulk report: {}", err)))
+    }
+
+    async fn health_check(&self) -> Result<Health, GoToError> {
+        let url = self.url("health")?;
+
+        let resp = check_status(self.send(|| self.http.get(url.clone())).await?).await?;
+        let body = resp.bytes().await?;
+
+        serde_json::from_slice(&body)
+            .map_err(|err| GoToError::ApiError(format!("parse health: {}", err)))
+    }
 }
 
 #[test]
 fn test_from_tostrerror() {
-    let header = hyper::header::HeaderValue::from_bytes(b"Hello \xF0\x90\x80World").unwrap();
+    let header = reqwest::header::HeaderValue::from_bytes(b"Hello \xF0\x90\x80World").unwrap();
 
     let res = header.to_str();
     assert!(res.is_err());
@@ -830,21 +4314,180 @@ mod http_client_tests {
     use httpmock::{Method, MockServer};
 
     #[actix_rt::test]
-    async fn test_create_new() {
+    async fn test_create_new() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST).path("/sdfsdf");
+
+            then.status(200).body("ok!!");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .create_new("sdfsdf".to_string(), "http://target.com".to_string(), None)
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!("ok!!", res);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_shorten() {
+        let server = MockServer::start();
+        let capabilities = server.mock(|when, then| {
+            when.method(Method::HEAD).path("/");
+
+            then.status(200).header(CAPABILITIES_HEADER, "shorten");
+        });
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST)
+                .path("/api/shorten")
+                .json_body(serde_json::json!({
+                    "id": "sdfsdf",
+                    "target": "http://target.com",
+                    "max_clicks": 3
+                }));
+
+            then.status(201).body(
+                r#"{"id":"sdfsdf","short_url":"https://go.to/sdfsdf","target":"http://target.com"}"#,
+            );
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        for _ in 0..2 {
+            let res = client
+                .create_new(
+                    "sdfsdf".to_string(),
+                    "http://target.com".to_string(),
+                    Some(3),
+                )
+                .await
+                .unwrap();
+            assert_eq!(
+                "https://go.to/sdfsdf now redirects to http://target.com",
+                res
+            );
+        }
+
+        // the capabilities are only checked once
+        capabilities.assert_hits(1);
+        mock.assert_hits(2);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_shorten_existing() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(Method::HEAD).path("/");
+
+            then.status(200).header(CAPABILITIES_HEADER, "shorten");
+        });
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST).path("/api/shorten");
+
+            then.status(200).body(
+                r#"{"id":"other","short_url":"https://go.to/other","target":"http://target.com"}"#,
+            );
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .create_new("sdfsdf".to_string(), "http://target.com".to_string(), None)
+            .await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::ApiError(
+                "/other already maps to http://target.com".to_string()
+            )),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_existing() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST).path("/sdfsdf");
+
+            then.status(200)
+                .body("/other already maps to http://target.com");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .create_new("sdfsdf".to_string(), "http://target.com".to_string(), None)
+            .await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::ApiError(
+                "/other already maps to http://target.com".to_string()
+            )),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_shorten_err() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(Method::HEAD).path("/");
+
+            then.status(404)
+                .header(CAPABILITIES_HEADER, "something, shorten");
+        });
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST).path("/api/shorten");
+
+            then.status(409).body("already registered");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .create_new("sdfsdf".to_string(), "http://target.com".to_string(), None)
+            .await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 409,
+                body: "already registered".to_string()
+            }),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_max_clicks() {
         let server = MockServer::start();
         let mock = server.mock(|when, then| {
-            when.method(Method::POST).path("/sdfsdf");
+            when.method(Method::POST)
+                .path("/sdfsdf")
+                .query_param("max_clicks", "3");
 
             then.status(200).body("ok!!");
         });
 
-        let client = HttpClient::new(server.base_url());
-        client
-            .create_new("sdfsdf".to_string(), "http://target.com".to_string())
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .create_new(
+                "sdfsdf".to_string(),
+                "http://target.com".to_string(),
+                Some(3),
+            )
             .await
             .unwrap();
 
         mock.assert();
+        assert_eq!("ok!!", res);
     }
 
     #[actix_rt::test]
@@ -856,13 +4499,20 @@ mod http_client_tests {
             then.status(400).body("è_é");
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client
-            .create_new("sdfsdf".to_string(), "http://target.com".to_string())
+            .create_new("sdfsdf".to_string(), "http://target.com".to_string(), None)
             .await;
 
         mock.assert();
-        assert_eq!(Err(GoToError::CliError("è_é".to_string())), res);
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 400,
+                body: "è_é".to_string()
+            }),
+            res
+        );
     }
 
     #[actix_rt::test]
@@ -874,13 +4524,20 @@ mod http_client_tests {
             then.status(500).body("woops");
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client
-            .create_new("sdfsdf".to_string(), "http://target.com".to_string())
+            .create_new("sdfsdf".to_string(), "http://target.com".to_string(), None)
             .await;
 
         mock.assert();
-        assert_eq!(Err(GoToError::ApiError("woops".to_string())), res);
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 500,
+                body: "woops".to_string()
+            }),
+            res
+        );
     }
 
     #[actix_rt::test]
@@ -889,12 +4546,13 @@ mod http_client_tests {
         let mock = server.mock(|when, then| {
             when.method(Method::POST).path("/qqqqq");
 
-            then.status(500).body(&[0, 159, 146, 150]);
+            then.status(500).body([0, 159, 146, 150]);
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client
-            .create_new("qqqqq".to_string(), "http://target.com".to_string())
+            .create_new("qqqqq".to_string(), "http://target.com".to_string(), None)
             .await;
 
         mock.assert();
@@ -917,13 +4575,60 @@ mod http_client_tests {
                 .body("bla bla bla");
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client.get_long_url("shorturl3".to_string()).await.unwrap();
 
         mock.assert();
         assert_eq!("http://hi.there", res);
     }
 
+    #[actix_rt::test]
+    async fn test_resolve_url() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::HEAD).path("/shorturl3");
+
+            then.status(200).header("location", "http://hi.there");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.resolve_url("shorturl3".to_string()).await.unwrap();
+
+        mock.assert();
+        assert_eq!("http://hi.there", res);
+    }
+
+    #[actix_rt::test]
+    async fn test_resolve_url_err() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(Method::HEAD).path("/gone");
+
+            then.status(410);
+        });
+        server.mock(|when, then| {
+            when.method(Method::HEAD).path("/nolocation");
+
+            then.status(200);
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 410,
+                body: "gone".to_string()
+            }),
+            client.resolve_url("gone".to_string()).await
+        );
+        assert_eq!(
+            Err(GoToError::NoRedirection),
+            client.resolve_url("nolocation".to_string()).await
+        );
+    }
+
     #[actix_rt::test]
     async fn test_get_long_url_api_err() {
         let server = MockServer::start();
@@ -933,11 +4638,18 @@ mod http_client_tests {
             then.status(500).body("oh no");
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client.get_long_url("shorturl4".to_string()).await;
 
         mock.assert();
-        assert_eq!(Err(GoToError::ApiError("oh no".to_string())), res);
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 500,
+                body: "oh no".to_string()
+            }),
+            res
+        );
     }
 
     #[actix_rt::test]
@@ -949,11 +4661,18 @@ mod http_client_tests {
             then.status(400).body("oh no!!");
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client.get_long_url("shorturl4".to_string()).await;
 
         mock.assert();
-        assert_eq!(Err(GoToError::CliError("oh no!!".to_string())), res);
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 400,
+                body: "oh no!!".to_string()
+            }),
+            res
+        );
     }
 
     #[actix_rt::test]
@@ -965,7 +4684,8 @@ mod http_client_tests {
             then.status(200);
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client.get_long_url("shorturl4".to_string()).await;
 
         mock.assert();
@@ -981,7 +4701,8 @@ mod http_client_tests {
             then.status(302);
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client.get_long_url("shorturl4".to_string()).await;
 
         mock.assert();
@@ -994,10 +4715,11 @@ mod http_client_tests {
         let mock = server.mock(|when, then| {
             when.method(Method::GET).path("/shorturl4");
 
-            then.status(500).body(&[0, 159, 146, 150]);
+            then.status(500).body([0, 159, 146, 150]);
         });
 
-        let client = HttpClient::new(server.base_url());
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
         let res = client.get_long_url("shorturl4".to_string()).await;
 
         mock.assert();
@@ -1009,13 +4731,450 @@ mod http_client_tests {
         );
     }
 
+    #[actix_rt::test]
+    async fn test_delete_url() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::DELETE).path("/byebye");
+
+            then.status(200).body("/byebye deleted");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        client.delete_url("byebye".to_string()).await.unwrap();
+
+        mock.assert();
+    }
+
+    #[actix_rt::test]
+    async fn test_admin_password() {
+        let server = MockServer::start();
+        let delete = server.mock(|when, then| {
+            when.method(Method::DELETE)
+                .path("/byebye")
+                .header("Authorization", "Basic YWRtaW46czNjcmV0");
+
+            then.status(200).body("/byebye deleted");
+        });
+        let bulk = server.mock(|when, then| {
+            when.method(Method::POST)
+                .path("/api/bulk")
+                .header("Authorization", "Basic YWRtaW46czNjcmV0");
+
+            then.status(200)
+                .body(r#"{"created": [], "updated": [], "failed": []}"#);
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .unwrap()
+            .with_admin_password(Some("s3cret".to_string()));
+        client.delete_url("byebye".to_string()).await.unwrap();
+        client.bulk_import(vec![]).await.unwrap();
+
+        delete.assert();
+        bulk.assert();
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_url_not_found() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::DELETE).path("/byebye");
+
+            then.status(404).body("not found");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.delete_url("byebye".to_string()).await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 404,
+                body: "not found".to_string()
+            }),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_delete_url_api_err() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::DELETE).path("/byebye");
+
+            then.status(500).body("woops");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.delete_url("byebye".to_string()).await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 500,
+                body: "woops".to_string()
+            }),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_update_url() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::PATCH)
+                .path("/sdfsdf")
+                .body("http://target.com");
+
+            then.status(200).body("ok!!");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .update_url("sdfsdf".to_string(), "http://target.com".to_string())
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!("ok!!", res);
+    }
+
+    #[actix_rt::test]
+    async fn test_list_urls() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::GET).path("/api/urls");
+
+            then.status(200).body(
+                r#"{"items": [{"id": "hi", "target": "http://hi.there"}], "next_cursor": null, "total": 1}"#,
+            );
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.list_urls().await.unwrap();
+
+        mock.assert();
+        assert_eq!(
+            vec![UrlEntry {
+                id: "hi".to_string(),
+                target: "http://hi.there".to_string(),
+                clicks: None,
+                created: None,
+                expires: None,
+            }],
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_export() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::GET).path("/api/urls");
+
+            then.status(200).body(
+                r#"{"items": [{"id": "gh", "target": "https://github.com"}, {"id": "hi", "target": "http://hi.there", "clicks": 3}], "next_cursor": null, "total": 2}"#,
+            );
+        });
+
+        let mut filepath = std::env::temp_dir();
+        filepath.push("test_export.yml");
+
+        let cli = Cli {
+            options: CliOptions {
+                shorturl: String::new(),
+                target: None,
+                command: Some(Command::Export {
+                    output: Some(filepath.clone()),
+                    format: ExportFormat::Yaml,
+                }),
+                max_clicks: None,
+                verbose: false,
+                open_browser: false,
+            },
+            client: HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+                .unwrap(),
+        };
+        cli.run().await.unwrap();
+
+        mock.assert();
+        let got = std::fs::read_to_string(&filepath).unwrap();
+        assert_eq!(
+            "---\ngh: \"https://github.com\"\nhi: \"http://hi.there\"\n",
+            got
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_bulk_import() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST)
+                .path("/api/bulk")
+                .header("content-type", "application/json")
+                .body(r#"[{"id":"hi","target":"http://hi.there"}]"#);
+
+            then.status(200)
+                .body(r#"{"created": [], "updated": ["hi"], "failed": []}"#);
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client
+            .bulk_import(vec![BulkEntry {
+                id: "hi".to_string(),
+                target: "http://hi.there".to_string(),
+            }])
+            .await
+            .unwrap();
+
+        mock.assert();
+        assert_eq!(
+            BulkReport {
+                created: vec![],
+                updated: vec!["hi".to_string()],
+                failed: vec![],
+            },
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_list_urls_pages() {
+        let server = MockServer::start();
+        // mocks are matched in the order they were created, so the second
+        // page must be mocked before the first one, which matches any cursor
+        let second_page = server.mock(|when, then| {
+            when.method(Method::GET)
+                .path("/api/urls")
+                .query_param("cursor", "aGk");
+
+            then.status(200).body(
+                r#"{"items": [{"id": "ho", "target": "http://ho.there"}], "next_cursor": null, "total": 2}"#,
+            );
+        });
+        let first_page = server.mock(|when, then| {
+            when.method(Method::GET)
+                .path("/api/urls")
+                .query_param("limit", "1000");
+
+            then.status(200).body(
+                r#"{"items": [{"id": "hi", "target": "http://hi.there"}], "next_cursor": "aGk", "total": 2}"#,
+            );
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.list_urls().await.unwrap();
+
+        first_page.assert();
+        second_page.assert();
+        let ids: Vec<String> = res.into_iter().map(|entry| entry.id).collect();
+        assert_eq!(vec!["hi".to_string(), "ho".to_string()], ids);
+    }
+
+    #[actix_rt::test]
+    async fn test_list_urls_api_err() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::GET).path("/api/urls");
+
+            then.status(500).body("oh no");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.list_urls().await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 500,
+                body: "oh no".to_string()
+            }),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_get_stats() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::GET).path("/api/popular/stats");
+
+            then.status(200).body(
+                r#"{"target": "http://hi.there", "clicks": 42, "last_accessed": "2021-06-01T12:00:00Z"}"#,
+            );
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.get_stats("popular".to_string()).await.unwrap();
+
+        mock.assert();
+        assert_eq!(
+            UrlStats {
+                target: "http://hi.there".to_string(),
+                clicks: 42,
+                created: None,
+                last_accessed: Some("2021-06-01T12:00:00Z".to_string()),
+                expires: None,
+            },
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_get_stats_not_found() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::GET).path("/api/unknown/stats");
+
+            then.status(404).body("not found");
+        });
+
+        let client =
+            HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS)).unwrap();
+        let res = client.get_stats("unknown".to_string()).await;
+
+        mock.assert();
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 404,
+                body: "not found".to_string()
+            }),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_retries() {
+        let server = MockServer::start();
+        let unavailable = server.mock(|when, then| {
+            when.method(Method::POST).path("/retry");
+
+            then.status(503).body("unavailable");
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .unwrap()
+            .with_retries(3, Duration::from_millis(1));
+
+        let task = tokio::spawn(async move {
+            client
+                .create_new("retry".to_string(), "http://retry.com".to_string(), None)
+                .await
+        });
+
+        // let the API recover after the first two attempts
+        while unavailable.hits() < 2 {
+            tokio::time::sleep(Duration::from_millis(1)).await;
+        }
+        unavailable.delete();
+        let ok = server.mock(|when, then| {
+            when.method(Method::POST).path("/retry");
+
+            then.status(200).body("ok!!");
+        });
+
+        let res = task.await.unwrap();
+        ok.assert();
+        assert_eq!(Ok("ok!!".to_string()), res);
+    }
+
+    #[actix_rt::test]
+    async fn test_create_new_retries_exhausted() {
+        let server = MockServer::start();
+        let mock = server.mock(|when, then| {
+            when.method(Method::POST).path("/retry");
+
+            then.status(503).body("unavailable");
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .unwrap()
+            .with_retries(2, Duration::from_millis(1));
+        let res = client
+            .create_new("retry".to_string(), "http://retry.com".to_string(), None)
+            .await;
+
+        mock.assert_hits(3);
+        assert_eq!(
+            Err(GoToError::Http {
+                status: 503,
+                body: "unavailable".to_string()
+            }),
+            res
+        );
+    }
+
+    #[actix_rt::test]
+    async fn test_retries_every_request() {
+        let server = MockServer::start();
+        let delete = server.mock(|when, then| {
+            when.method(Method::DELETE).path("/retry");
+
+            then.status(503).body("unavailable");
+        });
+        let stats = server.mock(|when, then| {
+            when.method(Method::GET).path("/api/retry/stats");
+
+            then.status(503).body("unavailable");
+        });
+        let health = server.mock(|when, then| {
+            when.method(Method::GET).path("/health");
+
+            then.status(503).body("unavailable");
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_secs(DEFAULT_TIMEOUT_SECS))
+            .unwrap()
+            .with_retries(2, Duration::from_millis(1));
+        assert!(client.delete_url("retry".to_string()).await.is_err());
+        assert!(client.get_stats("retry".to_string()).await.is_err());
+        assert!(client.health_check().await.is_err());
+
+        delete.assert_hits(3);
+        stats.assert_hits(3);
+        health.assert_hits(3);
+    }
+
+    #[actix_rt::test]
+    async fn test_get_long_url_timeout() {
+        let server = MockServer::start();
+        server.mock(|when, then| {
+            when.method(Method::GET).path("/slow");
+
+            then.status(302)
+                .header("Location", "http://slow.com")
+                .delay(Duration::from_secs(2));
+        });
+
+        let client = HttpClient::new(server.base_url(), Duration::from_millis(100)).unwrap();
+        let res = client.get_long_url("slow".to_string()).await;
+
+        assert_eq!(Err(GoToError::Timeout), res);
+    }
+
     #[actix_rt::test]
     async fn test_get_long_url_invalid_uri() {
-        let client = HttpClient::new("this is an invalid url".to_string());
+        let client = HttpClient::new(
+            "this is an invalid url".to_string(),
+            Duration::from_secs(DEFAULT_TIMEOUT_SECS),
+        )
+        .unwrap();
         let res = client.get_long_url("shorturl4".to_string()).await;
 
         assert_eq!(
-            Err(GoToError::CliError("invalid uri character".to_string())),
+            Err(GoToError::CliError(
+                "invalid url: relative URL without a base".to_string()
+            )),
             res
         );
     }
@@ -0,0 +1,242 @@
+//! Live stream of the redirects served by the goto API.
+//!
+//! Every redirect is published to an [`Events`] broadcast channel, and sent
+//! to the WebSocket clients connected to `/api/ws/events` as
+//! `{"event": "redirect", "id": "...", "target": "...", "at": "..."}`.
+//! Clients only get the redirects of some short URLs after sending
+//! `{"subscribe": ["id1", "id2"]}`. Clients too slow to keep up with the
+//! redirects miss some of them, and get `{"event": "lagged", "dropped": N}`
+//! instead.
+
+use actix::{Actor, ActorContext, AsyncContext, StreamHandler};
+use actix_web::{get, web, Error, HttpRequest, HttpResponse};
+use actix_web_actors::ws;
+use std::collections::HashSet;
+use std::time::SystemTime;
+use tokio::sync::broadcast::{self, error::RecvError};
+
+/// Event is a redirect served by goto.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub id: String,
+    pub target: String,
+    pub at: SystemTime,
+}
+
+/// Message is what the WebSocket clients receive.
+#[derive(serde::Serialize, Debug, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Message<'a> {
+    Redirect {
+        id: &'a str,
+        target: &'a str,
+        at: String,
+    },
+    Lagged {
+        dropped: u64,
+    },
+    Error {
+        message: String,
+    },
+}
+
+impl Message<'_> {
+    fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("serialise event")
+    }
+}
+
+impl<'a> From<&'a Event> for Message<'a> {
+    fn from(event: &'a Event) -> Self {
+        Message::Redirect {
+            id: &event.id,
+            target: &event.target,
+            at: humantime::format_rfc3339_seconds(event.at).to_string(),
+        }
+    }
+}
+
+/// Subscription is what the WebSocket clients send to only get the
+/// redirects of some short URLs.
+#[derive(serde::Deserialize, Debug, PartialEq)]
+struct Subscription {
+    subscribe: HashSet<String>,
+}
+
+/// Events publishes the redirects to the connected WebSocket clients. Only
+/// the last `buffer` redirects are kept for the clients that haven't
+/// received them yet.
+#[derive(Clone)]
+pub struct Events {
+    sender: broadcast::Sender<Event>,
+}
+
+impl Events {
+    pub fn new(buffer: usize) -> Self {
+        let (sender, _) = broadcast::channel(buffer);
+        Events { sender }
+    }
+
+    /// publish sends a redirect to /{id} to the connected clients.
+    pub fn publish(&self, id: &str, target: &str) {
+        // fails when no client is connected
+        let _ = self.sender.send(Event {
+            id: id.to_string(),
+            target: target.to_string(),
+            at: SystemTime::now(),
+        });
+    }
+
+    /// subscribe returns a receiver of the redirects published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Event> {
+        self.sender.subscribe()
+    }
+}
+
+/// Session forwards the redirects to a WebSocket client.
+struct Session {
+    receiver: Option<broadcast::Receiver<Event>>,
+    /// Short URLs the client subscribed to, or None for all of them.
+    ids: Option<HashSet<String>>,
+}
+
+impl Session {
+    fn wants(&self, event: &Event) -> bool {
+        match &self.ids {
+            Some(ids) => ids.contains(&event.id),
+            None => true,
+        }
+    }
+}
+
+impl Actor for Session {
+    type Context = ws::WebsocketContext<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        if let Some(receiver) = self.receiver.take() {
+            let events = futures::stream::unfold(receiver, |mut receiver| async move {
+                match receiver.recv().await {
+                    Err(RecvError::Closed) => None,
+                    received => Some((received, receiver)),
+                }
+            });
+            ctx.add_stream(events);
+        }
+    }
+}
+
+impl StreamHandler<Result<Event, RecvError>> for Session {
+    fn handle(&mut self, received: Result<Event, RecvError>, ctx: &mut Self::Context) {
+        match received {
+            Ok(event) if self.wants(&event) => ctx.text(Message::from(&event).to_json()),
+            Ok(_) => {}
+            Err(RecvError::Lagged(dropped)) => ctx.text(Message::Lagged { dropped }.to_json()),
+            Err(RecvError::Closed) => ctx.stop(),
+        }
+    }
+
+    // the client is still connected when goto stops publishing
+    fn finished(&mut self, _ctx: &mut Self::Context) {}
+}
+
+impl StreamHandler<Result<ws::Message, ws::ProtocolError>> for Session {
+    fn handle(&mut self, msg: Result<ws::Message, ws::ProtocolError>, ctx: &mut Self::Context) {
+        match msg {
+            Ok(ws::Message::Text(text)) => match serde_json::from_str::<Subscription>(&text) {
+                Ok(subscription) => self.ids = Some(subscription.subscribe),
+                Err(err) => ctx.text(
+                    Message::Error {
+                        message: format!("invalid subscription: {}", err),
+                    }
+                    .to_json(),
+                ),
+            },
+            Ok(ws::Message::Ping(msg)) => ctx.pong(&msg),
+            Ok(ws::Message::Close(reason)) => {
+                ctx.close(reason);
+                ctx.stop();
+            }
+            Ok(_) => {}
+            Err(_) => ctx.stop(),
+        }
+    }
+}
+
+/// stream upgrades the connection to a WebSocket, on which the redirects are
+/// sent as they are served.
+#[get("/api/ws/events")]
+pub async fn stream(
+    req: HttpRequest,
+    payload: web::Payload,
+    events: web::Data<Events>,
+) -> Result<HttpResponse, Error> {
+    let session = Session {
+        receiver: Some(events.subscribe()),
+        ids: None,
+    };
+    ws::start(session, &req, payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::{Duration, UNIX_EPOCH};
+
+    #[test]
+    fn test_messages() {
+        let event = Event {
+            id: "hi".to_string(),
+            target: "https://linkedin.com/in/tsauvajon".to_string(),
+            at: UNIX_EPOCH + Duration::from_secs(1621445809),
+        };
+        assert_eq!(
+            r#"{"event":"redirect","id":"hi","target":"https://linkedin.com/in/tsauvajon","at":"2021-05-19T17:36:49Z"}"#,
+            Message::from(&event).to_json()
+        );
+        assert_eq!(
+            r#"{"event":"lagged","dropped":3}"#,
+            Message::Lagged { dropped: 3 }.to_json()
+        );
+    }
+
+    #[test]
+    fn test_subscription() {
+        let got: Subscription = serde_json::from_str(r#"{"subscribe": ["id1", "id2"]}"#).unwrap();
+        let want: HashSet<String> = vec!["id1".to_string(), "id2".to_string()]
+            .into_iter()
+            .collect();
+        assert_eq!(want, got.subscribe);
+
+        let session = Session {
+            receiver: None,
+            ids: Some(got.subscribe),
+        };
+        let event = |id: &str| Event {
+            id: id.to_string(),
+            target: "https://linkedin.com/in/tsauvajon".to_string(),
+            at: SystemTime::now(),
+        };
+        assert!(session.wants(&event("id1")));
+        assert!(!session.wants(&event("id3")));
+    }
+
+    #[test]
+    fn test_publish() {
+        let events = Events::new(2);
+        // nobody is listening yet
+        events.publish("lost", "https://linkedin.com/in/tsauvajon");
+
+        let mut receiver = events.subscribe();
+        for id in &["one", "two", "three"] {
+            events.publish(id, "https://linkedin.com/in/tsauvajon");
+        }
+
+        // slow receivers miss the oldest events
+        assert_eq!(
+            Err(broadcast::error::TryRecvError::Lagged(1)),
+            receiver.try_recv()
+        );
+        assert_eq!("two", receiver.try_recv().unwrap().id);
+        assert_eq!("three", receiver.try_recv().unwrap().id);
+    }
+}
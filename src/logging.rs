@@ -0,0 +1,170 @@
+//! Structured logging for the goto API.
+//!
+//! Verbosity is controlled with the `RUST_LOG` environment variable, and
+//! defaults to `info`.
+
+use std::path::Path;
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::EnvFilter;
+
+const DEFAULT_FILTER: &str = "info";
+
+/// LogFormat is the format of the log lines.
+#[derive(clap::ValueEnum, Clone, Debug, PartialEq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+/// env_filter reads the log filter from `RUST_LOG`.
+pub fn env_filter() -> EnvFilter {
+    filter(std::env::var(EnvFilter::DEFAULT_ENV).ok().as_deref())
+}
+
+/// filter parses the log filter `directives`, or falls back to the default
+/// one if they are missing or invalid.
+fn filter(directives: Option<&str>) -> EnvFilter {
+    directives
+        .and_then(|directives| EnvFilter::try_new(directives).ok())
+        .unwrap_or_else(|| EnvFilter::new(DEFAULT_FILTER))
+}
+
+/// writer picks where log lines are written: stdout, or `log_file` if it is
+/// set. Log files are rotated daily, and only the last `keep_days` files are
+/// kept.
+///
+/// Lines are written to log files by a background thread, which flushes
+/// them and stops when the returned guard is dropped.
+pub fn writer(
+    log_file: Option<&Path>,
+    keep_days: usize,
+) -> Result<(BoxMakeWriter, Option<WorkerGuard>), String> {
+    let log_file = match log_file {
+        Some(log_file) => log_file,
+        None => return Ok((BoxMakeWriter::new(std::io::stdout), None)),
+    };
+
+    let file_name = log_file
+        .file_name()
+        .ok_or_else(|| format!("{} is not a file", log_file.display()))?;
+    let directory = match log_file.parent() {
+        Some(directory) if !directory.as_os_str().is_empty() => directory,
+        _ => Path::new("."),
+    };
+
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(file_name.to_string_lossy())
+        .max_log_files(keep_days)
+        .build(directory)
+        .map_err(|err| err.to_string())?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+
+    Ok((BoxMakeWriter::new(writer), Some(guard)))
+}
+
+/// subscriber builds a tracing subscriber writing log lines in `format` to
+/// `writer`.
+pub fn subscriber<W>(
+    format: &LogFormat,
+    filter: EnvFilter,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_env_filter(filter)
+        .with_writer(writer);
+
+    match format {
+        LogFormat::Pretty => Box::new(builder.pretty().finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Buffer {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    fn log_with(format: LogFormat, filter: EnvFilter) -> String {
+        let buf = Buffer::default();
+        let writer = buf.clone();
+        let subscriber = subscriber(&format, filter, move || writer.clone());
+
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(id = "hello", target = "https://world", "created short url");
+        });
+
+        buf.contents()
+    }
+
+    #[test]
+    fn test_log_format_from_str() {
+        use clap::ValueEnum;
+
+        assert_eq!(Ok(LogFormat::Pretty), LogFormat::from_str("pretty", false));
+        assert_eq!(Ok(LogFormat::Json), LogFormat::from_str("json", false));
+        assert!(LogFormat::from_str("xml", false).is_err());
+    }
+
+    #[test]
+    fn test_json_logs() {
+        let got = log_with(LogFormat::Json, EnvFilter::new("info"));
+        let got: serde_json::Value = serde_json::from_str(&got).unwrap();
+
+        assert_eq!("INFO", got["level"]);
+        assert_eq!("created short url", got["fields"]["message"]);
+        assert_eq!("hello", got["fields"]["id"]);
+        assert_eq!("https://world", got["fields"]["target"]);
+    }
+
+    #[test]
+    fn test_pretty_logs() {
+        let got = log_with(LogFormat::Pretty, EnvFilter::new("info"));
+        assert!(got.contains("created short url"), "{}", got);
+    }
+
+    #[test]
+    fn test_writer_not_a_file() {
+        assert!(writer(Some(Path::new("/")), 7).is_err());
+    }
+
+    #[test]
+    fn test_logs_off() {
+        assert_eq!("", log_with(LogFormat::Json, filter(Some("off"))));
+    }
+
+    #[test]
+    fn test_default_filter() {
+        for directives in &[None, Some("not a [filter")] {
+            let got = log_with(LogFormat::Pretty, filter(*directives));
+            assert!(got.contains("created short url"), "{}", got);
+        }
+    }
+}
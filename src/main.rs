@@ -28,6 +28,55 @@ $ curl 127.0.0.1:8080/tsauvajon -v
 * Connection #0 to host 127.0.0.1 left intact
 redirecting to https://linkedin.com/in/tsauvajon ...* Closing connection 0
 ```
+
+## Configuration
+
+Every option can be set either with a command-line flag or with an
+environment variable. Flags take precedence over environment variables.
+
+| Flag                        | Environment variable           | Default                    |
+|-----------------------------|--------------------------------|----------------------------|
+| `--addr`                    | `GOTO_ADDR`                    | `127.0.0.1:8080`           |
+| `--https-cert`              | `GOTO_HTTPS_CERT`              | none                       |
+| `--https-key`               | `GOTO_HTTPS_KEY`               | none                       |
+| `--bind-fd`                 | `GOTO_BIND_FD`                 | `LISTEN_FDS`               |
+| `--systemd-notify`          | `GOTO_SYSTEMD_NOTIFY`          | `false`                    |
+| `--workers`                 | `GOTO_WORKERS`                 | `0`, one per CPU           |
+| `--blocking-threads`        | `GOTO_BLOCKING_THREADS`        | `0`, 5 per CPU             |
+| `--database`                | `GOTO_DATABASE`                | none                       |
+| `--database-format`         | `GOTO_DATABASE_FORMAT`         | file extension             |
+| `--audit-log`               | `GOTO_AUDIT_LOG`               | none                       |
+| `--db-load-timeout`         | `GOTO_DB_LOAD_TIMEOUT`         | `30`                       |
+| `--no-hash-collision-retry` | `GOTO_NO_HASH_COLLISION_RETRY` | `false`                    |
+| `--allow-duplicate-targets` | `GOTO_ALLOW_DUPLICATE_TARGETS` | `false`                    |
+| `--hard-delete`             | `GOTO_HARD_DELETE`             | `false`                    |
+| `--frontdir`                | `GOTO_FRONT_DIR`               | `front/dist/`              |
+| `--cors-origins`            | `GOTO_CORS_ORIGINS`            | none                       |
+| `--log-format`              | `GOTO_LOG_FORMAT`              | `pretty`                   |
+| `--log-file`                | `GOTO_LOG_FILE`                | none                       |
+| `--log-keep-days`           | `GOTO_LOG_KEEP_DAYS`           | `7`                        |
+| `--allow-private-targets`   | `GOTO_ALLOW_PRIVATE_TARGETS`   | `false`                    |
+| `--preview`                 | `GOTO_PREVIEW`                 | `false`                    |
+| `--preview-delay`           | `GOTO_PREVIEW_DELAY`           | `5`                        |
+| `--cookie-name`             | `GOTO_COOKIE_NAME`             | `goto_trusted`             |
+| `--redirect-body`           | `GOTO_REDIRECT_BODY`           | `redirecting to {url} ...` |
+| `--max-id-length`           | `GOTO_MAX_ID_LENGTH`           | `64`                       |
+| `--max-target-length`       | `GOTO_MAX_TARGET_LENGTH`       | `2048`                     |
+| `--max-entries`             | `GOTO_MAX_ENTRIES`             | unlimited                  |
+| `--base-url`                | `GOTO_BASE_URL`                | none                       |
+| `--prefix`                  | `GOTO_PREFIX`                  | none                       |
+| `--allowed-schemes`         | `GOTO_ALLOWED_SCHEMES`         | `http,https`               |
+| `--case-insensitive`        | `GOTO_CASE_INSENSITIVE`        | `false`                    |
+| `--id-alphabet`             | `GOTO_ID_ALPHABET`             | hexadecimal                |
+| `--random-id-generator`     | `GOTO_RANDOM_ID_GENERATOR`     | `hash`                     |
+| `--id-length`               | `GOTO_ID_LENGTH`               | `5`                        |
+| `--read-only`               | `GOTO_READ_ONLY`               | `false`                    |
+| `--admin-password`          | `GOTO_ADMIN_PASSWORD`          | none                       |
+| `--no-security-headers`     | `GOTO_NO_SECURITY_HEADERS`     | `false`                    |
+| `--ws-buffer`               | `GOTO_WS_BUFFER`               | `100`                      |
+| `--shutdown-timeout`        | `GOTO_SHUTDOWN_TIMEOUT`        | `30`                       |
+
+The verbosity of the logs is controlled with `RUST_LOG`, e.g. `RUST_LOG=debug`.
 */
 
 #![deny(
@@ -38,717 +87,7872 @@ redirecting to https://linkedin.com/in/tsauvajon ...* Closing connection 0
     clippy::cargo
 )]
 
+mod events;
+mod logging;
+mod metrics;
+mod search;
+mod storage;
+mod systemd;
+
+use actix_cors::Cors;
 use actix_files::Files;
-use actix_web::{error, get, post, web, App, HttpResponse, HttpServer, Responder};
+use actix_web::dev::{Payload, Service, ServiceRequest, ServiceResponse};
+use actix_web::http::{header, Method, StatusCode};
+use actix_web::middleware::{Condition, DefaultHeaders};
+use actix_web::{
+    delete, error, get, head, patch, post, put, web, App, Error, FromRequest, HttpMessage,
+    HttpRequest, HttpResponse, HttpServer, Responder,
+};
+use clap::Parser;
+use events::Events;
+use futures::Future;
 use futures::StreamExt;
-use std::collections::HashMap;
+use indexmap::IndexMap;
+use logging::LogFormat;
+use metrics::{Instrument, Metrics};
+use search::SearchField;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::{Read, Write};
-use std::sync::RwLock;
-use structopt::StructOpt;
-use url::Url;
-
-const MAX_SIZE: usize = 256; // max payload size is 256 Kb
+use std::net::IpAddr;
+use std::num::{NonZeroU64, NonZeroUsize};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::RecvTimeoutError;
+use std::sync::{OnceLock, RwLock};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use storage::{FileBackend, InMemoryBackend, StorageBackend};
+use tracing_actix_web::TracingLogger;
+use url::{Host, Url};
+
+const MAX_BODY_OVERHEAD: usize = 256; // bytes allowed besides the target in payloads, e.g. JSON fields
+const MAX_BULK_SIZE: usize = 512 * 1024; // max bulk import payload size is 512 Kb
+const MAX_CONFIG_SIZE: usize = 4 * 1024; // max server config payload size is 4 Kb
+const MAX_BULK_ENTRIES: usize = 1000;
+const DEFAULT_PAGE_SIZE: usize = 100; // short URLs listed per page unless specified otherwise
+const MAX_PAGE_SIZE: usize = 1000;
 const RANDOM_URL_SIZE: usize = 5; // ramdomly generated URLs are 5 characters long
-
-struct Data {
-    data: HashMap<String, String>,
-    persistence: Option<File>,
+const MAX_HASH_ATTEMPTS: usize = 10; // hashes tried before picking a random id
+const DEFAULT_QR_SIZE: u32 = 256; // QR codes are 256x256 pixels unless specified otherwise
+const MAX_QR_SIZE: u32 = 1024;
+const DEFAULT_MAX_ID_LENGTH: usize = 64; // custom short URLs are at most 64 characters long
+const DEFAULT_MAX_TARGET_LENGTH: usize = 2048; // most browsers reject longer redirections
+const DEFAULT_ALLOWED_SCHEMES: &[&str] = &["http", "https"];
+const BASE62_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+const NANOID_ALPHABET: &str = "abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_-";
+const DEFAULT_LOG_KEEP_DAYS: usize = 7; // log files are rotated daily
+const DEFAULT_DB_LOAD_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_REDIRECT_BODY: &str = "redirecting to {url} ...";
+const DEFAULT_WS_BUFFER: usize = 100; // redirects kept for slow WebSocket clients
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 30;
+const DEFAULT_COOKIE_NAME: &str = "goto_trusted";
+const TRUST_COOKIE_MAX_AGE_SECS: u64 = 30 * 24 * 60 * 60; // trusting goto lasts 30 days
+
+/// Header listing the optional features of the API, so that clients can
+/// tell which endpoints they can use.
+const CAPABILITIES_HEADER: &str = "x-goto-capabilities";
+const REQUEST_ID_HEADER: &str = "x-request-id";
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+/// Header in which browsers' developer tools read how long each step of a
+/// request took.
+const SERVER_TIMING_HEADER: &str = "server-timing";
+/// Headers protecting the HTML pages, such as the front-end's, from XSS and
+/// clickjacking. The front-end needs 'wasm-unsafe-eval' to compile its
+/// WebAssembly.
+const HTML_SECURITY_HEADERS: &[(&str, &str)] = &[
+    (
+        "content-security-policy",
+        "default-src 'self'; script-src 'self' 'wasm-unsafe-eval'",
+    ),
+    ("x-frame-options", "DENY"),
+    ("referrer-policy", "no-referrer"),
+];
+
+/// IdGenerator picks the ids of short URLs created without one: the hash of
+/// their target, or random characters, so that shortening the same URL twice
+/// gives two different ids.
+#[derive(clap::ValueEnum, serde::Serialize, serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+enum IdGenerator {
+    Hash,
+    Nanoid,
 }
 
-impl Data {
-    fn get(&self, key: &str) -> Option<&String> {
-        self.data.get(key)
-    }
+/// DataFormat is the format of the database file, picked from its extension
+/// unless --database-format is set: `.json` files are JSON documents,
+/// `.ndjson` and `.jsonl` files have one JSON object per line, and any other
+/// file is a YAML document.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq)]
+enum DataFormat {
+    Yaml,
+    Json,
+    Ndjson,
+}
 
-    fn contains_key(&self, key: &str) -> bool {
-        self.data.contains_key(key)
+impl DataFormat {
+    fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|extension| extension.to_str()) {
+            Some("json") => DataFormat::Json,
+            Some("ndjson") | Some("jsonl") => DataFormat::Ndjson,
+            _ => DataFormat::Yaml,
+        }
     }
 
-    fn insert(&mut self, key: &str, value: &str) -> Option<String> {
-        match self.data.insert(key.to_string(), value.to_string()) {
-            Some(existing_value) => Some(existing_value),
-            None => {
-                if let Some(file) = &mut self.persistence {
-                    file.write_all(serialise_entry(key.to_string(), value.to_string()).as_bytes())
-                        .expect("persist new entry");
-                }
-                None
-            }
+    /// parse reads the entries of a database file, in the file's order. An
+    /// empty file, e.g. for a new database, has no entries.
+    fn parse(self, contents: &str) -> Result<IndexMap<String, Entry>, String> {
+        if contents.is_empty() {
+            return Ok(IndexMap::new());
         }
-    }
 
-    fn new(data: HashMap<String, String>) -> Self {
-        Data {
-            data,
-            persistence: None,
+        match self {
+            DataFormat::Yaml => serde_yaml::from_str(contents).map_err(|err| err.to_string()),
+            DataFormat::Json => serde_json::from_str(contents).map_err(|err| err.to_string()),
+            DataFormat::Ndjson => parse_ndjson(contents),
         }
     }
 
-    fn with_persistence(mut self, persistence: File) -> Self {
-        self.persistence = Some(persistence);
-        self
+    /// serialise writes entries into a database file, in insertion order.
+    fn serialise(self, data: &IndexMap<String, Entry>) -> String {
+        match self {
+            DataFormat::Yaml => serde_yaml::to_string(data).expect("serialise database"),
+            DataFormat::Json => serde_json::to_string_pretty(data).expect("serialise database"),
+            DataFormat::Ndjson => serialise_ndjson(data.iter()),
+        }
     }
 }
 
-#[test]
-fn test_insert_data() {
-    use std::env::temp_dir;
-
-    let dir = temp_dir();
-    let tmpfile_path = format!("{}/tmpfile2.txt", dir.to_str().unwrap());
-    let file = File::create(&tmpfile_path).unwrap();
+/// NdjsonLine is a line of an NDJSON database file: a short URL and its
+/// fields, or `{"id": "foo", "deleted": true}` when it was removed.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct NdjsonLine {
+    id: String,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    deleted: bool,
+    #[serde(flatten)]
+    fields: serde_json::Map<String, serde_json::Value>,
+}
 
-    {
-        let mut data = Data::new(HashMap::new()).with_persistence(file);
-        let outcome = data.insert("hi", "qwerty");
-        assert_eq!(None, outcome);
+/// parse_ndjson reads an NDJSON database file line by line. Lines can be
+/// appended to it: a short URL is replaced by the later lines with the same
+/// id, and removed by a later `deleted` line.
+fn parse_ndjson(contents: &str) -> Result<IndexMap<String, Entry>, String> {
+    let mut entries = IndexMap::new();
+    for (number, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
 
-        let outcome = data.insert("hi", "zxcvbnm");
-        assert_eq!(Some("qwerty".to_string()), outcome);
+        let parse_err = |err: serde_json::Error| format!("line {}: {}", number + 1, err);
+        let line: NdjsonLine = serde_json::from_str(line).map_err(parse_err)?;
+        if line.deleted {
+            entries.shift_remove(&line.id);
+        } else {
+            let entry = serde_json::from_value(serde_json::Value::Object(line.fields))
+                .map_err(parse_err)?;
+            entries.insert(line.id, entry);
+        }
     }
+    Ok(entries)
+}
 
-    let mut file = File::open(tmpfile_path).unwrap();
-    let mut got = String::new();
-    file.read_to_string(&mut got).unwrap();
+/// serialise_ndjson writes each entry as a JSON object on its own line.
+fn serialise_ndjson<'a>(entries: impl Iterator<Item = (&'a String, &'a Entry)>) -> String {
+    let mut contents = String::new();
+    for (id, entry) in entries {
+        let fields = match serde_json::to_value(entry).expect("serialise entry") {
+            serde_json::Value::Object(fields) => fields,
+            target => std::iter::once(("target".to_string(), target)).collect(),
+        };
+        let line = NdjsonLine {
+            id: id.to_owned(),
+            deleted: false,
+            fields,
+        };
+        contents.push_str(&serde_json::to_string(&line).expect("serialise entry"));
+        contents.push('\n');
+    }
+    contents
+}
 
-    assert_eq!("hi: \"qwerty\"\n".to_string(), got);
+/// ndjson_tombstone is the NDJSON line removing the short URL `id`.
+fn ndjson_tombstone(id: &str) -> String {
+    let line = NdjsonLine {
+        id: id.to_owned(),
+        deleted: true,
+        fields: serde_json::Map::new(),
+    };
+    format!(
+        "{}\n",
+        serde_json::to_string(&line).expect("serialise entry")
+    )
 }
 
-#[derive(Clone)]
-struct Db {
-    data: web::Data<RwLock<Data>>,
+/// Entry is a short URL stored in the database.
+#[derive(serde::Serialize, serde::Deserialize, Clone, Debug, PartialEq)]
+#[serde(from = "StoredEntry", into = "StoredEntry")]
+struct Entry {
+    target: String,
+    /// None for short URLs created before creation times were recorded.
+    created_at: Option<SystemTime>,
+    deleted_at: Option<SystemTime>,
+    /// Number of redirects.
+    clicks: u64,
+    /// Time of the last redirect.
+    last_accessed: Option<SystemTime>,
+    /// The short URL is removed after this many redirects.
+    max_clicks: Option<u64>,
+    /// The short URL stops redirecting after this time.
+    expires_at: Option<SystemTime>,
 }
 
-impl Db {
-    fn read(
-        &self,
-    ) -> Result<
-        std::sync::RwLockReadGuard<Data>,
-        std::sync::PoisonError<std::sync::RwLockReadGuard<Data>>,
-    > {
-        self.data.read()
+impl Entry {
+    /// new creates an entry for a short URL created now.
+    fn new(target: String) -> Self {
+        Entry {
+            created_at: Some(now()),
+            ..Entry::from(target)
+        }
     }
 
-    fn write(
-        &self,
-    ) -> Result<
-        std::sync::RwLockWriteGuard<Data>,
-        std::sync::PoisonError<std::sync::RwLockWriteGuard<Data>>,
-    > {
-        self.data.write()
+    fn is_deleted(&self) -> bool {
+        self.deleted_at.is_some()
     }
 
-    fn new(data: Data) -> Self {
-        Db {
-            data: web::Data::new(RwLock::new(data)),
-        }
+    fn is_expired(&self) -> bool {
+        matches!(self.expires_at, Some(expires_at) if expires_at <= SystemTime::now())
     }
 }
 
-/// serialise_entry serialises a new database entry into
-/// a new YAML line, that can be added to an existing
-/// database.
-fn serialise_entry(key: String, value: String) -> String {
-    format!("{}: \"{}\"\n", key, value)
-}
-
-/// browse redirects to the long URL hidden behind a short URL, or returns a
-/// 404 not found error if the short URL doesn't exist.
-#[get("/{id}")]
-async fn browse(db: web::Data<Db>, web::Path(id): web::Path<String>) -> impl Responder {
-    match db.read() {
-        Ok(db) => match db.get(&id) {
-            None => Err(error::ErrorNotFound("not found")),
-            Some(url) => Ok(HttpResponse::Found()
-                .header("Location", url.to_string())
-                .body(format!("redirecting to {} ...", url))),
-        },
-        Err(err) => {
-            println!("accessing the db: {}", err);
-            Err(error::ErrorInternalServerError(err.to_string()))
+impl From<String> for Entry {
+    fn from(target: String) -> Self {
+        Entry {
+            target,
+            created_at: None,
+            deleted_at: None,
+            clicks: 0,
+            last_accessed: None,
+            max_clicks: None,
+            expires_at: None,
         }
     }
 }
 
-/// hash returns a short hash of the string passed as a parameter.
-fn hash(input: &str) -> String {
-    blake3::hash(input.as_bytes()).to_hex()[..RANDOM_URL_SIZE].to_string()
+/// StoredEntry is how an entry is written to the database file: a map of
+/// its fields, or just its target for entries written before creation times
+/// and clicks were recorded. Such entries are still read, and are written
+/// as a map once they get clicked.
+#[derive(serde::Serialize, serde::Deserialize)]
+#[serde(untagged)]
+enum StoredEntry {
+    Target(String),
+    Full {
+        target: String,
+        /// Seconds since the Unix epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        created_at: Option<u64>,
+        /// Seconds since the Unix epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        deleted_at: Option<u64>,
+        #[serde(default, skip_serializing_if = "is_zero")]
+        clicks: u64,
+        /// Seconds since the Unix epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        last_accessed: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_clicks: Option<u64>,
+        /// Seconds since the Unix epoch.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        expires_at: Option<u64>,
+    },
 }
 
-/// Read a string target from an actix_web Payload
-async fn read_target(mut payload: web::Payload) -> Result<String, String> {
-    let mut body = web::BytesMut::new();
-    while let Some(chunk) = payload.next().await {
-        let chunk = chunk.or_else(|err| Err(err.to_string()))?;
-        // limit max size of in-memory payload
-        if (body.len() + chunk.len()) > MAX_SIZE {
-            return Err("overflow".to_string());
+fn is_zero(n: &u64) -> bool {
+    *n == 0
+}
+
+impl From<StoredEntry> for Entry {
+    fn from(stored: StoredEntry) -> Self {
+        match stored {
+            StoredEntry::Target(target) => Entry::from(target),
+            StoredEntry::Full {
+                target,
+                created_at,
+                deleted_at,
+                clicks,
+                last_accessed,
+                max_clicks,
+                expires_at,
+            } => Entry {
+                target,
+                created_at: created_at.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                deleted_at: deleted_at.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                clicks,
+                last_accessed: last_accessed.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+                max_clicks,
+                expires_at: expires_at.map(|secs| UNIX_EPOCH + Duration::from_secs(secs)),
+            },
         }
-        body.extend_from_slice(&chunk);
     }
-
-    String::from_utf8(body[..].to_vec())
-        .or_else(|err| Err(format!("invalid request body: {}", err)))
 }
 
-/// Create an short URL redirecting to a long URL.
-/// If you pass an `id` a parameter, your short URL will be /{id}.
-/// If you pass `None` instead, it will be /{hash of the target URL}.
-fn create_short_url(db: web::Data<Db>, target: &str, id: Option<&str>) -> Result<String, String> {
-    if let Err(err) = Url::parse(&target) {
-        return Err(format!("malformed URL: {}", err));
-    };
-
-    let id = match id {
-        Some(id) => id.to_string(),
-        None => hash(&target),
-    };
+impl From<Entry> for StoredEntry {
+    fn from(entry: Entry) -> Self {
+        if entry.created_at.is_none()
+            && entry.deleted_at.is_none()
+            && entry.clicks == 0
+            && entry.last_accessed.is_none()
+            && entry.max_clicks.is_none()
+            && entry.expires_at.is_none()
+        {
+            return StoredEntry::Target(entry.target);
+        }
 
-    let mut db = db.write().unwrap();
-    if db.contains_key(&id) {
-        Err("already registered".to_string())
-    } else {
-        db.insert(&id, target);
-        Ok(format!("/{} now redirects to {}", id, target))
+        StoredEntry::Full {
+            target: entry.target,
+            created_at: entry.created_at.map(unix_seconds),
+            deleted_at: entry.deleted_at.map(unix_seconds),
+            clicks: entry.clicks,
+            last_accessed: entry.last_accessed.map(unix_seconds),
+            max_clicks: entry.max_clicks,
+            expires_at: entry.expires_at.map(unix_seconds),
+        }
     }
 }
 
-#[post("/{id}")]
-async fn create_with_id(
-    db: web::Data<Db>,
-    payload: web::Payload,
-    web::Path(id): web::Path<String>,
-) -> impl Responder {
-    let target = match read_target(payload).await {
-        Ok(target) => target,
-        Err(err) => return Err(error::ErrorBadRequest(err)),
-    };
-
-    create_short_url(db, &target, Some(id.as_str())).or_else(|err| Err(error::ErrorBadRequest(err)))
+/// now returns the current time with a precision of one second, as times
+/// are stored in the database.
+fn now() -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(unix_seconds(SystemTime::now()))
 }
 
-#[post("/")]
-async fn create_random(db: web::Data<Db>, payload: web::Payload) -> impl Responder {
-    let target = match read_target(payload).await {
-        Ok(target) => target,
-        Err(err) => return Err(error::ErrorBadRequest(err)),
-    };
-
-    create_short_url(db, &target, None).or_else(|err| Err(error::ErrorBadRequest(err)))
+/// unix_seconds returns the number of seconds between the Unix epoch and
+/// `time`.
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
 }
 
-#[derive(StructOpt)]
-struct Cli {
-    #[structopt(short = "f", long = "frontdir")]
-    /// Directory where the front-end files are located, default: "front/dist".
-    front_dist_directory: Option<String>,
-
-    #[structopt(short = "a", long = "addr")]
-    /// Address to run the application on, default: "127.0.0.1:8080".
-    addr: Option<String>,
+struct Data {
+    data: IndexMap<String, Entry>,
+    storage: Box<dyn StorageBackend>,
+    hard_delete: bool,
+    allow_private_targets: bool,
+    /// Schemes of the long URLs short URLs can redirect to, in lowercase.
+    allowed_schemes: HashSet<String>,
+    max_id_length: usize,
+    max_target_length: usize,
+    /// Maximum number of short URLs, including the deleted ones, or None
+    /// for no limit.
+    max_entries: Option<usize>,
+    /// Whether short URLs are stored and looked up in lowercase.
+    case_insensitive: bool,
+    /// Whether another id is picked when the hash of a long URL is already
+    /// used by a short URL redirecting somewhere else.
+    hash_collision_retry: bool,
+    /// Whether a new short URL can redirect to the same long URL as an
+    /// existing one, instead of the existing one being returned.
+    allow_duplicate_targets: bool,
+    /// Characters of the random ids, which are hexadecimal if it is None.
+    id_alphabet: Option<String>,
+    id_generator: IdGenerator,
+    /// Number of characters of the ids picked by `IdGenerator::Nanoid`.
+    id_length: usize,
+    /// Append-only trail of the changes to the short URLs, never read back.
+    audit_log: Option<File>,
+}
 
-    #[structopt(short = "d", long = "database")]
-    /// Database file to persist the shortened URLs.
-    /// Will be created if it doesn't exist.
-    /// Example: database.yml.
-    /// If this option is omitted, the shortened URLs will not be persisted.
-    database: Option<String>,
+/// AuditRecord is a line of the audit log.
+#[derive(serde::Serialize)]
+struct AuditRecord<'a> {
+    op: &'a str,
+    id: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    target: Option<&'a str>,
+    at: String,
 }
 
-impl Cli {
-    fn get_front_dir(&self) -> String {
-        match &self.front_dist_directory {
-            Some(dir) => dir.to_owned(),
-            None => "front/dist/".to_string(),
+/// Cloning the database makes an in-memory snapshot of it, e.g. to serialise
+/// it without holding the lock: the clone is never persisted nor audited.
+impl Clone for Data {
+    fn clone(&self) -> Self {
+        Data {
+            data: self.data.clone(),
+            storage: Box::new(InMemoryBackend),
+            hard_delete: self.hard_delete,
+            allow_private_targets: self.allow_private_targets,
+            allowed_schemes: self.allowed_schemes.clone(),
+            max_id_length: self.max_id_length,
+            max_target_length: self.max_target_length,
+            max_entries: self.max_entries,
+            case_insensitive: self.case_insensitive,
+            hash_collision_retry: self.hash_collision_retry,
+            allow_duplicate_targets: self.allow_duplicate_targets,
+            id_alphabet: self.id_alphabet.clone(),
+            id_generator: self.id_generator,
+            id_length: self.id_length,
+            audit_log: None,
         }
     }
+}
 
-    fn get_addr(&self) -> String {
-        match &self.addr {
-            Some(addr) => addr.to_owned(),
-            None => "127.0.0.1:8080".to_string(),
+impl Data {
+    /// normalise_id returns the key under which a short URL is stored:
+    /// its id, in lowercase if ids are case-insensitive.
+    fn normalise_id<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        if self.case_insensitive {
+            Cow::Owned(id.to_lowercase())
+        } else {
+            Cow::Borrowed(id)
         }
     }
 
-    fn open_db(&self) -> Result<Db, String> {
-        let data = match &self.database {
-            None => Data::new(HashMap::new()),
-            Some(path) => {
-                let path = std::path::Path::new(&path);
+    /// get returns the target of a short URL, unless it was deleted or it
+    /// expired.
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn get(&self, key: &str) -> Option<&String> {
+        tracing::debug!(key = %key, "database lookup");
+        self.data
+            .get(self.normalise_id(key).as_ref())
+            .filter(|entry| !entry.is_deleted() && !entry.is_expired())
+            .map(|entry| &entry.target)
+    }
 
-                let mut file = OpenOptions::new()
-                    .write(true)
-                    .create(true)
-                    .read(true)
-                    .truncate(false)
-                    .open(path.to_owned())
-                    .or_else(|err| Err(err.to_string()))?;
-
-                let mut buf = String::new();
-                match file.read_to_string(&mut buf) {
-                    Err(_) => Data::new(HashMap::new()),
-                    Ok(len) => {
-                        if len == 0 {
-                            Data::new(HashMap::new()).with_persistence(file)
-                        } else {
-                            let yaml_contents: HashMap<String, String> = serde_yaml::from_str(&buf)
-                                .or_else(|err| Err(format!("parse data: {}", err)))?;
-
-                            Data::new(yaml_contents).with_persistence(file)
-                        }
-                    }
-                }
-            }
-        };
+    /// find_target returns the id of a short URL redirecting to `target`,
+    /// unless it was deleted or it expired.
+    fn find_target(&self, target: &str) -> Option<&String> {
+        self.data
+            .iter()
+            .find(|(_, entry)| entry.target == target && !entry.is_deleted() && !entry.is_expired())
+            .map(|(id, _)| id)
+    }
 
-        Ok(Db::new(data))
+    /// get_entry returns a short URL, even if it was deleted.
+    fn get_entry(&self, key: &str) -> Option<&Entry> {
+        self.data.get(self.normalise_id(key).as_ref())
     }
-}
 
-#[cfg(test)]
-mod cli_tests {
-    use super::Cli;
+    /// contains tells whether a short URL is taken, including by a deleted
+    /// short URL that can still be restored.
+    fn contains(&self, key: &str) -> bool {
+        self.data.contains_key(self.normalise_id(key).as_ref())
+    }
 
-    #[test]
-    fn test_get_front_dir() {
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: None,
-        };
-        assert_eq!("front/dist/", cli.get_front_dir());
+    fn len(&self) -> usize {
+        self.data.len()
+    }
 
-        let cli = Cli {
-            front_dist_directory: Some("/hello/world/".into()),
-            addr: None,
-            database: None,
-        };
-        assert_eq!("/hello/world/", cli.get_front_dir());
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
     }
 
-    #[test]
-    fn test_get_addr() {
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: None,
-        };
-        assert_eq!("127.0.0.1:8080", cli.get_addr());
+    /// is_full tells whether no more short URLs can be created.
+    fn is_full(&self) -> bool {
+        matches!(self.max_entries, Some(max_entries) if self.len() >= max_entries)
+    }
 
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: Some("123.34.56.78:99999".into()),
-            database: None,
-        };
-        assert_eq!("123.34.56.78:99999", cli.get_addr());
+    /// entries iterates over all the entries of the database, including the
+    /// deleted ones, in insertion order.
+    fn entries(&self) -> impl Iterator<Item = (&String, &Entry)> {
+        self.data.iter()
     }
 
-    #[test]
-    fn test_open_db_no_persistence() {
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: None,
-        };
-        let db = cli.open_db().unwrap();
-        let data = db.read().unwrap();
+    /// insert creates a short URL, or makes an existing one redirect to a
+    /// new target, keeping its creation time.
+    #[tracing::instrument(level = "debug", skip(self, value))]
+    fn insert(&mut self, key: &str, value: &str) -> std::io::Result<Option<String>> {
+        let mut entry = Entry::new(value.to_string());
+        if let Some(existing_entry) = self.get_entry(key) {
+            entry.created_at = existing_entry.created_at;
+        }
 
-        assert_eq!(true, data.persistence.is_none());
+        let existing_value = self.insert_entry(key, entry)?;
+        Ok(existing_value.map(|entry| entry.target))
     }
 
-    #[test]
-    fn test_open_db_new_file() {
-        use std::env::temp_dir;
+    fn insert_entry(&mut self, key: &str, entry: Entry) -> std::io::Result<Option<Entry>> {
+        let key = self.normalise_id(key).into_owned();
+        self.audit("insert", &key, Some(&entry.target))?;
+        let existing_entry = self.data.insert(key.clone(), entry);
+        self.persist_entry(&key)?;
+        Ok(existing_entry)
+    }
 
-        let dir = temp_dir();
-        let tmpfile_path = format!("{}/tmpfile3.txt", dir.to_str().unwrap());
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: Some(tmpfile_path),
-        };
-        let db = cli.open_db().unwrap();
-        let data = db.read().unwrap();
+    /// persist_entry persists the change to the short URL `key`, in its own
+    /// span so that the time spent writing to the storage shows in traces.
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn persist_entry(&self, key: &str) -> std::io::Result<()> {
+        self.storage.persist(&self.data, key)?;
+        tracing::debug!("persisted short url");
+        Ok(())
+    }
 
-        match &data.persistence {
-            None => panic!("expected persistence"),
-            Some(file) => {
-                let metadata = file.metadata().unwrap();
-                assert_eq!(true, metadata.is_file());
+    /// update_entry applies `f` to a short URL, even if it was deleted, and
+    /// persists the change. It returns false if the short URL doesn't exist.
+    #[tracing::instrument(level = "debug", skip(self, f))]
+    fn update_entry<F: FnOnce(&mut Entry)>(&mut self, key: &str, f: F) -> std::io::Result<bool> {
+        let key = self.normalise_id(key);
+        match self.data.get_mut(key.as_ref()) {
+            None => Ok(false),
+            Some(entry) => {
+                f(entry);
+                let target = entry.target.clone();
+                self.audit("update", &key, Some(&target))?;
+                self.persist_entry(&key)?;
+                Ok(true)
             }
-        };
+        }
     }
 
-    #[test]
-    fn test_open_db_existing_file() {
-        use std::env::temp_dir;
-        use std::fs::File;
-
-        let dir = temp_dir();
-        let tmpfile_path = format!("{}/tmpfile.txt", dir.to_str().unwrap());
-
-        File::create(&tmpfile_path).unwrap();
-
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: Some(tmpfile_path),
+    /// click counts a redirect to a short URL, and removes the short URL
+    /// after its last allowed click. It returns false if the short URL
+    /// doesn't exist or was deleted.
+    fn click(&mut self, key: &str) -> std::io::Result<bool> {
+        let key = self.normalise_id(key);
+        let exhausted = match self.data.get_mut(key.as_ref()) {
+            Some(entry) if !entry.is_deleted() => {
+                entry.clicks += 1;
+                entry.last_accessed = Some(now());
+                matches!(entry.max_clicks, Some(max_clicks) if entry.clicks >= max_clicks)
+            }
+            _ => return Ok(false),
         };
-        let db = cli.open_db().unwrap();
-        let data = db.read().unwrap();
 
-        assert_eq!(true, data.persistence.is_some());
+        if exhausted {
+            self.audit("remove", &key, None)?;
+            self.data.shift_remove(key.as_ref());
+            self.storage.remove(&self.data, &key)?;
+        } else {
+            self.persist_entry(&key)?;
+        }
+        Ok(true)
     }
 
-    #[test]
-    fn test_open_db_existing_file_with_data() {
-        use std::env::temp_dir;
-        use std::fs::File;
-        use std::io::Write;
+    /// remove removes a short URL for good, and returns it.
+    #[tracing::instrument(level = "debug", skip(self))]
+    fn remove(&mut self, key: &str) -> std::io::Result<Option<Entry>> {
+        let key = self.normalise_id(key);
+        let existing_entry = self.data.shift_remove(key.as_ref());
+        if existing_entry.is_some() {
+            self.audit("remove", &key, None)?;
+            self.storage.remove(&self.data, &key)?;
+            tracing::debug!("removed short url");
+        }
+        Ok(existing_entry)
+    }
 
-        let dir = temp_dir();
-        let tmpfile_path = format!("{}/temporary-file.txt", dir.to_str().unwrap());
+    /// delete marks a short URL as deleted, so that it can be restored
+    /// later, or removes it for good when hard deletes are enabled.
+    /// It returns false if the short URL doesn't exist or was already
+    /// deleted.
+    fn delete(&mut self, key: &str) -> std::io::Result<bool> {
+        if self.hard_delete {
+            return Ok(self.remove(key)?.is_some());
+        }
 
-        let mut file = File::create(&tmpfile_path).unwrap();
-        file.write_all(b"hello: \"http://world\"\n").unwrap();
+        let key = self.normalise_id(key);
+        match self.data.get_mut(key.as_ref()) {
+            Some(entry) if !entry.is_deleted() => {
+                entry.deleted_at = Some(now());
+                self.audit("delete", &key, None)?;
+                self.persist_entry(&key)?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
 
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: Some(tmpfile_path),
+    /// restore un-deletes a short URL. It returns false if the short URL
+    /// doesn't exist.
+    fn restore(&mut self, key: &str) -> std::io::Result<bool> {
+        let key = self.normalise_id(key);
+        let was_deleted = match self.data.get_mut(key.as_ref()) {
+            None => return Ok(false),
+            Some(entry) => entry.deleted_at.take().is_some(),
         };
-        let db = cli.open_db().unwrap();
-        let data = db.read().unwrap();
 
-        assert_eq!(true, data.persistence.is_some());
-        assert_eq!(Some(&"http://world".to_string()), data.data.get("hello"));
+        if was_deleted {
+            self.audit("restore", &key, None)?;
+            self.persist_entry(&key)?;
+        }
+        Ok(true)
     }
 
-    #[test]
-    fn test_open_db_existing_file_with_bad_data() {
-        use std::env::temp_dir;
-        use std::fs::File;
-        use std::io::Write;
+    /// compact removes the deleted and expired short URLs for good, and
+    /// rewrites the storage. It returns the number of short URLs left.
+    fn compact(&mut self) -> std::io::Result<usize> {
+        self.data
+            .retain(|_, entry| !entry.is_deleted() && !entry.is_expired());
+        self.storage.save(&self.data)?;
+        Ok(self.data.len())
+    }
 
-        let dir = temp_dir();
-        let tmpfile_path = format!("{}/tmpfile1.txt", dir.to_str().unwrap());
+    /// transaction runs the steps of `f` as one change: the database is only
+    /// persisted once `f` returns. If `f` panics or persisting fails, the
+    /// short URLs are rolled back to what they were before, and the panic or
+    /// the error goes on.
+    /// It is best-effort: the audit log still records the rolled back
+    /// changes, and the storage isn't locked.
+    fn transaction<F, T>(&mut self, f: F) -> std::io::Result<T>
+    where
+        F: FnOnce(&mut Data) -> T,
+    {
+        let checkpoint = self.data.clone();
+        // the intermediate states are not persisted
+        let storage = std::mem::replace(&mut self.storage, Box::new(InMemoryBackend));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(self)));
+        self.storage = storage;
+        match result {
+            Ok(value) => match self.storage.save(&self.data) {
+                Ok(()) => Ok(value),
+                Err(err) => {
+                    self.data = checkpoint;
+                    Err(err)
+                }
+            },
+            Err(panic) => {
+                self.data = checkpoint;
+                std::panic::resume_unwind(panic)
+            }
+        }
+    }
 
-        let mut file = File::create(&tmpfile_path).unwrap();
-        file.write_all(b"ds;flsd'f sdl;flfs~~!./'' /sf/;dsf;lsdf")
-            .unwrap();
+    /// audit appends a change to the short URL `id` to the audit log, if
+    /// there is one.
+    fn audit(&self, op: &str, id: &str, target: Option<&str>) -> std::io::Result<()> {
+        let mut file = match &self.audit_log {
+            Some(file) => file,
+            None => return Ok(()),
+        };
 
-        let cli = Cli {
-            front_dist_directory: None,
-            addr: None,
-            database: Some(tmpfile_path),
+        let record = AuditRecord {
+            op,
+            id,
+            target,
+            at: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
         };
+        let mut line = serde_json::to_string(&record).expect("serialise audit record");
+        line.push('\n');
+        file.write_all(line.as_bytes())
+    }
 
-        let res = cli.open_db();
-        assert_eq!(true, res.is_err());
-        if let Err(msg) = res {
-            assert_eq!(true, msg.contains("parse data: invalid type:"));
+    fn new(data: impl IntoIterator<Item = (String, String)>) -> Self {
+        Self::from_entries(
+            data.into_iter()
+                .map(|(id, target)| (id, Entry::from(target))),
+        )
+    }
+
+    fn from_entries(data: impl IntoIterator<Item = (String, Entry)>) -> Self {
+        Data {
+            data: data.into_iter().collect(),
+            storage: Box::new(InMemoryBackend),
+            hard_delete: false,
+            allow_private_targets: false,
+            allowed_schemes: DEFAULT_ALLOWED_SCHEMES
+                .iter()
+                .map(|scheme| scheme.to_string())
+                .collect(),
+            max_id_length: DEFAULT_MAX_ID_LENGTH,
+            max_target_length: DEFAULT_MAX_TARGET_LENGTH,
+            max_entries: None,
+            case_insensitive: false,
+            hash_collision_retry: true,
+            allow_duplicate_targets: false,
+            id_alphabet: None,
+            id_generator: IdGenerator::Hash,
+            id_length: RANDOM_URL_SIZE,
+            audit_log: None,
         }
     }
-}
 
-#[actix_web::main]
-#[cfg(not(tarpaulin_include))]
-async fn main() -> std::io::Result<()> {
-    let args = Cli::from_args();
+    /// from_reader parses the short URLs read from `reader`, e.g. an opened
+    /// database file, in `format`. The short URLs aren't persisted.
+    fn from_reader<R: Read>(mut reader: R, format: DataFormat) -> Result<Self, String> {
+        let mut contents = String::new();
+        reader
+            .read_to_string(&mut contents)
+            .map_err(|err| format!("read data: {}", err))?;
 
-    let front_dist_directory = args.get_front_dir();
-    let addr: String = args.get_addr();
-    let db = args.open_db().expect("open db");
+        Ok(Self::from_entries(format.parse(&contents)?))
+    }
 
-    println!("goto listening at http://{}/", &addr);
+    /// with_storage persists every change to the short URLs to `storage`.
+    fn with_storage(mut self, storage: impl StorageBackend + 'static) -> Self {
+        self.storage = Box::new(storage);
+        self
+    }
 
-    HttpServer::new(move || {
-        App::new()
-            .service(Files::new("/dist", &front_dist_directory))
-            .data(db.clone())
-            .service(browse)
-            .service(create_random)
-            .service(create_with_id)
-            // this doesn't do exactly what I need (just serve index.html
-            //    on /), but I can't find a simple way of doing it.
-            .service(Files::new("/", &front_dist_directory).index_file("index.html"))
-    })
-    .bind(addr)?
-    .run()
-    .await
+    /// with_persistence stores the short URLs in a file, in the format given
+    /// by its extension.
+    fn with_persistence(self, path: PathBuf) -> Self {
+        let format = DataFormat::from_path(&path);
+        self.with_storage(FileBackend::new(path, format))
+    }
+
+    /// with_audit_log appends every change to the short URLs to `file`, as
+    /// JSON lines.
+    fn with_audit_log(mut self, file: File) -> Self {
+        self.audit_log = Some(file);
+        self
+    }
+
+    fn with_hard_delete(mut self, hard_delete: bool) -> Self {
+        self.hard_delete = hard_delete;
+        self
+    }
+
+    fn with_hash_collision_retry(mut self, retry: bool) -> Self {
+        self.hash_collision_retry = retry;
+        self
+    }
+
+    fn with_duplicate_targets(mut self, allow: bool) -> Self {
+        self.allow_duplicate_targets = allow;
+        self
+    }
+
+    fn with_id_alphabet(mut self, alphabet: Option<String>) -> Self {
+        self.id_alphabet = alphabet;
+        self
+    }
+
+    fn with_id_generator(mut self, generator: IdGenerator, length: usize) -> Self {
+        self.id_generator = generator;
+        self.id_length = length;
+        self
+    }
+
+    /// hash_id returns a random id for `input`, made of the characters of
+    /// the id alphabet.
+    fn hash_id(&self, input: &str) -> String {
+        match &self.id_alphabet {
+            Some(alphabet) => hash_with_alphabet(input, RANDOM_URL_SIZE, alphabet.as_bytes()),
+            None => hash(input),
+        }
+    }
+
+    fn with_private_targets(mut self, allow: bool) -> Self {
+        self.allow_private_targets = allow;
+        self
+    }
+
+    fn with_allowed_schemes(mut self, schemes: &[String]) -> Self {
+        self.allowed_schemes = schemes.iter().map(|scheme| scheme.to_lowercase()).collect();
+        self
+    }
+
+    fn with_limits(mut self, max_id_length: usize, max_target_length: usize) -> Self {
+        self.max_id_length = max_id_length;
+        self.max_target_length = max_target_length;
+        self
+    }
+
+    /// max_body_size is the size of the largest payload holding a target:
+    /// targets may be percent-encoded in forms, tripling their size.
+    fn max_body_size(&self) -> usize {
+        self.max_target_length * 3 + MAX_BODY_OVERHEAD
+    }
+
+    fn with_max_entries(mut self, max_entries: Option<usize>) -> Self {
+        self.max_entries = max_entries;
+        self
+    }
+
+    /// with_case_insensitive_ids makes short URLs case-insensitive, and
+    /// converts the ids of the existing ones to lowercase. It fails if two of
+    /// them only differ by their case, instead of dropping one of them.
+    fn with_case_insensitive_ids(mut self, case_insensitive: bool) -> Result<Self, String> {
+        self.case_insensitive = case_insensitive;
+        if !case_insensitive {
+            return Ok(self);
+        }
+
+        let mut data = IndexMap::with_capacity(self.data.len());
+        for (id, entry) in self.data.drain(..) {
+            let lowercase = id.to_lowercase();
+            if data.contains_key(&lowercase) {
+                return Err(format!(
+                    "/{} and another short URL only differ by their case",
+                    id
+                ));
+            }
+            data.insert(lowercase, entry);
+        }
+        self.data = data;
+        Ok(self)
+    }
+}
+
+#[test]
+fn test_case_insensitive_ids() {
+    let data = Data::new(vec![
+        ("MyLink".to_string(), "qwerty".to_string()),
+        ("other".to_string(), "asdfgh".to_string()),
+    ]);
+    assert_eq!(None, data.get("mylink"));
+
+    let mut data = data.with_case_insensitive_ids(true).unwrap();
+    assert_eq!(Some(&"qwerty".to_string()), data.get("mylink"));
+    assert_eq!(Some(&"qwerty".to_string()), data.get("MYLINK"));
+    assert!(data.contains("OTHER"));
+
+    data.insert("NEW", "zxcvbnm").unwrap();
+    assert_eq!(
+        vec!["mylink", "other", "new"],
+        data.entries()
+            .map(|(id, _)| id.as_str())
+            .collect::<Vec<_>>()
+    );
+
+    let got = Data::new(vec![
+        ("MyLink".to_string(), "qwerty".to_string()),
+        ("mylink".to_string(), "asdfgh".to_string()),
+    ])
+    .with_case_insensitive_ids(true);
+    assert!(got.is_err());
+}
+
+#[test]
+fn test_insert_data() {
+    use std::env::temp_dir;
+
+    let dir = temp_dir();
+    let tmpfile_path = dir.join("tmpfile2.txt");
+    File::create(&tmpfile_path).unwrap();
+
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    let outcome = data.insert("hi", "qwerty").unwrap();
+    assert_eq!(None, outcome);
+    let created_at = data.get_entry("hi").unwrap().created_at;
+    assert!(created_at.is_some());
+
+    let outcome = data.insert("hi", "zxcvbnm").unwrap();
+    assert_eq!(Some("qwerty".to_string()), outcome);
+    assert_eq!(created_at, data.get_entry("hi").unwrap().created_at);
+
+    let outcome = data.insert("hello", "asdfgh").unwrap();
+    assert_eq!(None, outcome);
+
+    let mut file = File::open(&tmpfile_path).unwrap();
+    let mut got = String::new();
+    file.read_to_string(&mut got).unwrap();
+
+    let created_at = |id| unix_seconds(data.get_entry(id).unwrap().created_at.unwrap());
+    assert_eq!(
+        format!(
+            "---\nhi:\n  target: zxcvbnm\n  created_at: {}\nhello:\n  target: asdfgh\n  created_at: {}\n",
+            created_at("hi"),
+            created_at("hello"),
+        ),
+        got
+    );
+    assert!(!dir.join("tmpfile2.txt.tmp").exists());
+}
+
+#[test]
+fn test_clone_data() {
+    use std::env::temp_dir;
+
+    let tmpfile_path = temp_dir().join("test_clone_data.yml");
+    File::create(&tmpfile_path).unwrap();
+
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    data.insert("hi", "qwerty").unwrap();
+    let before = std::fs::read_to_string(&tmpfile_path).unwrap();
+
+    let mut snapshot = data.clone();
+    assert_eq!(None, snapshot.storage.path());
+    assert_eq!(data.data, snapshot.data);
+
+    // changing the snapshot changes neither the database nor its file
+    snapshot.insert("hello", "asdfgh").unwrap();
+    snapshot.delete("hi").unwrap();
+    assert_eq!(Some(&"qwerty".to_string()), data.get("hi"));
+    assert_eq!(None, data.get("hello"));
+    assert_eq!(Some(tmpfile_path.as_path()), data.storage.path());
+    assert_eq!(before, std::fs::read_to_string(&tmpfile_path).unwrap());
+}
+
+#[test]
+#[tracing_test::traced_test]
+fn test_data_spans() {
+    let tmpfile_path = std::env::temp_dir().join("test_data_spans.yml");
+    File::create(&tmpfile_path).unwrap();
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path);
+
+    data.insert("hi", "qwerty").unwrap();
+    assert!(logs_contain(r#"insert{key="hi"}:persist_entry{key="hi"}"#));
+    assert!(logs_contain("persisted short url"));
+
+    assert_eq!(Some(&"qwerty".to_string()), data.get("hi"));
+    assert!(logs_contain(r#"get{key="hi"}"#));
+    assert!(logs_contain("database lookup key=hi"));
+
+    data.update_entry("hi", |entry| entry.clicks += 1).unwrap();
+    assert!(logs_contain(
+        r#"update_entry{key="hi"}:persist_entry{key="hi"}"#
+    ));
+
+    data.remove("hi").unwrap();
+    assert!(logs_contain(
+        r#"remove{key="hi"}: goto_api: removed short url"#
+    ));
+}
+
+#[test]
+fn test_insert_data_json() {
+    use std::env::temp_dir;
+
+    let dir = temp_dir();
+    let tmpfile_path = dir.join("test_insert_data.json");
+    File::create(&tmpfile_path).unwrap();
+
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    data.insert("hi", "qwerty").unwrap();
+    data.insert("hi", "zxcvbnm").unwrap();
+    data.insert("hello", "asdfgh").unwrap();
+
+    let mut file = File::open(&tmpfile_path).unwrap();
+    let mut got = String::new();
+    file.read_to_string(&mut got).unwrap();
+
+    let created_at = |id| unix_seconds(data.get_entry(id).unwrap().created_at.unwrap());
+    assert_eq!(
+        format!(
+            "{{\n  \"hi\": {{\n    \"target\": \"zxcvbnm\",\n    \"created_at\": {}\n  }},\n  \"hello\": {{\n    \"target\": \"asdfgh\",\n    \"created_at\": {}\n  }}\n}}",
+            created_at("hi"),
+            created_at("hello"),
+        ),
+        got
+    );
+    assert!(!dir.join("test_insert_data.json.tmp").exists());
+}
+
+#[test]
+fn test_transaction() {
+    use std::env::temp_dir;
+
+    let tmpfile_path = temp_dir().join("test_transaction.yml");
+    File::create(&tmpfile_path).unwrap();
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    data.insert("old", "qwerty").unwrap();
+    let before = std::fs::read_to_string(&tmpfile_path).unwrap();
+
+    // renaming is persisted once
+    let renamed = data
+        .transaction(|data| {
+            let target = data.remove("old").unwrap().unwrap().target;
+            assert_eq!(before, std::fs::read_to_string(&tmpfile_path).unwrap());
+            data.insert("new", &target).unwrap();
+            target
+        })
+        .unwrap();
+    assert_eq!("qwerty", renamed);
+    assert_eq!(None, data.get("old"));
+    assert_eq!(Some(&"qwerty".to_string()), data.get("new"));
+    let after = std::fs::read_to_string(&tmpfile_path).unwrap();
+    assert!(after.contains("new:"), "{}", after);
+
+    // and rolled back if it panics half-way
+    let panicked = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        data.transaction(|data| {
+            data.remove("new").unwrap().unwrap();
+            panic!("no more room");
+        })
+    }));
+    assert!(panicked.is_err());
+    assert_eq!(Some(&"qwerty".to_string()), data.get("new"));
+    assert_eq!(1, data.len());
+    assert!(data.storage.path().is_some());
+    assert_eq!(after, std::fs::read_to_string(&tmpfile_path).unwrap());
+
+    // or if it can't be persisted
+    let mut data = data.with_storage(FileBackend::new(
+        temp_dir().join("test_transaction_missing").join("db.yml"),
+        DataFormat::Yaml,
+    ));
+    data.transaction(|data| data.remove("new").unwrap())
+        .unwrap_err();
+    assert_eq!(Some(&"qwerty".to_string()), data.get("new"));
+}
+
+#[test]
+fn test_audit_log() {
+    use std::env::temp_dir;
+
+    let dir = temp_dir();
+    let db_path = dir.join("test_audit_log.yml");
+    let audit_path = dir.join("test_audit_log.ndjson");
+    let _ = std::fs::remove_file(&audit_path);
+    let audit_log = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&audit_path)
+        .unwrap();
+
+    let mut data = Data::new(HashMap::new())
+        .with_persistence(db_path.clone())
+        .with_audit_log(audit_log);
+    data.insert("hi", "qwerty").unwrap();
+    data.insert("hi", "zxcvbnm").unwrap();
+    data.insert("hello", "asdfgh").unwrap();
+    data.delete("hi").unwrap();
+    data.restore("hi").unwrap();
+    data.remove("hello").unwrap();
+
+    // the database only has the short URLs left
+    let persisted = Data::from_entries(
+        DataFormat::Yaml
+            .parse(&std::fs::read_to_string(&db_path).unwrap())
+            .unwrap(),
+    );
+    assert_eq!(1, persisted.len());
+    assert_eq!(Some(&"zxcvbnm".to_string()), persisted.get("hi"));
+
+    // the audit log has every change
+    let got: Vec<serde_json::Value> = std::fs::read_to_string(&audit_path)
+        .unwrap()
+        .lines()
+        .map(|line| serde_json::from_str(line).unwrap())
+        .collect();
+    let ops: Vec<(&str, &str, Option<&str>)> = got
+        .iter()
+        .map(|record| {
+            assert!(record["at"].is_string(), "{}", record);
+            (
+                record["op"].as_str().unwrap(),
+                record["id"].as_str().unwrap(),
+                record["target"].as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            ("insert", "hi", Some("qwerty")),
+            ("insert", "hi", Some("zxcvbnm")),
+            ("insert", "hello", Some("asdfgh")),
+            ("delete", "hi", None),
+            ("restore", "hi", None),
+            ("remove", "hello", None),
+        ],
+        ops
+    );
+
+    // snapshots don't write to the audit log
+    let mut snapshot = data.clone();
+    snapshot.insert("snapshot", "qwerty").unwrap();
+    assert_eq!(
+        got.len(),
+        std::fs::read_to_string(&audit_path)
+            .unwrap()
+            .lines()
+            .count()
+    );
+}
+
+#[test]
+fn test_data_format() {
+    assert_eq!(
+        DataFormat::Json,
+        DataFormat::from_path(Path::new("db.json"))
+    );
+    assert_eq!(DataFormat::Yaml, DataFormat::from_path(Path::new("db.yml")));
+    assert_eq!(DataFormat::Yaml, DataFormat::from_path(Path::new("db")));
+    assert_eq!(
+        DataFormat::Ndjson,
+        DataFormat::from_path(Path::new("db.ndjson"))
+    );
+    assert_eq!(
+        DataFormat::Ndjson,
+        DataFormat::from_path(Path::new("db.jsonl"))
+    );
+
+    let got = DataFormat::Json
+        .parse(r#"{"zebra": "https://z.com", "apple": "https://a.com"}"#)
+        .unwrap();
+    let got: Vec<&String> = got.keys().collect();
+    assert_eq!(vec!["zebra", "apple"], got);
+}
+
+#[test]
+fn test_data_from_reader() {
+    use std::io::Cursor;
+
+    for format in &[DataFormat::Yaml, DataFormat::Json, DataFormat::Ndjson] {
+        let data = Data::from_reader(Cursor::new(b""), *format).unwrap();
+        assert!(data.is_empty(), "{:?}", format);
+        assert_eq!(None, data.storage.path());
+    }
+
+    let data = Data::from_reader(
+        Cursor::new(&b"hello: \"http://world\"\n"[..]),
+        DataFormat::Yaml,
+    )
+    .unwrap();
+    assert_eq!(1, data.len());
+    assert_eq!(Some(&"http://world".to_string()), data.get("hello"));
+
+    let contents = r#"{
+        "hello": "http://world",
+        "gh": {"target": "https://github.com", "clicks": 3},
+        "abc": "https://abc.xyz"
+    }"#;
+    let data = Data::from_reader(Cursor::new(contents.as_bytes()), DataFormat::Json).unwrap();
+    assert_eq!(
+        vec!["hello", "gh", "abc"],
+        data.data.keys().collect::<Vec<_>>()
+    );
+    assert_eq!(Some(&"https://github.com".to_string()), data.get("gh"));
+    assert_eq!(3, data.get_entry("gh").unwrap().clicks);
+
+    let err = Data::from_reader(
+        Cursor::new(&b"hello: \"http://world\"\n"[..]),
+        DataFormat::Json,
+    )
+    .err()
+    .unwrap();
+    assert!(err.contains("expected value"), "{}", err);
+
+    let err = Data::from_reader(Cursor::new(&b"\xF0\x90\x80"[..]), DataFormat::Yaml)
+        .err()
+        .unwrap();
+    assert!(err.starts_with("read data: "), "{}", err);
+}
+
+#[test]
+fn test_ndjson() {
+    let contents = r#"{"id":"foo","target":"https://foo.com","created_at":1600000000,"clicks":2}
+{"id":"bar","target":"https://bar.com"}
+
+{"id":"foo","target":"https://new.foo.com","created_at":1600000000,"clicks":3}
+{"id":"bar","deleted":true}
+{"id":"baz","target":"https://baz.com"}
+"#;
+    let got = DataFormat::Ndjson.parse(contents).unwrap();
+    assert_eq!(vec!["foo", "baz"], got.keys().collect::<Vec<_>>());
+    let foo = &got["foo"];
+    assert_eq!("https://new.foo.com", foo.target);
+    assert_eq!(3, foo.clicks);
+    assert_eq!(
+        Some(UNIX_EPOCH + Duration::from_secs(1600000000)),
+        foo.created_at
+    );
+
+    assert_eq!(
+        "{\"id\":\"foo\",\"clicks\":3,\"created_at\":1600000000,\"target\":\"https://new.foo.com\"}\n\
+         {\"id\":\"baz\",\"target\":\"https://baz.com\"}\n",
+        DataFormat::Ndjson.serialise(&got)
+    );
+
+    let err = DataFormat::Ndjson
+        .parse("{\"id\":\"foo\",\"target\":\"https://foo.com\"}\n{\"id\":\"bar\"}\n")
+        .unwrap_err();
+    assert!(err.starts_with("line 2: "), "{}", err);
+}
+
+#[test]
+fn test_entries_data() {
+    let mut data = Data::new(HashMap::new());
+    data.insert("zebra", "https://z.com").unwrap();
+    data.insert("apple", "https://a.com").unwrap();
+    data.insert("mango", "https://m.com").unwrap();
+    data.insert("zebra", "https://zz.com").unwrap();
+
+    let got: Vec<(&str, &str)> = data
+        .entries()
+        .map(|(id, entry)| (id.as_str(), entry.target.as_str()))
+        .collect();
+    let want = vec![
+        ("zebra", "https://zz.com"),
+        ("apple", "https://a.com"),
+        ("mango", "https://m.com"),
+    ];
+    assert_eq!(want, got);
+
+    data.remove("zebra").unwrap();
+    let got: Vec<&String> = data.entries().map(|(id, _)| id).collect();
+    assert_eq!(vec!["apple", "mango"], got);
+}
+
+#[test]
+fn test_len_data() {
+    let mut data = Data::new(HashMap::new());
+    assert_eq!(0, data.len());
+    assert!(data.is_empty());
+    assert!(!data.contains("hi"));
+
+    data.insert("hi", "qwerty").unwrap();
+    data.insert("hello", "asdfgh").unwrap();
+    data.insert("hi", "zxcvbnm").unwrap();
+    assert_eq!(2, data.len());
+    assert!(!data.is_empty());
+    assert!(data.contains("hi"));
+    assert!(!data.contains("qwerty"));
+}
+
+#[test]
+fn test_remove_data() {
+    use std::env::temp_dir;
+
+    let tmpfile_path = temp_dir().join("test_remove_data.yml");
+
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    data.insert("hi", "qwerty").unwrap();
+    data.insert("hello", "asdfgh").unwrap();
+
+    let removed = data.remove("hi").unwrap().unwrap();
+    assert_eq!("qwerty", removed.target);
+    assert_eq!(None, data.remove("hi").unwrap());
+
+    let mut got = String::new();
+    File::open(&tmpfile_path)
+        .unwrap()
+        .read_to_string(&mut got)
+        .unwrap();
+    let got: Vec<String> = DataFormat::Yaml
+        .parse(&got)
+        .unwrap()
+        .keys()
+        .cloned()
+        .collect();
+    assert_eq!(vec!["hello".to_string()], got);
+}
+
+#[test]
+fn test_delete_data() {
+    use std::env::temp_dir;
+
+    let tmpfile_path = temp_dir().join("test_delete_data.yml");
+
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    data.insert("hi", "qwerty").unwrap();
+
+    assert!(data.delete("hi").unwrap());
+    assert!(!data.delete("hi").unwrap());
+    assert!(!data.delete("hello").unwrap());
+    assert_eq!(None, data.get("hi"));
+    assert!(data.contains("hi"));
+    assert!(data.get_entry("hi").unwrap().is_deleted());
+
+    let mut got = String::new();
+    File::open(&tmpfile_path)
+        .unwrap()
+        .read_to_string(&mut got)
+        .unwrap();
+    let parsed = DataFormat::Yaml.parse(&got).unwrap();
+    // deleted_at has the precision of the stored times
+    assert_eq!(
+        data.get_entry("hi").unwrap().deleted_at,
+        parsed["hi"].deleted_at
+    );
+    assert!(got.starts_with("---\nhi:\n  target: qwerty\n  created_at: "));
+    assert!(got.contains("\n  deleted_at: "));
+
+    assert!(data.restore("hi").unwrap());
+    assert!(!data.restore("hello").unwrap());
+    assert_eq!(Some(&"qwerty".to_string()), data.get("hi"));
+
+    let mut got = String::new();
+    File::open(&tmpfile_path)
+        .unwrap()
+        .read_to_string(&mut got)
+        .unwrap();
+    assert!(!got.contains("deleted_at"), "{}", got);
+}
+
+#[test]
+fn test_hard_delete_data() {
+    let mut data = Data::new(HashMap::new()).with_hard_delete(true);
+    data.insert("hi", "qwerty").unwrap();
+
+    assert!(data.delete("hi").unwrap());
+    assert!(!data.contains("hi"));
+    assert!(!data.restore("hi").unwrap());
+}
+
+#[test]
+fn test_click_data() {
+    use std::env::temp_dir;
+
+    let tmpfile_path = temp_dir().join("test_click_data.yml");
+
+    let mut data = Data::new(HashMap::new()).with_persistence(tmpfile_path.clone());
+    let entry = Entry {
+        max_clicks: Some(2),
+        ..Entry::from("qwerty".to_string())
+    };
+    data.insert_entry("hi", entry).unwrap();
+
+    assert!(data.click("hi").unwrap());
+    assert_eq!(1, data.get_entry("hi").unwrap().clicks);
+    let last_accessed = unix_seconds(data.get_entry("hi").unwrap().last_accessed.unwrap());
+
+    let mut got = String::new();
+    File::open(&tmpfile_path)
+        .unwrap()
+        .read_to_string(&mut got)
+        .unwrap();
+    assert_eq!(
+        format!(
+            "---\nhi:\n  target: qwerty\n  clicks: 1\n  last_accessed: {}\n  max_clicks: 2\n",
+            last_accessed
+        ),
+        got
+    );
+    let parsed = DataFormat::Yaml.parse(&got).unwrap();
+    assert_eq!(data.get_entry("hi"), parsed.get("hi"));
+
+    assert!(data.click("hi").unwrap());
+    assert!(!data.contains("hi"));
+    assert!(!data.click("hi").unwrap());
+
+    let mut got = String::new();
+    File::open(&tmpfile_path)
+        .unwrap()
+        .read_to_string(&mut got)
+        .unwrap();
+    assert_eq!("---\n{}\n".to_string(), got);
+}
+
+#[test]
+fn test_update_entry() {
+    let mut data = Data::new(vec![("hi".to_string(), "qwerty".to_string())]);
+
+    assert!(data.update_entry("hi", |entry| entry.clicks = 42).unwrap());
+    assert_eq!(42, data.get_entry("hi").unwrap().clicks);
+
+    assert!(!data
+        .update_entry("unknown", |_| panic!("there is no such entry"))
+        .unwrap());
+    assert!(!data.contains("unknown"));
+}
+
+#[test]
+fn test_load_old_format() {
+    let old = "---\nhi: qwerty\nhello:\n  target: asdfgh\n  created_at: 1600000000\n  clicks: 42\n";
+
+    let parsed = DataFormat::Yaml.parse(old).unwrap();
+    assert_eq!(Entry::from("qwerty".to_string()), parsed["hi"]);
+    assert_eq!(
+        Entry {
+            created_at: Some(UNIX_EPOCH + Duration::from_secs(1_600_000_000)),
+            clicks: 42,
+            ..Entry::from("asdfgh".to_string())
+        },
+        parsed["hello"]
+    );
+
+    // migrated entries are written in the new format once clicked
+    let mut data = Data::from_entries(parsed);
+    assert!(data.click("hi").unwrap());
+    let last_accessed = unix_seconds(data.get_entry("hi").unwrap().last_accessed.unwrap());
+    assert_eq!(
+        format!(
+            "---\nhi:\n  target: qwerty\n  clicks: 1\n  last_accessed: {}\nhello:\n  target: asdfgh\n  created_at: 1600000000\n  clicks: 42\n",
+            last_accessed
+        ),
+        DataFormat::Yaml.serialise(&data.data)
+    );
+}
+
+#[test]
+fn test_cursor() {
+    let cursor = encode_cursor("hello/world?");
+    assert_eq!(Some("hello/world?".to_string()), decode_cursor(&cursor));
+    assert_eq!(None, decode_cursor("%%%"));
+}
+
+#[test]
+fn test_serialise() {
+    let mut database: HashMap<String, String> = HashMap::new();
+    database.insert(
+        "tsauvajon".to_string(),
+        "https://linkedin.com/in/tsauvajon".to_string(),
+    );
+    database.insert("a: b".to_string(), "http://hello.world/#\"".to_string());
+
+    let got = DataFormat::Yaml.serialise(&Data::new(database.clone()).data);
+
+    let parsed: HashMap<String, String> = serde_yaml::from_str(&got).unwrap();
+    assert_eq!(database, parsed);
+}
+
+#[test]
+fn test_serialise_round_trip() {
+    let entries = vec![
+        (
+            "unicode".to_string(),
+            Entry {
+                clicks: 3,
+                ..Entry::new("https://example.com/café/日本語/🦀".to_string())
+            },
+        ),
+        (
+            "colon".to_string(),
+            Entry::new("https://example.com:8080/a:b".to_string()),
+        ),
+        (
+            "émoji-🦀".to_string(),
+            Entry::from("https://🦀.rs".to_string()),
+        ),
+        (
+            "quotes".to_string(),
+            Entry::from("https://example.com/?q=\"it's\"".to_string()),
+        ),
+    ];
+
+    for format in &[DataFormat::Yaml, DataFormat::Json, DataFormat::Ndjson] {
+        let data = Data::from_entries(entries.clone());
+        let parsed = format.parse(&format.serialise(&data.data)).unwrap();
+        // entries keep their order
+        assert_eq!(
+            entries,
+            parsed.into_iter().collect::<Vec<_>>(),
+            "{:?}",
+            format
+        );
+    }
+}
+
+#[derive(Clone)]
+struct Db {
+    data: web::Data<RwLock<Data>>,
+}
+
+impl Db {
+    fn read(
+        &self,
+    ) -> Result<
+        std::sync::RwLockReadGuard<'_, Data>,
+        std::sync::PoisonError<std::sync::RwLockReadGuard<'_, Data>>,
+    > {
+        self.data.read()
+    }
+
+    fn write(
+        &self,
+    ) -> Result<
+        std::sync::RwLockWriteGuard<'_, Data>,
+        std::sync::PoisonError<std::sync::RwLockWriteGuard<'_, Data>>,
+    > {
+        self.data.write()
+    }
+
+    fn new(data: Data) -> Self {
+        Db {
+            data: web::Data::new(RwLock::new(data)),
+        }
+    }
+}
+
+/// Preview makes browse show a page telling where a short URL leads, instead
+/// of redirecting right away. The page redirects by itself after `delay`
+/// seconds.
+#[derive(Clone, Debug, PartialEq)]
+struct Preview {
+    delay: u32,
+    /// Name of the cookie of the browsers that trust goto, and skip the
+    /// preview page.
+    cookie_name: String,
+    /// Key signing the trust cookies. It is random, so they are no longer
+    /// valid once goto restarts.
+    key: [u8; 32],
+}
+
+impl Preview {
+    fn new(delay: u32, cookie_name: &str) -> Self {
+        Preview {
+            delay,
+            cookie_name: cookie_name.to_string(),
+            key: rand::random(),
+        }
+    }
+
+    /// trust_token returns the value of a trust cookie valid until `expires`,
+    /// in seconds since the Unix epoch: `<expires>.<signature>`.
+    fn trust_token(&self, expires: u64) -> String {
+        format!(
+            "{}.{}",
+            expires,
+            base64::encode(self.sign(expires).as_bytes())
+        )
+    }
+
+    fn sign(&self, expires: u64) -> blake3::Hash {
+        blake3::keyed_hash(&self.key, expires.to_string().as_bytes())
+    }
+
+    /// is_trusted tells whether `token` is a trust cookie signed by goto,
+    /// which hasn't expired.
+    fn is_trusted(&self, token: &str) -> bool {
+        use std::convert::TryInto;
+
+        let (expires, signature) = match token.split_once('.') {
+            Some(parts) => parts,
+            None => return false,
+        };
+        let expires: u64 = match expires.parse() {
+            Ok(expires) => expires,
+            Err(_) => return false,
+        };
+        let signature: [u8; 32] = match base64::decode(signature)
+            .ok()
+            .and_then(|signature| signature.try_into().ok())
+        {
+            Some(signature) => signature,
+            None => return false,
+        };
+
+        // comparing blake3 hashes takes constant time
+        expires > unix_seconds(SystemTime::now())
+            && self.sign(expires) == blake3::Hash::from(signature)
+    }
+
+    /// trust_cookie returns a `Set-Cookie` header value making the browser
+    /// skip the preview page for 30 days.
+    fn trust_cookie(&self) -> String {
+        let expires = unix_seconds(SystemTime::now()) + TRUST_COOKIE_MAX_AGE_SECS;
+        format!(
+            "{}={}; Max-Age={}; Path=/; HttpOnly; SameSite=Lax",
+            self.cookie_name,
+            self.trust_token(expires),
+            TRUST_COOKIE_MAX_AGE_SECS
+        )
+    }
+}
+
+/// RedirectBody is the template of the body of redirects, where `{url}` is
+/// replaced by the long URL and `{id}` by the short URL.
+#[derive(Clone, Debug, PartialEq)]
+struct RedirectBody(String);
+
+impl Default for RedirectBody {
+    fn default() -> Self {
+        RedirectBody(DEFAULT_REDIRECT_BODY.to_string())
+    }
+}
+
+impl RedirectBody {
+    fn render(&self, url: &str, id: &str) -> String {
+        self.0.replace("{id}", id).replace("{url}", url)
+    }
+}
+
+/// parse_redirect_body reads the --redirect-body flag, which must tell where
+/// the redirect leads.
+fn parse_redirect_body(template: &str) -> Result<RedirectBody, String> {
+    if !template.contains("{url}") {
+        return Err("the template must contain {url}".to_string());
+    }
+    Ok(RedirectBody(template.to_string()))
+}
+
+/// parse_cookie_name reads the --cookie-name flag: letters, digits, `-` and
+/// `_`.
+fn parse_cookie_name(name: &str) -> Result<String, String> {
+    if !is_valid_id(name) {
+        return Err("the cookie name can only contain letters, digits, '-' and '_'".to_string());
+    }
+    Ok(name.to_string())
+}
+
+/// parse_prefix reads the --prefix flag: a path starting with `/`, but not
+/// ending with one, e.g. `/goto`.
+fn parse_prefix(prefix: &str) -> Result<String, String> {
+    if !prefix.starts_with('/') || prefix.ends_with('/') {
+        return Err("the prefix must start with '/' and not end with '/'".to_string());
+    }
+    if !prefix
+        .chars()
+        .all(|c| c.is_ascii_alphanumeric() || "/-_.".contains(c))
+    {
+        return Err(
+            "the prefix can only contain letters, digits, '/', '-', '_' and '.'".to_string(),
+        );
+    }
+    Ok(prefix.to_string())
+}
+
+#[derive(serde::Deserialize)]
+struct BrowseQuery {
+    preview: Option<String>,
+    /// Set by the preview page's link, to remember that the browser trusts
+    /// goto.
+    trust: Option<String>,
+}
+
+/// BaseUrl is the public URL of goto, e.g. when it runs behind a reverse
+/// proxy. Short URLs are shown relative to it.
+#[derive(Clone, Debug, PartialEq)]
+struct BaseUrl(String);
+
+impl BaseUrl {
+    fn short_url(&self, id: &str) -> String {
+        format!("{}/{}", self.0.trim_end_matches('/'), id)
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+/// Prefix is the path goto is served under with --prefix, e.g. `/goto`, or
+/// an empty string.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Prefix(String);
+
+impl Prefix {
+    /// of returns the prefix given to the app, or an empty string if there
+    /// is none.
+    fn of(prefix: &Option<web::Data<Prefix>>) -> &str {
+        prefix.as_ref().map_or("", |prefix| prefix.0.as_str())
+    }
+}
+
+/// full_short_url is the URL a short URL is reached at: it is relative to
+/// the base URL if it is known, or else to the host and scheme the request
+/// was sent to.
+fn full_short_url(req: &HttpRequest, base_url: Option<&BaseUrl>, prefix: &str, id: &str) -> String {
+    match base_url {
+        Some(base_url) => base_url.short_url(id),
+        None => {
+            let connection_info = req.connection_info();
+            format!(
+                "{}://{}{}/{}",
+                connection_info.scheme(),
+                connection_info.host(),
+                prefix,
+                id
+            )
+        }
+    }
+}
+
+#[test]
+fn test_full_short_url() {
+    let req = actix_web::test::TestRequest::default()
+        .header("Host", "go.to")
+        .to_http_request();
+    assert_eq!("http://go.to/hi", full_short_url(&req, None, "", "hi"));
+    assert_eq!(
+        "http://go.to/goto/hi",
+        full_short_url(&req, None, "/goto", "hi")
+    );
+
+    let req = actix_web::test::TestRequest::default()
+        .header("Host", "go.to")
+        .header("X-Forwarded-Proto", "https")
+        .to_http_request();
+    assert_eq!(
+        "https://go.to/goto/hi",
+        full_short_url(&req, None, "/goto", "hi")
+    );
+
+    let base_url = BaseUrl("https://go.company.com/goto".to_string());
+    assert_eq!(
+        "https://go.company.com/goto/hi",
+        full_short_url(&req, Some(&base_url), "/goto", "hi")
+    );
+}
+
+/// redirects_message tells that /{id} now redirects to `target`, with the
+/// full short URL when the base URL is known.
+fn redirects_message(id: &str, target: &str, base_url: Option<&BaseUrl>) -> String {
+    match base_url {
+        Some(base_url) => format!(
+            "/{} now redirects to {} (short URL: {})",
+            id,
+            target,
+            base_url.short_url(id)
+        ),
+        None => format!("/{} now redirects to {}", id, target),
+    }
+}
+
+/// related_links lists the stats and the QR code of /{id} in the format of
+/// a `Link` header, with full URLs when the base URL is known, and paths
+/// under `prefix` otherwise.
+fn related_links(id: &str, base_url: Option<&BaseUrl>, prefix: &str) -> String {
+    let url = |path: String| match base_url {
+        Some(base_url) => base_url.short_url(&path),
+        None => format!("{}/{}", prefix, path),
+    };
+
+    format!(
+        "<{}>; rel=\"stats\", <{}>; rel=\"qr\"",
+        url(format!("api/{}/stats", id)),
+        url(format!("{}/qr", id))
+    )
+}
+
+#[test]
+fn test_related_links() {
+    assert_eq!(
+        r#"</api/hi/stats>; rel="stats", </hi/qr>; rel="qr""#,
+        related_links("hi", None, "")
+    );
+    assert_eq!(
+        r#"</goto/api/hi/stats>; rel="stats", </goto/hi/qr>; rel="qr""#,
+        related_links("hi", None, "/goto")
+    );
+    assert_eq!(
+        r#"<https://go.company.com/api/hi/stats>; rel="stats", <https://go.company.com/hi/qr>; rel="qr""#,
+        related_links(
+            "hi",
+            Some(&BaseUrl("https://go.company.com/".to_string())),
+            "/goto"
+        )
+    );
+}
+
+/// escape_html escapes the characters that have a meaning in HTML, so that
+/// `input` can be safely embedded in a page or an attribute.
+fn escape_html(input: &str) -> String {
+    let mut escaped = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// render_preview renders the page shown instead of redirecting to `target`
+/// when previews are enabled. Its link goes through /{id} again, to remember
+/// that the browser trusts goto.
+fn render_preview(id: &str, target: &str, delay: u32) -> String {
+    let id = escape_html(id);
+    let target = escape_html(target);
+    format!(
+        r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<meta http-equiv="refresh" content="{delay}; url={target}">
+<title>Redirecting to {target}</title>
+</head>
+<body>
+<p>This link redirects to {target}.</p>
+<p><a href="{id}?preview=0&amp;trust=1">Continue, and don't ask again.</a></p>
+</body>
+</html>
+"#,
+        delay = delay,
+        id = id,
+        target = target
+    )
+}
+
+/// browse redirects to the long URL hidden behind a short URL, or returns a
+/// 404 not found error if the short URL doesn't exist, or a 410 gone error if
+/// it was deleted.
+/// When previews are enabled, it shows a page linking to the long URL
+/// instead, unless `?preview=0` is passed or the browser has the trust cookie,
+/// which `?trust=1` sets.
+/// Every redirect is counted, and short URLs with a maximum number of clicks
+/// are removed after the last one. Redirects link to the stats and the QR
+/// code of the short URL.
+#[get("/{id}")]
+async fn browse(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    preview: Option<web::Data<Preview>>,
+    redirect_body: Option<web::Data<RedirectBody>>,
+    events: Option<web::Data<Events>>,
+    base_url: Option<web::Data<BaseUrl>>,
+    prefix: Option<web::Data<Prefix>>,
+    web::Path(id): web::Path<String>,
+    query: web::Query<BrowseQuery>,
+) -> Result<HttpResponse, Error> {
+    let start = Instant::now();
+    let url = {
+        let db = db.read().map_err(|err| {
+            tracing::error!(error = %err, "accessing the db");
+            error::ErrorInternalServerError(err.to_string())
+        })?;
+
+        check_id(&id, db.max_id_length)?;
+
+        match db.get_entry(&id) {
+            None => return Err(error::ErrorNotFound("not found")),
+            Some(entry) if entry.is_deleted() => return Err(error::ErrorGone("gone")),
+            Some(entry) if entry.is_expired() => return Err(error::ErrorGone("expired")),
+            Some(entry) => entry.target.clone(),
+        }
+    };
+    ServerTimings::record(&req, "db-lookup", start.elapsed());
+
+    let url = &url;
+    let preview = preview.as_ref().map(|preview| preview.get_ref());
+    // browsers that trust goto skip the preview
+    let trusted = preview.map_or(false, |preview| {
+        req.cookie(&preview.cookie_name)
+            .map_or(false, |cookie| preview.is_trusted(cookie.value()))
+    });
+    let start = Instant::now();
+    match preview.filter(|_| query.preview.as_deref() != Some("0") && !trusted) {
+        Some(preview) => {
+            let response = HttpResponse::Ok()
+                .content_type("text/html; charset=utf-8")
+                .body(render_preview(&id, url, preview.delay));
+            ServerTimings::record(&req, "preview", start.elapsed());
+            Ok(response)
+        }
+        None => {
+            // only redirections count as clicks: previews don't
+            {
+                let mut db = db.write().map_err(|err| {
+                    tracing::error!(error = %err, "accessing the db");
+                    error::ErrorInternalServerError(err.to_string())
+                })?;
+
+                // another request may have used the last click, or deleted
+                // the short URL in the meantime
+                if !db.click(&id)? {
+                    return Err(error::ErrorNotFound("not found"));
+                }
+                if !db.contains(&id) {
+                    tracing::info!(id = %id, "short url reached its maximum number of clicks");
+                }
+            }
+
+            if let Some(events) = events {
+                events.publish(&id, url);
+            }
+
+            let body = match redirect_body {
+                Some(redirect_body) => redirect_body.render(url, &id),
+                None => RedirectBody::default().render(url, &id),
+            };
+            let mut response = HttpResponse::Found();
+            response.header("Location", url.to_string());
+            response.header(
+                header::LINK,
+                related_links(
+                    &id,
+                    base_url.as_ref().map(|base_url| base_url.get_ref()),
+                    Prefix::of(&prefix),
+                ),
+            );
+            if let Some(preview) = preview.filter(|_| query.trust.as_deref() == Some("1")) {
+                response.header(header::SET_COOKIE, preview.trust_cookie());
+            }
+            let response = response.body(body);
+            ServerTimings::record(&req, "redirect", start.elapsed());
+            Ok(response)
+        }
+    }
+}
+
+/// exists tells whether a short URL exists, without redirecting: it returns
+/// a 200 OK with the `Location` header set to the long URL, or a 404 not
+/// found error if the short URL doesn't exist, or a 410 gone error if it was
+/// deleted or it expired.
+#[head("/{id}")]
+async fn exists(db: web::Data<Db>, web::Path(id): web::Path<String>) -> impl Responder {
+    match db.read() {
+        Ok(db) => match db.get_entry(&id) {
+            None => HttpResponse::NotFound().finish(),
+            Some(entry) if entry.is_deleted() || entry.is_expired() => {
+                HttpResponse::Gone().finish()
+            }
+            Some(entry) => HttpResponse::Ok()
+                .header("Location", entry.target.to_string())
+                .finish(),
+        },
+        Err(err) => {
+            tracing::error!(error = %err, "accessing the db");
+            HttpResponse::InternalServerError().finish()
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct Health {
+    status: &'static str,
+    version: &'static str,
+    entries: usize,
+}
+
+/// FrontIndex is the index.html of the front-end, and the path the API is
+/// served under, which the front-end needs to call it.
+#[derive(Clone)]
+struct FrontIndex {
+    path: PathBuf,
+    api_base: String,
+}
+
+/// front_index serves the index.html of the front-end, telling it where the
+/// API is with a `<meta name="api-base">` tag.
+async fn front_index(front: web::Data<FrontIndex>) -> impl Responder {
+    let html = std::fs::read_to_string(&front.path)
+        .map_err(|err| error::ErrorNotFound(format!("front-end not found: {}", err)))?;
+    let meta = format!(
+        "<head>\n    <meta name=\"api-base\" content=\"{}\">",
+        escape_html(&front.api_base)
+    );
+
+    Ok::<_, Error>(
+        HttpResponse::Ok()
+            .content_type("text/html; charset=utf-8")
+            .body(html.replacen("<head>", &meta, 1)),
+    )
+}
+
+/// health tells that the API is up, with its version and how many short
+/// URLs it holds, including the deleted ones.
+#[get("/health")]
+async fn health(db: web::Data<Db>) -> impl Responder {
+    let db = db
+        .read()
+        .map_err(|err| error::ErrorInternalServerError(err.to_string()))?;
+
+    Ok::<_, Error>(HttpResponse::Ok().json(Health {
+        status: "ok",
+        version: env!("CARGO_PKG_VERSION"),
+        entries: db.len(),
+    }))
+}
+
+#[derive(serde::Serialize)]
+struct Exists {
+    exists: bool,
+}
+
+/// url_exists tells whether a short URL exists as `{"exists": true}`, or
+/// `{"exists": false}` if it doesn't, was deleted or expired. Unlike
+/// `exists`, it always returns a 200 OK, for monitoring tools that treat 404
+/// not found errors as failures. Clicks are not counted.
+#[get("/api/url/{id}/exists")]
+async fn url_exists(db: web::Data<Db>, web::Path(id): web::Path<String>) -> impl Responder {
+    let db = db
+        .read()
+        .map_err(|err| error::ErrorInternalServerError(err.to_string()))?;
+
+    Ok::<_, Error>(HttpResponse::Ok().json(Exists {
+        exists: db.get(&id).is_some(),
+    }))
+}
+
+#[derive(serde::Serialize)]
+struct UrlStats<'a> {
+    target: &'a str,
+    clicks: u64,
+    /// RFC 3339 timestamps, null when unknown.
+    created: Option<String>,
+    last_accessed: Option<String>,
+    expires: Option<String>,
+}
+
+/// rfc3339 formats `time` as an RFC 3339 timestamp, e.g.
+/// 2021-05-19T17:36:49Z.
+fn rfc3339(time: SystemTime) -> String {
+    humantime::format_rfc3339_seconds(time).to_string()
+}
+
+/// url_stats returns the usage statistics of a short URL, or a 404 not found
+/// error if it doesn't exist, was deleted or expired.
+#[get("/api/{id}/stats")]
+async fn url_stats(db: web::Data<Db>, web::Path(id): web::Path<String>) -> impl Responder {
+    let db = db
+        .read()
+        .map_err(|err| error::ErrorInternalServerError(err.to_string()))?;
+
+    let entry = match db.get_entry(&id) {
+        Some(entry) if !entry.is_deleted() && !entry.is_expired() => entry,
+        _ => return Err(error::ErrorNotFound("not found")),
+    };
+
+    Ok(HttpResponse::Ok().json(UrlStats {
+        target: &entry.target,
+        clicks: entry.clicks,
+        created: entry.created_at.map(rfc3339),
+        last_accessed: entry.last_accessed.map(rfc3339),
+        expires: entry.expires_at.map(rfc3339),
+    }))
+}
+
+#[derive(serde::Deserialize)]
+struct QrQuery {
+    size: Option<u32>,
+}
+
+/// qr_code returns a PNG QR code encoding the full short URL, or returns a
+/// 404 not found error if the short URL doesn't exist.
+/// The size of the image can be set with `?size=<pixels>`.
+#[get("/{id}/qr")]
+async fn qr_code(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    base_url: Option<web::Data<BaseUrl>>,
+    prefix: Option<web::Data<Prefix>>,
+    web::Path(id): web::Path<String>,
+    query: web::Query<QrQuery>,
+) -> impl Responder {
+    let size = query.size.unwrap_or(DEFAULT_QR_SIZE);
+    if size == 0 || size > MAX_QR_SIZE {
+        return Err(error::ErrorBadRequest(format!(
+            "size must be between 1 and {}",
+            MAX_QR_SIZE
+        )));
+    }
+
+    match db.read() {
+        Ok(db) => {
+            if db.get(&id).is_none() {
+                return Err(error::ErrorNotFound("not found"));
+            }
+        }
+        Err(err) => {
+            tracing::error!(error = %err, "accessing the db");
+            return Err(error::ErrorInternalServerError(err.to_string()));
+        }
+    }
+
+    let short_url = full_short_url(
+        &req,
+        base_url.as_ref().map(|base_url| base_url.get_ref()),
+        Prefix::of(&prefix),
+        &id,
+    );
+    let png = render_qr_code(&short_url, size).map_err(error::ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok().content_type("image/png").body(png))
+}
+
+/// render_qr_code encodes `data` into a `size`x`size` PNG QR code.
+fn render_qr_code(data: &str, size: u32) -> Result<Vec<u8>, String> {
+    let code = qrcode::QrCode::new(data.as_bytes()).map_err(|err| err.to_string())?;
+    let image = code
+        .render::<image::Luma<u8>>()
+        .min_dimensions(size, size)
+        .max_dimensions(size, size)
+        .build();
+
+    let mut png = Vec::new();
+    image::DynamicImage::ImageLuma8(image)
+        .write_to(&mut png, image::ImageOutputFormat::Png)
+        .map_err(|err| format!("encode png: {}", err))?;
+
+    Ok(png)
+}
+
+/// hash returns a short hash of the string passed as a parameter.
+fn hash(input: &str) -> String {
+    blake3::hash(input.as_bytes()).to_hex()[..RANDOM_URL_SIZE].to_string()
+}
+
+/// hash_with_alphabet returns a hash of `input`, `length` characters long,
+/// made of the characters of `alphabet`: each byte of the blake3 hash picks
+/// a character, modulo the size of the alphabet.
+/// The alphabet must not be empty.
+fn hash_with_alphabet(input: &str, length: usize, alphabet: &[u8]) -> String {
+    let mut bytes = vec![0; length];
+    blake3::Hasher::new()
+        .update(input.as_bytes())
+        .finalize_xof()
+        .fill(&mut bytes);
+
+    bytes
+        .iter()
+        .map(|byte| alphabet[*byte as usize % alphabet.len()] as char)
+        .collect()
+}
+
+/// parse_id_alphabet reads the --id-alphabet flag: `base62`, or the
+/// characters random ids are made of.
+fn parse_id_alphabet(alphabet: &str) -> Result<String, String> {
+    if alphabet == "base62" {
+        return Ok(BASE62_ALPHABET.to_string());
+    }
+
+    if alphabet.chars().count() < 2 {
+        return Err("the alphabet needs at least 2 characters".to_string());
+    }
+    if !is_valid_id(alphabet) {
+        return Err("ids can only contain letters, digits, '_' and '-'".to_string());
+    }
+    let unique: HashSet<char> = alphabet.chars().collect();
+    if unique.len() != alphabet.len() {
+        return Err("the alphabet contains duplicate characters".to_string());
+    }
+    Ok(alphabet.to_string())
+}
+
+/// hashed_id picks the id of a new short URL redirecting to `target`: the
+/// hash of the target, unless another short URL already uses it. It then
+/// tries the hashes of `target1`, `target2`, etc., and falls back to a random
+/// id. Deleted or expired short URLs keep their id taken, even when they
+/// redirect to the same target. The returned id is already used if a short URL
+/// redirecting to the same target was found on the way, or if collision
+/// retries are disabled.
+fn hashed_id(db: &Data, target: &str) -> String {
+    let id = db.hash_id(target);
+    if !db.hash_collision_retry {
+        return id;
+    }
+
+    let candidates = std::iter::once(id)
+        .chain((1..MAX_HASH_ATTEMPTS).map(|attempt| db.hash_id(&format!("{}{}", target, attempt))));
+    for id in candidates {
+        match db.get_entry(&id) {
+            Some(entry) if entry.target != target || entry.is_deleted() || entry.is_expired() => {
+                continue
+            }
+            _ => return id,
+        }
+    }
+
+    tracing::warn!(target = %target, "every hash is taken, picking a random id");
+    loop {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos();
+        let id = db.hash_id(&format!("{}{}", target, nanos));
+        if !db.contains(&id) {
+            return id;
+        }
+    }
+}
+
+/// nanoid returns `length` random characters of `alphabet`, which must not
+/// be empty.
+fn nanoid(length: usize, alphabet: &[u8]) -> String {
+    use rand::Rng;
+
+    let mut rng = rand::thread_rng();
+    (0..length)
+        .map(|_| alphabet[rng.gen_range(0..alphabet.len())] as char)
+        .collect()
+}
+
+/// random_id picks the id of a new short URL redirecting to `target`, with
+/// the id generator of the database. Random ids made with
+/// `IdGenerator::Nanoid` use the id alphabet, or `NANOID_ALPHABET`.
+fn random_id(db: &Data, target: &str) -> String {
+    match db.id_generator {
+        IdGenerator::Hash => hashed_id(db, target),
+        IdGenerator::Nanoid => {
+            let alphabet = db.id_alphabet.as_deref().unwrap_or(NANOID_ALPHABET);
+            loop {
+                let id = nanoid(db.id_length, alphabet.as_bytes());
+                if !db.contains(&id) {
+                    return id;
+                }
+            }
+        }
+    }
+}
+
+/// is_valid_id tells whether `id` can be used as a short URL: it must be
+/// made of letters, digits, `_` or `-`, so it never needs to be
+/// percent-encoded.
+fn is_valid_id(id: &str) -> bool {
+    !id.is_empty()
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// check_id makes sure `id` is at most `max_length` characters long and
+/// only contains allowed characters.
+fn check_id(id: &str, max_length: usize) -> Result<(), UpsertError> {
+    if id.len() > max_length {
+        return Err(UpsertError::IdTooLong);
+    }
+
+    if !is_valid_id(id) {
+        return Err(UpsertError::InvalidId);
+    }
+
+    Ok(())
+}
+
+/// Read a string body from an actix_web Payload, up to `max_size` bytes.
+async fn read_body(mut payload: web::Payload, max_size: usize) -> Result<String, String> {
+    let mut body = web::BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk.map_err(|err| err.to_string())?;
+        // limit max size of in-memory payload
+        if (body.len() + chunk.len()) > max_size {
+            return Err("overflow".to_string());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    String::from_utf8(body[..].to_vec()).map_err(|err| format!("invalid request body: {}", err))
+}
+
+/// TargetBody is a JSON body holding the target of a short URL.
+#[derive(serde::Deserialize)]
+struct TargetBody {
+    target: String,
+}
+
+/// Read a target from an actix_web Payload, up to `max_size` bytes, see
+/// `parse_target`.
+async fn read_target_with_content_type(
+    payload: web::Payload,
+    content_type: Option<&str>,
+    max_size: usize,
+) -> Result<String, String> {
+    let body = read_body(payload, max_size).await?;
+    parse_target(body, content_type)
+}
+
+/// content_type returns the Content-Type header of a request, if any.
+fn content_type(req: &HttpRequest) -> Option<&str> {
+    req.headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+}
+
+/// parse_target reads a target from a request body, depending on its
+/// content type: the `target` field of a JSON object sent as
+/// application/json, the `target` field of a form sent as
+/// application/x-www-form-urlencoded, or else the raw body.
+///
+/// `curl -d <url>` sends raw URLs as forms, so form bodies that are a URL are
+/// used as is.
+fn parse_target(body: String, content_type: Option<&str>) -> Result<String, String> {
+    let content_type = content_type.unwrap_or_default();
+    if content_type.contains("application/json") {
+        return serde_json::from_str::<TargetBody>(&body)
+            .map(|body| body.target)
+            .map_err(|err| format!("invalid request body: {}", err));
+    }
+
+    if !content_type.starts_with("application/x-www-form-urlencoded") || Url::parse(&body).is_ok() {
+        return Ok(body);
+    }
+
+    url::form_urlencoded::parse(body.as_bytes())
+        .find(|(key, _)| key == "target")
+        .map(|(_, target)| target.into_owned())
+        .ok_or_else(|| "missing target field".to_string())
+}
+
+/// UpsertError is the reason why a short URL couldn't be created or updated.
+#[derive(Debug)]
+enum UpsertError {
+    AlreadyExists,
+    NotFound,
+    IdTooLong,
+    InvalidId,
+    ReservedId,
+    TargetTooLong,
+    MalformedUrl(url::ParseError),
+    ForbiddenTarget,
+    DisallowedScheme,
+    DatabaseFull,
+    PersistenceError(std::io::Error),
+    PoisonedLock(String),
+    Canceled,
+}
+
+impl std::fmt::Display for UpsertError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UpsertError::AlreadyExists => write!(f, "already registered"),
+            UpsertError::NotFound => write!(f, "not found"),
+            UpsertError::IdTooLong => write!(f, "id too long"),
+            UpsertError::InvalidId => write!(f, "invalid id"),
+            UpsertError::ReservedId => write!(f, "reserved id"),
+            UpsertError::TargetTooLong => write!(f, "target URL too long"),
+            UpsertError::MalformedUrl(err) => write!(f, "malformed URL: {}", err),
+            UpsertError::ForbiddenTarget => write!(f, "target URL not allowed"),
+            UpsertError::DisallowedScheme => write!(f, "target URL scheme not allowed"),
+            UpsertError::DatabaseFull => write!(f, "too many short URLs"),
+            UpsertError::PersistenceError(err) => write!(f, "persist database: {}", err),
+            UpsertError::PoisonedLock(err) => write!(f, "{}", err),
+            UpsertError::Canceled => write!(f, "operation canceled"),
+        }
+    }
+}
+
+impl error::ResponseError for UpsertError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            UpsertError::AlreadyExists => StatusCode::CONFLICT,
+            UpsertError::NotFound => StatusCode::NOT_FOUND,
+            UpsertError::IdTooLong
+            | UpsertError::InvalidId
+            | UpsertError::ReservedId
+            | UpsertError::TargetTooLong
+            | UpsertError::MalformedUrl(_)
+            | UpsertError::ForbiddenTarget
+            | UpsertError::DisallowedScheme => StatusCode::BAD_REQUEST,
+            UpsertError::DatabaseFull => StatusCode::INSUFFICIENT_STORAGE,
+            UpsertError::PersistenceError(_)
+            | UpsertError::PoisonedLock(_)
+            | UpsertError::Canceled => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<std::io::Error> for UpsertError {
+    fn from(err: std::io::Error) -> Self {
+        UpsertError::PersistenceError(err)
+    }
+}
+
+impl<T> From<std::sync::PoisonError<T>> for UpsertError {
+    fn from(err: std::sync::PoisonError<T>) -> Self {
+        UpsertError::PoisonedLock(err.to_string())
+    }
+}
+
+/// blocking runs `f` on the thread pool for blocking operations, so that
+/// resolving host names or writing the database file doesn't stall the
+/// worker handling requests.
+async fn blocking<T, F>(f: F) -> Result<T, UpsertError>
+where
+    F: FnOnce() -> Result<T, UpsertError> + Send + 'static,
+    T: Send + 'static,
+{
+    web::block(f).await.map_err(|err| match err {
+        error::BlockingError::Error(err) => err,
+        error::BlockingError::Canceled => UpsertError::Canceled,
+    })
+}
+
+/// is_private_ip tells whether `ip` belongs to a loopback, private,
+/// link-local or unspecified range.
+fn is_private_ip(ip: &IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            ip.is_loopback() || ip.is_private() || ip.is_link_local() || ip.is_unspecified()
+        }
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_private_ip(&IpAddr::V4(ip)),
+            None => {
+                let first_segment = ip.segments()[0];
+                ip.is_loopback()
+                    || ip.is_unspecified()
+                    || (first_segment & 0xfe00) == 0xfc00 // unique local, fc00::/7
+                    || (first_segment & 0xffc0) == 0xfe80 // link-local, fe80::/10
+            }
+        },
+    }
+}
+
+/// is_private_target tells whether `url` points to a private address, e.g.
+/// an internal service or the metadata endpoint of a cloud provider.
+/// Host names are resolved, blocking the thread: if any of their addresses is
+/// private, the target is considered private.
+fn is_private_target(url: &Url) -> bool {
+    let addrs = match url.host() {
+        None => return false,
+        Some(Host::Ipv4(ip)) => vec![IpAddr::V4(ip)],
+        Some(Host::Ipv6(ip)) => vec![IpAddr::V6(ip)],
+        Some(Host::Domain(domain)) => {
+            if domain == "localhost" || domain.ends_with(".localhost") {
+                return true;
+            }
+
+            resolve(domain, url.port_or_known_default().unwrap_or(80))
+        }
+    };
+
+    addrs.iter().any(is_private_ip)
+}
+
+/// resolve returns the addresses of a host name. A host name that doesn't
+/// resolve can't be used to reach an internal service, so it has none.
+#[cfg(not(test))]
+fn resolve(domain: &str, port: u16) -> Vec<IpAddr> {
+    use std::net::ToSocketAddrs;
+
+    match (domain, port).to_socket_addrs() {
+        Ok(addrs) => addrs.map(|addr| addr.ip()).collect(),
+        Err(_) => vec![],
+    }
+}
+
+/// resolve doesn't use the DNS in tests: host names ending in `.internal`
+/// resolve to a private address, and the other ones don't resolve.
+#[cfg(test)]
+fn resolve(domain: &str, _port: u16) -> Vec<IpAddr> {
+    if domain.ends_with(".internal") {
+        vec![IpAddr::from([10, 0, 0, 1])]
+    } else {
+        vec![]
+    }
+}
+
+/// check_target makes sure `target` is a valid URL that short URLs are
+/// allowed to redirect to. It may resolve the host name of the target, so
+/// handlers call it through `blocking`.
+fn check_target(db: &Db, target: &str) -> Result<(), UpsertError> {
+    let (max_target_length, allow_private_targets, allowed_schemes) = {
+        let db = db.read()?;
+        (
+            db.max_target_length,
+            db.allow_private_targets,
+            db.allowed_schemes.clone(),
+        )
+    };
+
+    if target.len() > max_target_length {
+        return Err(UpsertError::TargetTooLong);
+    }
+
+    let url = Url::parse(target).map_err(UpsertError::MalformedUrl)?;
+    if !allowed_schemes.contains(url.scheme()) {
+        return Err(UpsertError::DisallowedScheme);
+    }
+    if !allow_private_targets && is_private_target(&url) {
+        return Err(UpsertError::ForbiddenTarget);
+    }
+
+    Ok(())
+}
+
+/// Shortened tells whether creating a short URL inserted it, or found an
+/// existing one already redirecting to the same long URL.
+#[derive(Debug, PartialEq)]
+enum Shortened {
+    Created(String),
+    Existing(String),
+}
+
+impl Shortened {
+    fn id(&self) -> &str {
+        match self {
+            Shortened::Created(id) | Shortened::Existing(id) => id,
+        }
+    }
+
+    /// message answers the request that created the short URL.
+    fn message(&self, target: &str, base_url: Option<&BaseUrl>) -> String {
+        match self {
+            Shortened::Created(id) => redirects_message(id, target, base_url),
+            Shortened::Existing(id) => format!("/{} already maps to {}", id, target),
+        }
+    }
+}
+
+/// Create an short URL redirecting to a long URL.
+/// If you pass an `id` a parameter, your short URL will be /{id}.
+/// If you pass `None` instead, it will be /{hash of the target URL}.
+/// Unless duplicate targets are allowed, an existing short URL redirecting to
+/// the same long URL is returned instead of a new random one. Short URLs with
+/// an id are always created, since the caller expects that id.
+fn create_short_url(
+    db: web::Data<Db>,
+    target: &str,
+    id: Option<&str>,
+    max_clicks: Option<u64>,
+    expires_at: Option<SystemTime>,
+) -> Result<Shortened, UpsertError> {
+    check_target(&db, target)?;
+
+    // the id is picked with the lock held, so that it's still free when the
+    // short URL is inserted
+    let mut db = db.write()?;
+    let random = id.is_none();
+    let id = match id {
+        Some(id) => {
+            check_id(id, db.max_id_length)?;
+            db.normalise_id(id).into_owned()
+        }
+        None => random_id(&db, target),
+    };
+
+    if reserved_ids().contains(&id.as_str()) {
+        return Err(UpsertError::ReservedId);
+    }
+
+    if random && !db.allow_duplicate_targets {
+        if let Some(existing_id) = db.find_target(target) {
+            return Ok(Shortened::Existing(existing_id.clone()));
+        }
+    }
+
+    if db.contains(&id) {
+        return Err(UpsertError::AlreadyExists);
+    }
+
+    if db.is_full() {
+        return Err(UpsertError::DatabaseFull);
+    }
+
+    let entry = Entry {
+        max_clicks,
+        expires_at,
+        ..Entry::new(target.to_string())
+    };
+    db.insert_entry(&id, entry)?;
+    tracing::info!(id = %id, target = %target, "created short url");
+    Ok(Shortened::Created(id))
+}
+
+/// Upserted tells whether upserting a short URL created or updated it.
+#[derive(Debug, PartialEq)]
+enum Upserted {
+    Created,
+    Updated,
+}
+
+/// Create a short URL /{id} redirecting to a long URL, or make it redirect
+/// to a new long URL if it already exists.
+fn upsert_short_url(db: web::Data<Db>, target: &str, id: &str) -> Result<Upserted, UpsertError> {
+    let id = check_upsert(&db, target, id)?;
+    let mut db = db.write()?;
+    upsert_entry(&mut db, target, &id)
+}
+
+/// check_upsert makes sure the short URL /{id} can redirect to `target`, and
+/// returns its normalised id.
+fn check_upsert(db: &Db, target: &str, id: &str) -> Result<String, UpsertError> {
+    let id = {
+        let db = db.read()?;
+        check_id(id, db.max_id_length)?;
+        db.normalise_id(id).into_owned()
+    };
+
+    if reserved_ids().contains(&id.as_str()) {
+        return Err(UpsertError::ReservedId);
+    }
+
+    check_target(db, target)?;
+    Ok(id)
+}
+
+/// upsert_entry creates or updates the short URL `id`, once `check_upsert`
+/// accepted it.
+fn upsert_entry(db: &mut Data, target: &str, id: &str) -> Result<Upserted, UpsertError> {
+    // existing short URLs can always be updated
+    if !db.contains(id) && db.is_full() {
+        return Err(UpsertError::DatabaseFull);
+    }
+
+    match db.insert(id, target)? {
+        None => {
+            tracing::info!(id = %id, target = %target, "created short url");
+            Ok(Upserted::Created)
+        }
+        Some(_) => {
+            tracing::info!(id = %id, target = %target, "updated short url");
+            Ok(Upserted::Updated)
+        }
+    }
+}
+
+/// Make an existing short URL /{id} redirect to a new long URL, keeping its
+/// clicks and limits.
+/// Unlike `upsert_short_url`, it fails if the short URL doesn't exist.
+fn update_short_url(db: web::Data<Db>, target: &str, id: &str) -> Result<(), UpsertError> {
+    check_target(&db, target)?;
+
+    let mut db = db.write()?;
+    if db.get(id).is_none() {
+        return Err(UpsertError::NotFound);
+    }
+
+    db.update_entry(id, |entry| entry.target = target.to_string())?;
+    tracing::info!(id = %id, target = %target, "updated short url");
+    Ok(())
+}
+
+#[derive(serde::Deserialize)]
+struct BulkEntry {
+    id: String,
+    target: String,
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+struct BulkFailure {
+    id: String,
+    reason: String,
+}
+
+#[derive(serde::Serialize, Default, Debug, PartialEq)]
+struct BulkReport {
+    created: Vec<String>,
+    updated: Vec<String>,
+    failed: Vec<BulkFailure>,
+}
+
+/// ADMIN_USER is the user name of the admin routes' basic authentication.
+const ADMIN_USER: &str = "admin";
+
+/// AdminPassword protects the admin routes, when goto runs with
+/// --admin-password.
+#[derive(Clone)]
+struct AdminPassword(String);
+
+/// AdminAuth guards the admin routes: requests to them must authenticate as
+/// `admin` with HTTP basic authentication. When there is no admin password,
+/// the routes are open to everyone, e.g. to delete short URLs from the
+/// front-end of a private deployment.
+struct AdminAuth;
+
+impl FromRequest for AdminAuth {
+    type Error = Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = match req.app_data::<web::Data<AdminPassword>>() {
+            Some(password) => check_admin(req, password),
+            None => Ok(()),
+        };
+        futures::future::ready(result.map(|_| AdminAuth))
+    }
+}
+
+/// AdminOnly guards the routes changing how the server runs: like AdminAuth,
+/// but they are forbidden when there is no admin password.
+struct AdminOnly;
+
+impl FromRequest for AdminOnly {
+    type Error = Error;
+    type Future = futures::future::Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        let result = match req.app_data::<web::Data<AdminPassword>>() {
+            Some(password) => check_admin(req, password),
+            None => Err(error::ErrorForbidden(
+                "this route needs goto to run with --admin-password",
+            )),
+        };
+        futures::future::ready(result.map(|_| AdminOnly))
+    }
+}
+
+/// check_admin returns an unauthorized error unless the request authenticates
+/// as `admin` with the admin password.
+fn check_admin(req: &HttpRequest, password: &AdminPassword) -> Result<(), Error> {
+    let credentials = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_basic_auth);
+    match credentials {
+        // comparing blake3 hashes takes constant time
+        Some((user, given))
+            if user == ADMIN_USER
+                && blake3::hash(given.as_bytes()) == blake3::hash(password.0.as_bytes()) =>
+        {
+            Ok(())
+        }
+        _ => {
+            let response = HttpResponse::Unauthorized()
+                .header(header::WWW_AUTHENTICATE, r#"Basic realm="goto""#)
+                .finish();
+            Err(error::InternalError::from_response("unauthorized", response).into())
+        }
+    }
+}
+
+/// parse_basic_auth returns the user and the password of an `Authorization:
+/// Basic <base64(user:password)>` header.
+fn parse_basic_auth(authorization: &str) -> Option<(String, String)> {
+    let encoded = authorization.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(base64::decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+    Some((user.to_string(), password.to_string()))
+}
+
+/// bulk_import upserts every entry of a JSON list of `{"id", "target"}`.
+/// Invalid entries are reported as failed, and don't prevent the other ones
+/// from being imported. The imported entries are persisted at once, or not at
+/// all if that fails.
+#[post("/api/bulk")]
+async fn bulk_import(
+    _admin: AdminAuth,
+    req: HttpRequest,
+    db: web::Data<Db>,
+    payload: web::Payload,
+) -> impl Responder {
+    if req.content_type() != "application/json" {
+        return Err(error::ErrorUnsupportedMediaType(
+            "expected application/json",
+        ));
+    }
+
+    let body = read_body(payload, MAX_BULK_SIZE)
+        .await
+        .map_err(error::ErrorBadRequest)?;
+    let entries: Vec<BulkEntry> = serde_json::from_str(&body)
+        .map_err(|err| error::ErrorBadRequest(format!("invalid request body: {}", err)))?;
+
+    if entries.len() > MAX_BULK_ENTRIES {
+        return Err(error::ErrorBadRequest(format!(
+            "too many entries: the maximum is {}",
+            MAX_BULK_ENTRIES
+        )));
+    }
+
+    let report = blocking(move || {
+        let mut report = BulkReport::default();
+        let mut valid = Vec::new();
+        for entry in entries {
+            match check_upsert(&db, &entry.target, &entry.id) {
+                Ok(id) => valid.push((id, entry)),
+                Err(err) => report.failed.push(BulkFailure {
+                    id: entry.id,
+                    reason: err.to_string(),
+                }),
+            }
+        }
+
+        db.write()?.transaction(|data| {
+            for (id, entry) in valid {
+                match upsert_entry(data, &entry.target, &id) {
+                    Ok(Upserted::Created) => report.created.push(entry.id),
+                    Ok(Upserted::Updated) => report.updated.push(entry.id),
+                    Err(err) => report.failed.push(BulkFailure {
+                        id: entry.id,
+                        reason: err.to_string(),
+                    }),
+                }
+            }
+        })?;
+        Ok(report)
+    })
+    .await?;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
+#[derive(serde::Deserialize)]
+struct CreateQuery {
+    max_clicks: Option<NonZeroU64>,
+    /// RFC 3339 timestamp, e.g. 2021-05-19T17:36:49Z.
+    expires_at: Option<String>,
+}
+
+impl CreateQuery {
+    fn expires_at(&self) -> Result<Option<SystemTime>, Error> {
+        parse_expires_at(self.expires_at.as_deref())
+    }
+}
+
+/// parse_expires_at parses the RFC 3339 expiry time of a short URL, which
+/// must be in the future.
+fn parse_expires_at(expires_at: Option<&str>) -> Result<Option<SystemTime>, Error> {
+    let expires_at = match expires_at {
+        None => return Ok(None),
+        Some(expires_at) => humantime::parse_rfc3339_weak(expires_at)
+            .map_err(|err| error::ErrorBadRequest(format!("invalid expires_at: {}", err)))?,
+    };
+
+    if expires_at <= SystemTime::now() {
+        return Err(error::ErrorBadRequest("expires_at is in the past"));
+    }
+    Ok(Some(expires_at))
+}
+
+#[post("/{id}")]
+async fn create_with_id(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    base_url: Option<web::Data<BaseUrl>>,
+    payload: web::Payload,
+    web::Path(id): web::Path<String>,
+    query: web::Query<CreateQuery>,
+) -> impl Responder {
+    let max_size = db.read().map_err(UpsertError::from)?.max_body_size();
+    let target = match read_target_with_content_type(payload, content_type(&req), max_size).await {
+        Ok(target) => target,
+        Err(err) => return Err(error::ErrorBadRequest(err)),
+    };
+
+    let max_clicks = query.max_clicks.map(NonZeroU64::get);
+    let expires_at = query.expires_at()?;
+    let shortened = blocking({
+        let target = target.clone();
+        move || create_short_url(db, &target, Some(id.as_str()), max_clicks, expires_at)
+    })
+    .await?;
+    Ok(shortened.message(
+        &target,
+        base_url.as_ref().map(|base_url| base_url.get_ref()),
+    ))
+}
+
+#[patch("/{id}")]
+async fn patch_with_id(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    base_url: Option<web::Data<BaseUrl>>,
+    payload: web::Payload,
+    web::Path(id): web::Path<String>,
+) -> impl Responder {
+    let max_size = db.read().map_err(UpsertError::from)?.max_body_size();
+    let target = match read_target_with_content_type(payload, content_type(&req), max_size).await {
+        Ok(target) => target,
+        Err(err) => return Err(error::ErrorBadRequest(err)),
+    };
+
+    blocking({
+        let (target, id) = (target.clone(), id.clone());
+        move || update_short_url(db, &target, &id)
+    })
+    .await?;
+    Ok(redirects_message(
+        &id,
+        &target,
+        base_url.as_ref().map(|base_url| base_url.get_ref()),
+    ))
+}
+
+#[post("/")]
+async fn create_random(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    base_url: Option<web::Data<BaseUrl>>,
+    payload: web::Payload,
+    query: web::Query<CreateQuery>,
+) -> impl Responder {
+    let max_size = db.read().map_err(UpsertError::from)?.max_body_size();
+    let target = match read_target_with_content_type(payload, content_type(&req), max_size).await {
+        Ok(target) => target,
+        Err(err) => return Err(error::ErrorBadRequest(err)),
+    };
+
+    let max_clicks = query.max_clicks.map(NonZeroU64::get);
+    let expires_at = query.expires_at()?;
+    let shortened = blocking({
+        let target = target.clone();
+        move || create_short_url(db, &target, None, max_clicks, expires_at)
+    })
+    .await?;
+    Ok(shortened.message(
+        &target,
+        base_url.as_ref().map(|base_url| base_url.get_ref()),
+    ))
+}
+
+#[derive(serde::Deserialize)]
+struct ShortenRequest {
+    /// A random id is picked when it is omitted.
+    id: Option<String>,
+    target: String,
+    /// RFC 3339 timestamp, e.g. 2021-05-19T17:36:49Z.
+    expires_at: Option<String>,
+    max_clicks: Option<NonZeroU64>,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct ShortenResponse {
+    id: String,
+    /// Full short URL when the base URL is known, its path otherwise.
+    short_url: String,
+    target: String,
+}
+
+/// shorten creates a short URL from a JSON `{"id", "target", "expires_at",
+/// "max_clicks"}` object, where only the target is required. It does the same
+/// as `create_with_id` and `create_random`, but answers with JSON.
+#[post("/api/shorten")]
+async fn shorten(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    base_url: Option<web::Data<BaseUrl>>,
+    payload: web::Payload,
+) -> impl Responder {
+    if req.content_type() != "application/json" {
+        return Err(error::ErrorUnsupportedMediaType(
+            "expected application/json",
+        ));
+    }
+
+    let max_size = db.read().map_err(UpsertError::from)?.max_body_size();
+    let body = read_body(payload, max_size)
+        .await
+        .map_err(error::ErrorBadRequest)?;
+    let shorten: ShortenRequest = serde_json::from_str(&body)
+        .map_err(|err| error::ErrorBadRequest(format!("invalid request body: {}", err)))?;
+
+    let max_clicks = shorten.max_clicks.map(NonZeroU64::get);
+    let expires_at = parse_expires_at(shorten.expires_at.as_deref())?;
+    let shortened = blocking({
+        let (target, id) = (shorten.target.clone(), shorten.id);
+        move || create_short_url(db, &target, id.as_deref(), max_clicks, expires_at)
+    })
+    .await?;
+
+    let id = shortened.id().to_string();
+    let short_url = match &base_url {
+        Some(base_url) => base_url.short_url(&id),
+        None => format!("/{}", id),
+    };
+    let mut response = match shortened {
+        Shortened::Created(_) => HttpResponse::Created(),
+        Shortened::Existing(_) => HttpResponse::Ok(),
+    };
+    Ok(response.json(ShortenResponse {
+        id,
+        short_url,
+        target: shorten.target,
+    }))
+}
+
+#[derive(serde::Serialize)]
+struct UrlEntry<'a> {
+    id: &'a str,
+    target: &'a str,
+    #[serde(skip_serializing_if = "is_zero")]
+    clicks: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    deleted_at: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    expires_at: Option<u64>,
+}
+
+impl<'a> UrlEntry<'a> {
+    fn new(id: &'a str, entry: &'a Entry) -> Self {
+        UrlEntry {
+            id,
+            target: &entry.target,
+            clicks: entry.clicks,
+            deleted_at: entry.deleted_at.map(unix_seconds),
+            expires_at: entry.expires_at.map(unix_seconds),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct UrlPage<'a> {
+    items: Vec<UrlEntry<'a>>,
+    /// Cursor of the next page, or None on the last page.
+    next_cursor: Option<String>,
+    /// Number of short URLs across all the pages.
+    total: usize,
+}
+
+#[derive(serde::Deserialize)]
+struct ListQuery {
+    include_deleted: Option<bool>,
+    cursor: Option<String>,
+    limit: Option<NonZeroUsize>,
+}
+
+/// encode_cursor returns an opaque cursor pointing after the short URL `id`.
+fn encode_cursor(id: &str) -> String {
+    base64::encode_config(id, base64::URL_SAFE_NO_PAD)
+}
+
+/// decode_cursor returns the ID of the short URL a cursor points after.
+fn decode_cursor(cursor: &str) -> Option<String> {
+    let id = base64::decode_config(cursor, base64::URL_SAFE_NO_PAD).ok()?;
+    String::from_utf8(id).ok()
+}
+
+/// list_urls returns a page of short URLs, in insertion order, as
+/// `{"items": [...], "next_cursor": "...", "total": N}`.
+/// Pass `?cursor=<next_cursor>` to get the next page, until `next_cursor`
+/// is null. The cursor is invalid once its short URL is removed for good. Pages hold `?limit=` short URLs, 100 by default and at most 1000.
+/// Deleted short URLs are only listed with `?include_deleted=true`.
+/// Pages have an ETag: requests with a matching `If-None-Match` get a
+/// `304 Not Modified` without a body.
+#[get("/api/urls")]
+async fn list_urls(
+    req: HttpRequest,
+    db: web::Data<Db>,
+    query: web::Query<ListQuery>,
+) -> impl Responder {
+    let db = db
+        .read()
+        .map_err(|err| error::ErrorInternalServerError(err.to_string()))?;
+
+    let include_deleted = query.include_deleted.unwrap_or(false);
+    let listed = |(_, entry): &(&String, &Entry)| include_deleted || !entry.is_deleted();
+    let total = db.entries().filter(listed).count();
+
+    let start = match &query.cursor {
+        None => 0,
+        Some(cursor) => {
+            let (index, _, _) = decode_cursor(cursor)
+                .and_then(|after| db.data.get_full(&after))
+                .ok_or_else(|| error::ErrorBadRequest("invalid cursor"))?;
+            index + 1
+        }
+    };
+    let limit = query
+        .limit
+        .map_or(DEFAULT_PAGE_SIZE, NonZeroUsize::get)
+        .min(MAX_PAGE_SIZE);
+
+    // one more short URL than the limit tells whether there is a next page
+    let mut entries: Vec<UrlEntry> = (start..db.data.len())
+        .filter_map(|index| db.data.get_index(index))
+        .filter(listed)
+        .take(limit + 1)
+        .map(|(id, entry)| UrlEntry::new(id, entry))
+        .collect();
+    let next_cursor = if entries.len() > limit {
+        entries.truncate(limit);
+        entries.last().map(|entry| encode_cursor(entry.id))
+    } else {
+        None
+    };
+
+    let body = serde_json::to_string(&UrlPage {
+        items: entries,
+        next_cursor,
+        total,
+    })
+    .map_err(error::ErrorInternalServerError)?;
+    let etag = etag(&body);
+    if matches_etag(&req, &etag) {
+        return Ok(HttpResponse::NotModified()
+            .header(header::ETAG, etag)
+            .finish());
+    }
+
+    Ok::<_, Error>(
+        HttpResponse::Ok()
+            .content_type("application/json")
+            .header(header::ETAG, etag)
+            .body(body),
+    )
+}
+
+/// etag returns the strong ETag of a response body: its quoted blake3 hash.
+fn etag(body: &str) -> String {
+    format!("\"{}\"", blake3::hash(body.as_bytes()).to_hex())
+}
+
+/// matches_etag tells whether the `If-None-Match` header of a request holds
+/// `etag`, or is `*`.
+fn matches_etag(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get_all(header::IF_NONE_MATCH)
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[derive(serde::Deserialize)]
+struct SearchQuery {
+    q: String,
+    #[serde(default)]
+    field: SearchField,
+}
+
+/// search_urls returns the short URLs whose id or target contains `?q=`,
+/// in insertion order, in the same format as `list_urls` but on a single page.
+/// `?field=id` or `?field=target` only searches one of them. Deleted short
+/// URLs are left out.
+#[get("/api/search")]
+async fn search_urls(db: web::Data<Db>, query: web::Query<SearchQuery>) -> impl Responder {
+    let db = db
+        .read()
+        .map_err(|err| error::ErrorInternalServerError(err.to_string()))?;
+
+    let entries: Vec<UrlEntry> = search::search(&db.data, &query.q, query.field)
+        .filter(|(_, entry)| !entry.is_deleted())
+        .map(|(id, entry)| UrlEntry::new(id, entry))
+        .collect();
+    let total = entries.len();
+
+    Ok::<_, Error>(HttpResponse::Ok().json(UrlPage {
+        items: entries,
+        next_cursor: None,
+        total,
+    }))
+}
+
+/// delete_short_url deletes a short URL, or returns a 404 not found error if the short
+/// URL doesn't exist. Unless hard deletes are enabled, it can be restored.
+#[delete("/{id}")]
+async fn delete_short_url(
+    _admin: AdminAuth,
+    db: web::Data<Db>,
+    web::Path(id): web::Path<String>,
+) -> Result<String, UpsertError> {
+    if !db.write()?.delete(&id)? {
+        return Err(UpsertError::NotFound);
+    }
+
+    tracing::info!(id = %id, "deleted short url");
+    Ok(format!("/{} deleted", id))
+}
+
+/// restore_short_url restores a deleted short URL, or returns a 404 not found
+/// error if the short URL doesn't exist.
+#[post("/{id}/restore")]
+async fn restore_short_url(
+    _admin: AdminAuth,
+    db: web::Data<Db>,
+    web::Path(id): web::Path<String>,
+) -> Result<String, UpsertError> {
+    if !db.write()?.restore(&id)? {
+        return Err(UpsertError::NotFound);
+    }
+
+    tracing::info!(id = %id, "restored short url");
+    Ok(format!("/{} restored", id))
+}
+
+#[derive(serde::Serialize, Debug, PartialEq)]
+struct CompactReport {
+    entries_written: usize,
+    file_size_bytes: u64,
+}
+
+/// compact removes the deleted and expired short URLs from the database and
+/// from its file, which otherwise keeps them forever.
+#[post("/api/admin/compact")]
+async fn compact(_admin: AdminOnly, db: web::Data<Db>) -> Result<HttpResponse, UpsertError> {
+    let mut db = db.write()?;
+    let entries_written = db.compact()?;
+    let file_size_bytes = db.storage.size()?;
+
+    tracing::info!(entries = entries_written, "compacted the database");
+    Ok(HttpResponse::Ok().json(CompactReport {
+        entries_written,
+        file_size_bytes,
+    }))
+}
+
+/// ServerConfig is the part of the configuration of the server that can be
+/// changed while it runs, with `PUT /api/config`.
+#[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+struct ServerConfig {
+    allow_private_targets: bool,
+    allowed_schemes: Vec<String>,
+    max_id_length: usize,
+    max_target_length: usize,
+    max_entries: Option<usize>,
+    hard_delete: bool,
+    random_id_generator: IdGenerator,
+    id_length: usize,
+}
+
+impl ServerConfig {
+    fn validate(&self) -> Result<(), String> {
+        if self.max_id_length == 0 {
+            return Err("max_id_length must be at least 1".to_string());
+        }
+        if self.max_target_length == 0 {
+            return Err("max_target_length must be at least 1".to_string());
+        }
+        if self.id_length == 0 {
+            return Err("id_length must be at least 1".to_string());
+        }
+        if self.allowed_schemes.is_empty() {
+            return Err("allowed_schemes must not be empty".to_string());
+        }
+        // RFC 3986: ALPHA *( ALPHA / DIGIT / "+" / "-" / "." )
+        let is_scheme = |scheme: &str| {
+            scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        };
+        if let Some(scheme) = self
+            .allowed_schemes
+            .iter()
+            .find(|scheme| !is_scheme(scheme))
+        {
+            return Err(format!("invalid scheme {:?}", scheme));
+        }
+        Ok(())
+    }
+}
+
+impl Data {
+    /// server_config returns the settings that can be changed while the
+    /// server runs.
+    fn server_config(&self) -> ServerConfig {
+        let mut allowed_schemes: Vec<String> = self.allowed_schemes.iter().cloned().collect();
+        allowed_schemes.sort();
+        ServerConfig {
+            allow_private_targets: self.allow_private_targets,
+            allowed_schemes,
+            max_id_length: self.max_id_length,
+            max_target_length: self.max_target_length,
+            max_entries: self.max_entries,
+            hard_delete: self.hard_delete,
+            random_id_generator: self.id_generator,
+            id_length: self.id_length,
+        }
+    }
+
+    /// set_server_config applies settings validated with
+    /// `ServerConfig::validate`.
+    fn set_server_config(&mut self, config: ServerConfig) {
+        self.allow_private_targets = config.allow_private_targets;
+        self.allowed_schemes = config
+            .allowed_schemes
+            .iter()
+            .map(|scheme| scheme.to_lowercase())
+            .collect();
+        self.max_id_length = config.max_id_length;
+        self.max_target_length = config.max_target_length;
+        self.max_entries = config.max_entries;
+        self.hard_delete = config.hard_delete;
+        self.id_generator = config.random_id_generator;
+        self.id_length = config.id_length;
+    }
+}
+
+/// get_server_config answers with the settings of the server that can be
+/// changed with `PUT /api/config`.
+#[get("/api/config")]
+async fn get_server_config(
+    _admin: AdminOnly,
+    db: web::Data<Db>,
+) -> Result<HttpResponse, UpsertError> {
+    Ok(HttpResponse::Ok().json(db.read()?.server_config()))
+}
+
+/// put_server_config changes the settings of the server without restarting
+/// it, and answers with the settings in use. The changes are lost when the
+/// server restarts.
+#[put("/api/config")]
+async fn put_server_config(
+    _admin: AdminOnly,
+    req: HttpRequest,
+    db: web::Data<Db>,
+    payload: web::Payload,
+) -> Result<HttpResponse, Error> {
+    if req.content_type() != "application/json" {
+        return Err(error::ErrorUnsupportedMediaType(
+            "expected application/json",
+        ));
+    }
+
+    let body = read_body(payload, MAX_CONFIG_SIZE)
+        .await
+        .map_err(error::ErrorBadRequest)?;
+    let config: ServerConfig = serde_json::from_str(&body)
+        .map_err(|err| error::ErrorBadRequest(format!("invalid request body: {}", err)))?;
+    config.validate().map_err(error::ErrorBadRequest)?;
+
+    let mut db = db.write().map_err(UpsertError::from)?;
+    db.set_server_config(config);
+    let config = db.server_config();
+    tracing::info!(config = ?config, "changed the server config");
+    Ok(HttpResponse::Ok().json(config))
+}
+
+/// AppRoute is one of the routes of goto.
+struct AppRoute {
+    /// Path of the route, as in its `#[get]`-like attribute. Short URLs can't
+    /// have its first segment as id, unless it is a `{parameter}`.
+    path: &'static str,
+    /// Write routes modify the database. They are left out when goto runs
+    /// with `--read-only`.
+    write: bool,
+    register: fn(&mut web::ServiceConfig, front_dist_directory: &str),
+}
+
+/// ROUTES are registered by `routes`, in this order.
+const ROUTES: &[AppRoute] = &[
+    AppRoute {
+        path: "/dist",
+        write: false,
+        register: |cfg, front_dist_directory| {
+            cfg.service(Files::new("/dist", front_dist_directory));
+        },
+    },
+    AppRoute {
+        path: "/metrics",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(metrics::expose);
+        },
+    },
+    AppRoute {
+        path: "/health",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(health);
+        },
+    },
+    AppRoute {
+        path: "/{id}",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(browse);
+        },
+    },
+    AppRoute {
+        path: "/{id}",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(exists);
+        },
+    },
+    AppRoute {
+        path: "/api/url/{id}/exists",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(url_exists);
+        },
+    },
+    AppRoute {
+        path: "/api/{id}/stats",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(url_stats);
+        },
+    },
+    AppRoute {
+        path: "/api/ws/events",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(events::stream);
+        },
+    },
+    AppRoute {
+        path: "/api/config",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(get_server_config);
+        },
+    },
+    AppRoute {
+        path: "/{id}/qr",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(qr_code);
+        },
+    },
+    AppRoute {
+        path: "/api/urls",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(list_urls);
+        },
+    },
+    AppRoute {
+        path: "/api/search",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(search_urls);
+        },
+    },
+    AppRoute {
+        path: "",
+        write: false,
+        register: |cfg, _| {
+            cfg.service(web::resource(vec!["", "/"]).route(web::get().to(front_index)));
+        },
+    },
+    AppRoute {
+        path: "/api/bulk",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(bulk_import);
+        },
+    },
+    AppRoute {
+        path: "/api/admin/compact",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(compact);
+        },
+    },
+    AppRoute {
+        path: "/api/config",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(put_server_config);
+        },
+    },
+    AppRoute {
+        path: "/api/shorten",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(shorten);
+        },
+    },
+    AppRoute {
+        path: "/",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(create_random);
+        },
+    },
+    AppRoute {
+        path: "/{id}/restore",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(restore_short_url);
+        },
+    },
+    AppRoute {
+        path: "/{id}",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(create_with_id);
+        },
+    },
+    AppRoute {
+        path: "/{id}",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(patch_with_id);
+        },
+    },
+    AppRoute {
+        path: "/{id}",
+        write: true,
+        register: |cfg, _| {
+            cfg.service(delete_short_url);
+        },
+    },
+];
+
+/// reserved_ids lists the ids that can't be used for short URLs, because
+/// they are the first segment of the path of one of the `ROUTES`.
+fn reserved_ids() -> &'static [&'static str] {
+    static RESERVED_IDS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    RESERVED_IDS.get_or_init(|| {
+        let mut ids = Vec::new();
+        for route in ROUTES {
+            let id = route
+                .path
+                .trim_start_matches('/')
+                .split('/')
+                .next()
+                .unwrap_or_default();
+            if !id.is_empty() && !id.starts_with('{') && !ids.contains(&id) {
+                ids.push(id);
+            }
+        }
+        ids
+    })
+}
+
+/// Register every route of goto, including the front-end. main serves them
+/// under --prefix.
+fn routes(cfg: &mut web::ServiceConfig, front_dist_directory: &str, read_only: bool) {
+    for route in ROUTES.iter().filter(|route| !route.write) {
+        (route.register)(cfg, front_dist_directory);
+    }
+    if !read_only {
+        write_routes(cfg);
+    }
+    // this doesn't do exactly what I need (just serve index.html
+    //    on /), but I can't find a simple way of doing it.
+    cfg.service(Files::new("/", front_dist_directory).index_file("index.html"));
+}
+
+/// Register the routes that modify the database. They are left out when
+/// goto runs with `--read-only`.
+fn write_routes(cfg: &mut web::ServiceConfig) {
+    for route in ROUTES.iter().filter(|route| route.write) {
+        // the write routes don't serve the front-end
+        (route.register)(cfg, "");
+    }
+}
+
+/// parse_with_timeout runs `parse` in its own thread, and gives up if it
+/// hasn't finished after `timeout`. The thread isn't stopped, but goto exits
+/// when the database can't be read anyway.
+fn parse_with_timeout<T, F>(parse: F, timeout: Duration) -> Result<T, String>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, String> + Send + 'static,
+{
+    let (sender, receiver) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        // the receiver is gone if it timed out
+        let _ = sender.send(parse());
+    });
+
+    match receiver.recv_timeout(timeout) {
+        Ok(res) => res,
+        Err(RecvTimeoutError::Timeout) => Err(format!(
+            "timed out after {}, see --db-load-timeout",
+            humantime::format_duration(timeout)
+        )),
+        Err(RecvTimeoutError::Disconnected) => Err("parser panicked".to_string()),
+    }
+}
+
+#[test]
+fn test_parse_with_timeout() {
+    let got = parse_with_timeout(|| Ok(42), Duration::from_secs(1));
+    assert_eq!(Ok(42), got);
+
+    let got = parse_with_timeout(|| Err::<(), _>("oops".to_string()), Duration::from_secs(1));
+    assert_eq!(Err("oops".to_string()), got);
+
+    let got = parse_with_timeout(
+        || {
+            std::thread::sleep(Duration::from_secs(1));
+            Ok(())
+        },
+        Duration::from_millis(10),
+    );
+    assert_eq!(
+        Err("timed out after 10ms, see --db-load-timeout".to_string()),
+        got
+    );
+}
+
+#[derive(Parser)]
+#[command(version, about = "Shorten URLs")]
+struct Cli {
+    #[arg(short = 'f', long = "frontdir", env = "GOTO_FRONT_DIR")]
+    /// Directory where the front-end files are located, default: "front/dist".
+    front_dist_directory: Option<String>,
+
+    #[arg(short = 'a', long = "addr", env = "GOTO_ADDR")]
+    /// Address to run the application on, default: "127.0.0.1:8080", or
+    /// "0.0.0.0:443" with --https-cert.
+    addr: Option<String>,
+
+    #[arg(long = "https-cert", env = "GOTO_HTTPS_CERT", requires = "https_key")]
+    /// PEM file of the TLS certificate chain, to serve HTTPS instead of HTTP.
+    https_cert: Option<PathBuf>,
+
+    #[arg(long = "https-key", env = "GOTO_HTTPS_KEY", requires = "https_cert")]
+    /// PEM file of the private key of --https-cert.
+    https_key: Option<PathBuf>,
+
+    #[arg(long = "bind-fd", env = "GOTO_BIND_FD")]
+    /// Listen on this already open socket instead of --addr. With systemd
+    /// socket activation, the socket passed in LISTEN_FDS is used
+    /// automatically.
+    bind_fd: Option<i32>,
+
+    #[arg(long = "systemd-notify", env = "GOTO_SYSTEMD_NOTIFY")]
+    /// Tell systemd when the server is ready, for services of Type=notify.
+    systemd_notify: bool,
+
+    #[arg(long = "workers", env = "GOTO_WORKERS", default_value_t = 0)]
+    /// Number of worker threads handling requests, 0 to start one per CPU.
+    workers: usize,
+
+    #[arg(
+        long = "blocking-threads",
+        env = "GOTO_BLOCKING_THREADS",
+        default_value_t = 0
+    )]
+    /// Number of threads for blocking operations such as file I/O, 0 to
+    /// start five per CPU.
+    blocking_threads: usize,
+
+    #[arg(short = 'd', long = "database", env = "GOTO_DATABASE")]
+    /// Database file to persist the shortened URLs.
+    /// Will be created if it doesn't exist.
+    /// Example: database.yml.
+    /// Files ending in .json are stored as JSON, files ending in .ndjson or
+    /// .jsonl as JSON lines, other files as YAML.
+    /// If this option is omitted, the shortened URLs will not be persisted.
+    database: Option<String>,
+
+    #[arg(long = "database-format", env = "GOTO_DATABASE_FORMAT", value_enum)]
+    /// Format of the database file, instead of guessing it from its
+    /// extension.
+    database_format: Option<DataFormat>,
+
+    #[arg(long = "audit-log", env = "GOTO_AUDIT_LOG")]
+    /// File to append every change to the short URLs to, as JSON lines.
+    /// It is never read by goto, nor compacted.
+    audit_log: Option<PathBuf>,
+
+    #[arg(
+        long = "db-load-timeout",
+        env = "GOTO_DB_LOAD_TIMEOUT",
+        default_value_t = DEFAULT_DB_LOAD_TIMEOUT_SECS
+    )]
+    /// Number of seconds after which goto gives up reading the database file
+    /// at startup, e.g. when it is a huge corrupted file.
+    db_load_timeout: u64,
+
+    #[arg(long = "no-hash-collision-retry", env = "GOTO_NO_HASH_COLLISION_RETRY")]
+    /// When the hash of a long URL is already used by another short URL,
+    /// refuse to create a random short URL for it instead of picking another
+    /// id.
+    no_hash_collision_retry: bool,
+
+    #[arg(long = "allow-duplicate-targets", env = "GOTO_ALLOW_DUPLICATE_TARGETS")]
+    /// Create a new random short URL even if another one already redirects to
+    /// the same long URL. By default, the existing short URL is returned.
+    allow_duplicate_targets: bool,
+
+    #[arg(long = "hard-delete", env = "GOTO_HARD_DELETE")]
+    /// Remove deleted short URLs for good. By default, they are only marked
+    /// as deleted, and can be restored with POST /{id}/restore.
+    hard_delete: bool,
+
+    #[arg(
+        long = "cors-origins",
+        env = "GOTO_CORS_ORIGINS",
+        value_delimiter = ','
+    )]
+    /// Comma-separated list of origins allowed to make cross-origin requests.
+    /// Example: https://example.com,https://other.example.com.
+    /// Use "*" to allow any origin.
+    /// If this option is omitted, cross-origin requests are not allowed.
+    cors_origins: Vec<String>,
+
+    #[arg(
+        long = "log-format",
+        env = "GOTO_LOG_FORMAT",
+        value_enum,
+        default_value_t = LogFormat::Pretty
+    )]
+    /// Format of the logs: "pretty" or "json".
+    /// The verbosity is controlled with the RUST_LOG environment variable.
+    log_format: LogFormat,
+
+    #[arg(long = "log-file", env = "GOTO_LOG_FILE")]
+    /// Write the logs to this file instead of stdout. A new file, suffixed
+    /// with the date, is started every day.
+    log_file: Option<PathBuf>,
+
+    #[arg(
+        long = "log-keep-days",
+        env = "GOTO_LOG_KEEP_DAYS",
+        default_value_t = DEFAULT_LOG_KEEP_DAYS
+    )]
+    /// Number of daily log files kept when --log-file is set, older files
+    /// are deleted.
+    log_keep_days: usize,
+
+    #[arg(long = "allow-private-targets", env = "GOTO_ALLOW_PRIVATE_TARGETS")]
+    /// Allow short URLs to redirect to loopback, private or link-local
+    /// addresses, e.g. for self-hosted deployments on a private network.
+    /// By default, such targets are rejected.
+    allow_private_targets: bool,
+
+    #[arg(long = "preview", env = "GOTO_PREVIEW")]
+    /// Show a page telling where a short URL leads instead of redirecting
+    /// right away. Clients can skip it by adding ?preview=0 to the short URL.
+    preview: bool,
+
+    #[arg(
+        long = "preview-delay",
+        env = "GOTO_PREVIEW_DELAY",
+        default_value_t = 5
+    )]
+    /// Number of seconds after which the preview page redirects by itself.
+    preview_delay: u32,
+
+    #[arg(
+        long = "cookie-name",
+        env = "GOTO_COOKIE_NAME",
+        default_value = DEFAULT_COOKIE_NAME,
+        value_parser = parse_cookie_name
+    )]
+    /// Name of the cookie set when a user continues from the preview page,
+    /// so that their browser skips it for 30 days.
+    cookie_name: String,
+
+    #[arg(
+        long = "redirect-body",
+        env = "GOTO_REDIRECT_BODY",
+        default_value = DEFAULT_REDIRECT_BODY,
+        value_parser = parse_redirect_body
+    )]
+    /// Body of redirect responses, where {url} is replaced by the long URL
+    /// and {id} by the short URL.
+    redirect_body: RedirectBody,
+
+    #[arg(
+        long = "max-id-length",
+        env = "GOTO_MAX_ID_LENGTH",
+        default_value_t = DEFAULT_MAX_ID_LENGTH
+    )]
+    /// Maximum number of characters of a short URL id.
+    max_id_length: usize,
+
+    #[arg(
+        long = "max-target-length",
+        env = "GOTO_MAX_TARGET_LENGTH",
+        default_value_t = DEFAULT_MAX_TARGET_LENGTH
+    )]
+    /// Maximum number of characters of the long URLs short URLs redirect to.
+    max_target_length: usize,
+
+    #[arg(long = "max-entries", env = "GOTO_MAX_ENTRIES")]
+    /// Maximum number of short URLs, including the deleted ones that can
+    /// still be restored. There is no limit by default.
+    max_entries: Option<usize>,
+
+    #[arg(long = "base-url", env = "GOTO_BASE_URL")]
+    /// Public URL of goto, e.g. when it runs behind a reverse proxy.
+    /// Example: https://go.company.com.
+    /// If this option is omitted, only the path of short URLs is shown.
+    base_url: Option<Url>,
+
+    #[arg(long = "prefix", env = "GOTO_PREFIX", value_parser = parse_prefix)]
+    /// Path all the routes are served under, e.g. when a reverse proxy
+    /// serves goto at https://tools.company.com/goto/.
+    /// Example: /goto.
+    prefix: Option<String>,
+
+    #[arg(
+        long = "allowed-schemes",
+        env = "GOTO_ALLOWED_SCHEMES",
+        value_delimiter = ',',
+        default_values = DEFAULT_ALLOWED_SCHEMES
+    )]
+    /// Comma-separated list of schemes short URLs can redirect to.
+    /// Example: http,https,ftp.
+    allowed_schemes: Vec<String>,
+
+    #[arg(long = "case-insensitive", env = "GOTO_CASE_INSENSITIVE")]
+    /// Make short URLs case-insensitive: /MyLink and /mylink are the same
+    /// short URL, stored as /mylink.
+    case_insensitive: bool,
+
+    #[arg(long = "id-alphabet", env = "GOTO_ID_ALPHABET", value_parser = parse_id_alphabet)]
+    /// Characters random short URLs are made of, or "base62" for letters
+    /// and digits. By default, they are hexadecimal.
+    id_alphabet: Option<String>,
+
+    #[arg(
+        long = "random-id-generator",
+        env = "GOTO_RANDOM_ID_GENERATOR",
+        value_enum,
+        default_value_t = IdGenerator::Hash
+    )]
+    /// How the ids of short URLs created without one are picked: "hash"
+    /// hashes their target, "nanoid" picks random characters.
+    random_id_generator: IdGenerator,
+
+    #[arg(
+        long = "id-length",
+        env = "GOTO_ID_LENGTH",
+        default_value_t = NonZeroUsize::new(RANDOM_URL_SIZE).unwrap()
+    )]
+    /// Number of characters of the ids picked by --random-id-generator
+    /// nanoid.
+    id_length: NonZeroUsize,
+
+    #[arg(long = "read-only", env = "GOTO_READ_ONLY")]
+    /// Only serve existing short URLs: the routes creating, updating or
+    /// deleting short URLs are not registered.
+    read_only: bool,
+
+    #[arg(long = "admin-password", env = "GOTO_ADMIN_PASSWORD")]
+    /// Require the user `admin` and this password, with HTTP basic
+    /// authentication, to compact the database, bulk import, delete and
+    /// restore short URLs, and to read or change the server config. Without
+    /// it, anyone can bulk import, delete and restore short URLs, while
+    /// compacting the database and the server config are disabled.
+    admin_password: Option<String>,
+
+    #[arg(long = "no-security-headers", env = "GOTO_NO_SECURITY_HEADERS")]
+    /// Don't send the Content-Security-Policy, X-Frame-Options,
+    /// Referrer-Policy and X-Content-Type-Options headers, e.g. to develop
+    /// the front-end.
+    no_security_headers: bool,
+
+    #[arg(
+        long = "ws-buffer",
+        env = "GOTO_WS_BUFFER",
+        default_value_t = NonZeroUsize::new(DEFAULT_WS_BUFFER).unwrap()
+    )]
+    /// Number of redirects kept for the clients of /api/ws/events that are
+    /// too slow to keep up. They miss the older ones.
+    ws_buffer: NonZeroUsize,
+
+    #[arg(
+        long = "shutdown-timeout",
+        env = "GOTO_SHUTDOWN_TIMEOUT",
+        default_value_t = DEFAULT_SHUTDOWN_TIMEOUT_SECS
+    )]
+    /// Number of seconds to wait for the requests in progress when goto is
+    /// asked to stop, before dropping them.
+    shutdown_timeout: u64,
+}
+
+impl Cli {
+    fn get_preview(&self) -> Option<Preview> {
+        if self.preview {
+            Some(Preview::new(self.preview_delay, &self.cookie_name))
+        } else {
+            None
+        }
+    }
+
+    fn get_base_url(&self) -> Option<BaseUrl> {
+        self.base_url
+            .as_ref()
+            .map(|base_url| BaseUrl(base_url.to_string()))
+    }
+
+    fn get_front_dir(&self) -> String {
+        match &self.front_dist_directory {
+            Some(dir) => dir.to_owned(),
+            None => "front/dist/".to_string(),
+        }
+    }
+
+    /// get_workers returns the number of worker threads, one per CPU unless
+    /// --workers is set.
+    fn get_workers(&self) -> usize {
+        match self.workers {
+            0 => cpus(),
+            workers => workers,
+        }
+    }
+
+    /// get_blocking_threads returns the number of threads for blocking
+    /// operations, five per CPU like actix-web unless --blocking-threads is
+    /// set.
+    fn get_blocking_threads(&self) -> usize {
+        match self.blocking_threads {
+            0 => cpus() * 5,
+            threads => threads,
+        }
+    }
+
+    /// get_bind_fd returns the socket to listen on instead of the address:
+    /// the one given with --bind-fd, or the one passed by systemd.
+    fn get_bind_fd(&self) -> Option<i32> {
+        self.bind_fd.or_else(systemd::listen_fd)
+    }
+
+    fn get_addr(&self) -> String {
+        match &self.addr {
+            Some(addr) => addr.to_owned(),
+            None if self.https_cert.is_some() => "0.0.0.0:443".to_string(),
+            None => "127.0.0.1:8080".to_string(),
+        }
+    }
+
+    /// get_tls_config loads the certificate and the key to serve HTTPS
+    /// with, if any.
+    fn get_tls_config(&self) -> Result<Option<rustls::ServerConfig>, String> {
+        match (&self.https_cert, &self.https_key) {
+            (Some(cert), Some(key)) => tls_config(cert, key).map(Some),
+            (None, None) => Ok(None),
+            // clap requires both
+            _ => Err("--https-cert and --https-key go together".to_string()),
+        }
+    }
+
+    fn open_db(&self) -> Result<Db, String> {
+        let data = match &self.database {
+            None => Data::new(HashMap::new()),
+            Some(path) => {
+                let path = Path::new(&path);
+                let format = self
+                    .database_format
+                    .unwrap_or_else(|| DataFormat::from_path(path));
+
+                // a new database starts with an empty file
+                OpenOptions::new()
+                    .write(true)
+                    .create(true)
+                    .truncate(false)
+                    .open(path)
+                    .map_err(|err| err.to_string())?;
+
+                let storage = FileBackend::new(path.to_path_buf(), format);
+                let loader = storage.clone();
+                let contents = parse_with_timeout(
+                    move || loader.load().map_err(|err| err.to_string()),
+                    Duration::from_secs(self.db_load_timeout),
+                )
+                .map_err(|err| format!("parse data: {}", err))?;
+
+                Data::from_entries(contents).with_storage(storage)
+            }
+        };
+
+        let data = match &self.audit_log {
+            None => data,
+            Some(path) => {
+                let file = OpenOptions::new()
+                    .create(true)
+                    .append(true)
+                    .open(path)
+                    .map_err(|err| format!("open audit log {}: {}", path.display(), err))?;
+                data.with_audit_log(file)
+            }
+        };
+
+        Ok(Db::new(
+            data.with_hard_delete(self.hard_delete)
+                .with_hash_collision_retry(!self.no_hash_collision_retry)
+                .with_duplicate_targets(self.allow_duplicate_targets)
+                .with_id_alphabet(self.id_alphabet.clone())
+                .with_id_generator(self.random_id_generator, self.id_length.get())
+                .with_private_targets(self.allow_private_targets)
+                .with_allowed_schemes(&self.allowed_schemes)
+                .with_limits(self.max_id_length, self.max_target_length)
+                .with_max_entries(self.max_entries)
+                .with_case_insensitive_ids(self.case_insensitive)?,
+        ))
+    }
+}
+
+/// cpus returns the number of CPUs goto can use.
+fn cpus() -> usize {
+    std::thread::available_parallelism()
+        .map(NonZeroUsize::get)
+        .unwrap_or(1)
+}
+
+/// tls_config builds the TLS configuration of the server from PEM files of
+/// a certificate chain and of its PKCS#8 or RSA private key.
+fn tls_config(cert_path: &Path, key_path: &Path) -> Result<rustls::ServerConfig, String> {
+    use rustls::internal::pemfile::{certs, pkcs8_private_keys, rsa_private_keys};
+    use std::io::BufReader;
+
+    let open = |path: &Path| {
+        File::open(path)
+            .map(BufReader::new)
+            .map_err(|err| format!("open {}: {}", path.display(), err))
+    };
+
+    let certs = certs(&mut open(cert_path)?)
+        .ok()
+        .filter(|certs| !certs.is_empty())
+        .ok_or_else(|| format!("no certificate in {}", cert_path.display()))?;
+
+    let mut keys = pkcs8_private_keys(&mut open(key_path)?).unwrap_or_default();
+    if keys.is_empty() {
+        keys = rsa_private_keys(&mut open(key_path)?).unwrap_or_default();
+    }
+    if keys.is_empty() {
+        return Err(format!("no private key in {}", key_path.display()));
+    }
+
+    let mut config = rustls::ServerConfig::new(rustls::NoClientAuth::new());
+    config
+        .set_single_cert(certs, keys.remove(0))
+        .map_err(|err| format!("invalid certificate or key: {}", err))?;
+    Ok(config)
+}
+
+/// cors builds the CORS middleware allowing cross-origin requests from
+/// `origins`. It is disabled when `origins` is empty, so that no CORS headers
+/// are sent back.
+fn cors(origins: &[String]) -> Condition<Cors> {
+    let cors = if origins.iter().any(|origin| origin == "*") {
+        Cors::default().allow_any_origin().send_wildcard()
+    } else {
+        origins
+            .iter()
+            .fold(Cors::default(), |cors, origin| cors.allowed_origin(origin))
+    };
+
+    Condition::new(
+        !origins.is_empty(),
+        cors.allow_any_method().allow_any_header(),
+    )
+}
+
+/// capabilities advertises the optional endpoints of the API in the
+/// `CAPABILITIES_HEADER` of every response. There are none in read-only mode.
+fn capabilities(read_only: bool) -> Condition<DefaultHeaders> {
+    Condition::new(
+        !read_only,
+        DefaultHeaders::new().header(CAPABILITIES_HEADER, "shorten"),
+    )
+}
+
+/// RequestId identifies a request in the logs. It is taken from the
+/// `REQUEST_ID_HEADER` of the request, e.g. set by a load balancer, or
+/// generated.
+#[derive(Clone, Debug, PartialEq)]
+struct RequestId(String);
+
+/// parse_request_id validates a request id: at most `MAX_REQUEST_ID_LENGTH`
+/// letters, digits and hyphens.
+fn parse_request_id(value: &header::HeaderValue) -> Result<RequestId, String> {
+    let id = value.to_str().unwrap_or_default();
+    if id.is_empty() || id.len() > MAX_REQUEST_ID_LENGTH {
+        return Err(format!(
+            "X-Request-ID must be 1 to {} characters long",
+            MAX_REQUEST_ID_LENGTH
+        ));
+    }
+    if !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return Err("X-Request-ID must only contain letters, digits and hyphens".to_string());
+    }
+    Ok(RequestId(id.to_string()))
+}
+
+/// request_id stores the `RequestId` of every request in its extensions,
+/// adds it to the logs written while handling the request, and sends it back
+/// in the `REQUEST_ID_HEADER` of the response. Requests with an invalid id
+/// are rejected with `400 Bad Request`.
+fn request_id<S, B>(
+    req: ServiceRequest,
+    srv: &mut S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    use tracing::Instrument as _;
+
+    let id = match req.headers().get(REQUEST_ID_HEADER) {
+        Some(value) => parse_request_id(value),
+        None => Ok(RequestId(uuid::Uuid::new_v4().to_string())),
+    };
+    let call = id.map(|id| {
+        req.extensions_mut().insert(id.clone());
+        let span = tracing::info_span!("request_id", request_id = %id.0);
+        (id, srv.call(req).instrument(span))
+    });
+
+    async move {
+        let (id, fut) = call.map_err(error::ErrorBadRequest)?;
+        let mut res = fut.await?;
+        res.headers_mut().insert(
+            header::HeaderName::from_static(REQUEST_ID_HEADER),
+            header::HeaderValue::from_str(&id.0).expect("valid request id"),
+        );
+        Ok(res)
+    }
+}
+
+/// security_headers adds `HTML_SECURITY_HEADERS` to the HTML responses, and
+/// `X-Content-Type-Options: nosniff` to every response, unless it is
+/// disabled.
+fn security_headers<S, B>(
+    req: ServiceRequest,
+    srv: &mut S,
+    enabled: bool,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let fut = srv.call(req);
+    async move {
+        let mut res = fut.await?;
+        if !enabled {
+            return Ok(res);
+        }
+
+        let is_html = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map_or(false, |content_type| content_type.starts_with("text/html"));
+        let headers = res.headers_mut();
+        headers.insert(
+            header::X_CONTENT_TYPE_OPTIONS,
+            header::HeaderValue::from_static("nosniff"),
+        );
+        if is_html {
+            for &(name, value) in HTML_SECURITY_HEADERS {
+                headers.insert(
+                    header::HeaderName::from_static(name),
+                    header::HeaderValue::from_static(value),
+                );
+            }
+        }
+        Ok(res)
+    }
+}
+
+/// ServerTimings collects how long the steps of a request took, to send
+/// them back in its `SERVER_TIMING_HEADER`.
+#[derive(Debug, Default)]
+struct ServerTimings(Vec<(&'static str, Duration)>);
+
+impl ServerTimings {
+    /// record adds the `duration` of the step `name` to the timings of `req`.
+    /// It does nothing unless the request goes through `server_timing`.
+    fn record(req: &HttpRequest, name: &'static str, duration: Duration) {
+        if let Some(timings) = req.extensions_mut().get_mut::<ServerTimings>() {
+            timings.0.push((name, duration));
+        }
+    }
+
+    /// header_value formats the timings as `name;dur=milliseconds` metrics,
+    /// or returns None if no step was recorded.
+    fn header_value(&self) -> Option<String> {
+        if self.0.is_empty() {
+            return None;
+        }
+
+        let metrics: Vec<String> = self
+            .0
+            .iter()
+            .map(|(name, duration)| format!("{};dur={:.3}", name, duration.as_secs_f64() * 1000.))
+            .collect();
+        Some(metrics.join(", "))
+    }
+}
+
+#[test]
+fn test_server_timings_header_value() {
+    assert_eq!(None, ServerTimings::default().header_value());
+
+    let timings = ServerTimings(vec![
+        ("db-lookup", Duration::from_micros(500)),
+        ("redirect", Duration::from_micros(123_456)),
+    ]);
+    assert_eq!(
+        Some("db-lookup;dur=0.500, redirect;dur=123.456".to_string()),
+        timings.header_value()
+    );
+}
+
+/// server_timing lets the handlers record how long the steps of a request
+/// took, and sends them back in the `SERVER_TIMING_HEADER` of the response.
+fn server_timing<S, B>(
+    req: ServiceRequest,
+    srv: &mut S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    req.extensions_mut().insert(ServerTimings::default());
+    let fut = srv.call(req);
+
+    async move {
+        let mut res = fut.await?;
+        let timings = res.request().extensions_mut().remove::<ServerTimings>();
+        if let Some(value) = timings.and_then(|timings| timings.header_value()) {
+            res.headers_mut().insert(
+                header::HeaderName::from_static(SERVER_TIMING_HEADER),
+                header::HeaderValue::from_str(&value).expect("valid server timing"),
+            );
+        }
+        Ok(res)
+    }
+}
+
+/// no_content_preflight turns successful CORS preflight responses into
+/// `204 No Content`, since they never have a body.
+fn no_content_preflight<S, B>(
+    req: ServiceRequest,
+    srv: &mut S,
+) -> impl Future<Output = Result<ServiceResponse<B>, Error>>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+{
+    let is_preflight = req.method() == Method::OPTIONS
+        && req
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+    let fut = srv.call(req);
+    async move {
+        let mut res = fut.await?;
+        if is_preflight && res.status() == StatusCode::OK {
+            *res.response_mut().status_mut() = StatusCode::NO_CONTENT;
+        }
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod cli_tests {
+    use super::*;
+
+    /// default_cli returns the options goto runs with when none are given.
+    fn default_cli() -> Cli {
+        Cli::parse_from(["goto-api"])
+    }
+
+    #[test]
+    fn test_cli_definition() {
+        use clap::CommandFactory;
+
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn test_from_env() {
+        use clap::{CommandFactory, Parser};
+
+        // the tests share the environment of the process, so the variables
+        // are only checked by name instead of being set
+        let command = Cli::command();
+        let env = |id: &str| {
+            command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .and_then(|arg| arg.get_env())
+                .and_then(|env| env.to_str())
+                .map(str::to_string)
+        };
+        assert_eq!(Some("GOTO_ADDR".to_string()), env("addr"));
+        assert_eq!(Some("GOTO_DATABASE".to_string()), env("database"));
+        assert_eq!(
+            Some("GOTO_FRONT_DIR".to_string()),
+            env("front_dist_directory")
+        );
+        assert_eq!(Some("GOTO_CORS_ORIGINS".to_string()), env("cors_origins"));
+
+        let cli = Cli::try_parse_from(vec![
+            "goto-api",
+            "--cors-origins",
+            "https://a.com,https://b.com",
+        ])
+        .unwrap();
+        assert_eq!(
+            vec!["https://a.com".to_string(), "https://b.com".to_string()],
+            cli.cors_origins
+        );
+
+        let cli = Cli::try_parse_from(vec!["goto-api", "--addr", "127.0.0.1:4321"]).unwrap();
+        assert_eq!("127.0.0.1:4321", cli.get_addr());
+        assert!(!cli.allow_private_targets);
+
+        let cli = Cli::try_parse_from(vec!["goto-api", "--allow-private-targets"]).unwrap();
+        assert!(cli.allow_private_targets);
+
+        let cli = Cli::try_parse_from(vec!["goto-api"]).unwrap();
+        assert_eq!(vec!["http", "https"], cli.allowed_schemes);
+
+        let cli =
+            Cli::try_parse_from(vec!["goto-api", "--allowed-schemes", "http,https,ftp"]).unwrap();
+        assert_eq!(vec!["http", "https", "ftp"], cli.allowed_schemes);
+
+        let cli = Cli::try_parse_from(vec!["goto-api"]).unwrap();
+        assert_eq!(DEFAULT_MAX_ID_LENGTH, cli.max_id_length);
+        assert_eq!(DEFAULT_MAX_TARGET_LENGTH, cli.max_target_length);
+
+        let cli = Cli::try_parse_from(vec![
+            "goto-api",
+            "--max-id-length",
+            "10",
+            "--max-target-length",
+            "100",
+        ])
+        .unwrap();
+        assert_eq!(10, cli.max_id_length);
+        assert_eq!(100, cli.max_target_length);
+    }
+
+    #[test]
+    fn test_get_preview() {
+        use clap::Parser;
+
+        let cli = Cli::try_parse_from(vec!["goto-api"]).unwrap();
+        assert_eq!(None, cli.get_preview());
+
+        let cli = Cli::try_parse_from(vec!["goto-api", "--preview"]).unwrap();
+        let preview = cli.get_preview().unwrap();
+        assert_eq!(5, preview.delay);
+        assert_eq!("goto_trusted", preview.cookie_name);
+
+        let cli = Cli::try_parse_from(vec![
+            "goto-api",
+            "--preview",
+            "--preview-delay",
+            "10",
+            "--cookie-name",
+            "trusted",
+        ])
+        .unwrap();
+        let preview = cli.get_preview().unwrap();
+        assert_eq!(10, preview.delay);
+        assert_eq!("trusted", preview.cookie_name);
+
+        assert!(Cli::try_parse_from(vec!["goto-api", "--cookie-name", "a=b"]).is_err());
+    }
+
+    #[test]
+    fn test_get_base_url() {
+        use clap::Parser;
+
+        let cli = Cli::try_parse_from(vec!["goto-api"]).unwrap();
+        assert_eq!(None, cli.get_base_url());
+
+        let cli =
+            Cli::try_parse_from(vec!["goto-api", "--base-url", "https://go.company.com"]).unwrap();
+        let base_url = cli.get_base_url().unwrap();
+        assert_eq!("https://go.company.com/hello", base_url.short_url("hello"));
+
+        assert!(Cli::try_parse_from(vec!["goto-api", "--base-url", "not a URL"]).is_err());
+    }
+
+    #[test]
+    fn test_get_front_dir() {
+        let cli = default_cli();
+        assert_eq!("front/dist/", cli.get_front_dir());
+
+        let cli = Cli {
+            front_dist_directory: Some("/hello/world/".into()),
+            ..default_cli()
+        };
+        assert_eq!("/hello/world/", cli.get_front_dir());
+    }
+
+    #[test]
+    fn test_get_addr() {
+        let cli = default_cli();
+        assert_eq!("127.0.0.1:8080", cli.get_addr());
+
+        let cli = Cli {
+            addr: Some("123.34.56.78:99999".into()),
+            ..default_cli()
+        };
+        assert_eq!("123.34.56.78:99999", cli.get_addr());
+    }
+
+    #[test]
+    fn test_get_workers() {
+        let cli = Cli::parse_from(["goto-api", "--workers", "1", "--blocking-threads", "2"]);
+        assert_eq!(1, cli.get_workers());
+        assert_eq!(2, cli.get_blocking_threads());
+
+        let cli = Cli::parse_from(["goto-api"]);
+        assert_eq!(cpus(), cli.get_workers());
+        assert_eq!(cpus() * 5, cli.get_blocking_threads());
+
+        assert!(Cli::try_parse_from(["goto-api", "--workers", "-1"]).is_err());
+        assert!(Cli::try_parse_from(["goto-api", "--blocking-threads", "many"]).is_err());
+    }
+
+    #[test]
+    fn test_get_bind_fd() {
+        let cli = Cli::parse_from(["goto-api", "--bind-fd", "3"]);
+        assert_eq!(Some(3), cli.get_bind_fd());
+    }
+
+    /// write_self_signed writes a self-signed certificate and its key to
+    /// temporary files.
+    fn write_self_signed(name: &str) -> (PathBuf, PathBuf) {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+        let dir = std::env::temp_dir();
+        let cert_path = dir.join(format!("{}.crt", name));
+        let key_path = dir.join(format!("{}.key", name));
+        std::fs::write(&cert_path, cert.serialize_pem().unwrap()).unwrap();
+        std::fs::write(&key_path, cert.serialize_private_key_pem()).unwrap();
+        (cert_path, key_path)
+    }
+
+    #[test]
+    fn test_get_tls_config() {
+        let cli = Cli::parse_from(["goto-api"]);
+        assert!(cli.get_tls_config().unwrap().is_none());
+        assert_eq!("127.0.0.1:8080", cli.get_addr());
+
+        // one doesn't go without the other
+        assert!(Cli::try_parse_from(["goto-api", "--https-cert", "goto.crt"]).is_err());
+        assert!(Cli::try_parse_from(["goto-api", "--https-key", "goto.key"]).is_err());
+
+        let (cert, key) = write_self_signed("test_get_tls_config");
+        let (cert, key) = (cert.to_str().unwrap(), key.to_str().unwrap());
+        let cli = Cli::parse_from(["goto-api", "--https-cert", cert, "--https-key", key]);
+        assert!(cli.get_tls_config().unwrap().is_some());
+        assert_eq!("0.0.0.0:443", cli.get_addr());
+
+        // the files are swapped
+        let cli = Cli::parse_from(["goto-api", "--https-cert", key, "--https-key", cert]);
+        let err = cli.get_tls_config().unwrap_err();
+        assert!(err.starts_with("no certificate in "), "{}", err);
+
+        let cli = Cli::parse_from(["goto-api", "--https-cert", cert, "--https-key", cert]);
+        let err = cli.get_tls_config().unwrap_err();
+        assert!(err.starts_with("no private key in "), "{}", err);
+
+        let cli = Cli::parse_from([
+            "goto-api",
+            "--https-cert",
+            "/does/not/exist.crt",
+            "--https-key",
+            key,
+        ]);
+        let err = cli.get_tls_config().unwrap_err();
+        assert!(err.starts_with("open /does/not/exist.crt: "), "{}", err);
+    }
+
+    #[test]
+    fn integration_test_https() {
+        let (cert, key) = write_self_signed("integration_test_https");
+        let cli = Cli::parse_from([
+            "goto-api",
+            "--https-cert",
+            cert.to_str().unwrap(),
+            "--https-key",
+            key.to_str().unwrap(),
+        ]);
+        let db = cli.open_db().unwrap();
+        let tls = cli.get_tls_config().unwrap().unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let system = actix_web::rt::System::new("integration_test_https");
+            let server = HttpServer::new(move || App::new().data(db.clone()).service(health))
+                .workers(1)
+                .bind_rustls("127.0.0.1:0", tls)
+                .unwrap();
+            sender.send(server.addrs()[0]).unwrap();
+            let _server = server.run();
+            system.run()
+        });
+
+        let addr = receiver.recv().unwrap();
+        let body = tokio::runtime::Runtime::new()
+            .unwrap()
+            .block_on(async {
+                reqwest::Client::builder()
+                    // the certificate is self-signed
+                    .danger_accept_invalid_certs(true)
+                    .build()?
+                    .get(format!("https://{}/health", addr))
+                    .send()
+                    .await?
+                    .error_for_status()?
+                    .text()
+                    .await
+            })
+            .unwrap();
+        assert!(body.contains(r#""entries":0"#), "{}", body);
+    }
+
+    // a server with a single worker still answers requests
+    #[test]
+    fn integration_test_single_worker() {
+        use std::io::{Read, Write};
+
+        let cli = Cli::parse_from(["goto-api", "--workers", "1"]);
+        let db = cli.open_db().unwrap();
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let system = actix_web::rt::System::new("integration_test_single_worker");
+            let server = HttpServer::new(move || App::new().data(db.clone()).service(health))
+                .workers(cli.get_workers())
+                .bind("127.0.0.1:0")
+                .unwrap();
+            sender.send(server.addrs()[0]).unwrap();
+            let _server = server.run();
+            system.run()
+        });
+
+        let addr = receiver.recv().unwrap();
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        stream
+            .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+            .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.contains(r#""entries":0"#), "{}", response);
+    }
+
+    // stopping gracefully waits for the requests in progress
+    #[test]
+    fn integration_test_graceful_stop() {
+        use std::io::{Read, Write};
+
+        let (sender, receiver) = std::sync::mpsc::channel();
+        std::thread::spawn(move || {
+            let system = actix_web::rt::System::new("integration_test_graceful_stop");
+            let server = HttpServer::new(|| {
+                App::new().route(
+                    "/slow",
+                    web::get().to(|| async {
+                        actix_web::rt::time::delay_for(Duration::from_millis(500)).await;
+                        "done"
+                    }),
+                )
+            })
+            .workers(1)
+            .shutdown_timeout(DEFAULT_SHUTDOWN_TIMEOUT_SECS)
+            .disable_signals()
+            .bind("127.0.0.1:0")
+            .unwrap();
+            let addr = server.addrs()[0];
+            sender.send((addr, server.run())).unwrap();
+            system.run()
+        });
+        let (addr, server) = receiver.recv().unwrap();
+
+        let request = std::thread::spawn(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            stream
+                .write_all(b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+                .unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        });
+
+        // let the request start before stopping
+        std::thread::sleep(Duration::from_millis(100));
+        actix_web::rt::System::new("stop").block_on(server.stop(true));
+
+        let response = request.join().unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+        assert!(response.ends_with("done"), "{}", response);
+        assert!(std::net::TcpStream::connect(addr).is_err());
+    }
+
+    #[test]
+    fn test_open_db_no_persistence() {
+        let cli = default_cli();
+        let db = cli.open_db().unwrap();
+        let data = db.read().unwrap();
+
+        assert_eq!(None, data.storage.path());
+    }
+
+    #[test]
+    fn test_open_db_new_file() {
+        use std::env::temp_dir;
+
+        let dir = temp_dir();
+        let tmpfile_path = format!("{}/tmpfile3.txt", dir.to_str().unwrap());
+        let cli = Cli {
+            database: Some(tmpfile_path),
+            ..default_cli()
+        };
+        let db = cli.open_db().unwrap();
+        let data = db.read().unwrap();
+
+        match data.storage.path() {
+            None => panic!("expected persistence"),
+            Some(path) => {
+                let metadata = path.metadata().unwrap();
+                assert!(metadata.is_file());
+            }
+        };
+    }
+
+    #[test]
+    fn test_open_db_existing_file() {
+        use std::env::temp_dir;
+        use std::fs::File;
+
+        let dir = temp_dir();
+        let tmpfile_path = format!("{}/tmpfile.txt", dir.to_str().unwrap());
+
+        File::create(&tmpfile_path).unwrap();
+
+        let cli = Cli {
+            database: Some(tmpfile_path),
+            ..default_cli()
+        };
+        let db = cli.open_db().unwrap();
+        let data = db.read().unwrap();
+
+        assert!(data.storage.path().is_some());
+    }
+
+    #[test]
+    fn test_open_db_existing_file_with_data() {
+        use std::env::temp_dir;
+        use std::fs::File;
+        use std::io::Write;
+
+        let dir = temp_dir();
+        let tmpfile_path = format!("{}/temporary-file.txt", dir.to_str().unwrap());
+
+        let mut file = File::create(&tmpfile_path).unwrap();
+        file.write_all(b"hello: \"http://world\"\n").unwrap();
+
+        let cli = Cli {
+            database: Some(tmpfile_path),
+            ..default_cli()
+        };
+        let db = cli.open_db().unwrap();
+        let data = db.read().unwrap();
+
+        assert!(data.storage.path().is_some());
+        assert_eq!(Some(&"http://world".to_string()), data.get("hello"));
+    }
+
+    #[test]
+    fn test_open_db_existing_json_file_with_data() {
+        use std::env::temp_dir;
+        use std::fs::File;
+        use std::io::Write;
+
+        let dir = temp_dir();
+        let tmpfile_path = format!("{}/temporary-file.json", dir.to_str().unwrap());
+
+        let mut file = File::create(&tmpfile_path).unwrap();
+        file.write_all(br#"{"hello": "http://world"}"#).unwrap();
+
+        let cli = Cli {
+            database: Some(tmpfile_path),
+            ..default_cli()
+        };
+        let db = cli.open_db().unwrap();
+        let data = db.read().unwrap();
+
+        assert!(data.storage.path().is_some());
+        assert_eq!(Some(&"http://world".to_string()), data.get("hello"));
+    }
+
+    #[test]
+    fn test_open_db_audit_log() {
+        let dir = std::env::temp_dir();
+        let database = dir.join("test_open_db_audit_log.yml");
+        let audit_log = dir.join("test_open_db_audit_log.ndjson");
+        let _ = std::fs::remove_file(&database);
+        // the audit log is never read, so it can hold anything
+        std::fs::write(&audit_log, "not JSON\n").unwrap();
+
+        let cli = Cli::parse_from([
+            "goto-api",
+            "--database",
+            database.to_str().unwrap(),
+            "--audit-log",
+            audit_log.to_str().unwrap(),
+        ]);
+        let db = cli.open_db().unwrap();
+        db.write().unwrap().insert("hi", "qwerty").unwrap();
+
+        let got = std::fs::read_to_string(&audit_log).unwrap();
+        let mut lines = got.lines();
+        assert_eq!(Some("not JSON"), lines.next());
+        let record: serde_json::Value = serde_json::from_str(lines.next().unwrap()).unwrap();
+        assert_eq!("insert", record["op"]);
+        assert_eq!("qwerty", record["target"]);
+        assert!(std::fs::read_to_string(&database)
+            .unwrap()
+            .contains("qwerty"));
+
+        let cli = Cli::parse_from(["goto-api", "--audit-log", "/does/not/exist/audit.ndjson"]);
+        let err = cli.open_db().err().unwrap();
+        assert!(err.starts_with("open audit log "), "{}", err);
+    }
+
+    // targets and ids that YAML would read as something else if they weren't
+    // quoted
+    #[test]
+    fn test_open_db_round_trip() {
+        let entries = [
+            ("port", "https://example.com:8080/path"),
+            ("yes", "https://example.com/?answer=no"),
+            ("007", "https://example.com/#: colon"),
+            ("null", "https://example.com/~"),
+            ("1e3", "https://example.com/'quoted'\"path\""),
+        ];
+
+        for extension in &["yml", "json", "ndjson"] {
+            let mut path = std::env::temp_dir();
+            path.push(format!("test_open_db_round_trip.{}", extension));
+            let _ = std::fs::remove_file(&path);
+
+            let mut data = Data::new(HashMap::new()).with_persistence(path.clone());
+            for (id, target) in &entries {
+                data.insert(id, target).unwrap();
+            }
+
+            let cli = Cli::parse_from(["goto-api", "--database", path.to_str().unwrap()]);
+            let db = cli.open_db().unwrap();
+            let data = db.read().unwrap();
+            for (id, target) in &entries {
+                assert_eq!(Some(&target.to_string()), data.get(id), "{}", extension);
+            }
+        }
+    }
+
+    #[test]
+    fn test_open_db_existing_json_file_with_bad_data() {
+        use std::env::temp_dir;
+        use std::fs::File;
+        use std::io::Write;
+
+        let dir = temp_dir();
+        let tmpfile_path = format!("{}/tmpfile1.json", dir.to_str().unwrap());
+
+        let mut file = File::create(&tmpfile_path).unwrap();
+        file.write_all(b"hello: \"http://world\"\n").unwrap();
+
+        let cli = Cli {
+            database: Some(tmpfile_path),
+            ..default_cli()
+        };
+
+        let res = cli.open_db();
+        assert!(res.is_err());
+        if let Err(msg) = res {
+            assert!(msg.contains("parse data: expected value"), "{}", msg);
+        }
+    }
+
+    #[test]
+    fn test_open_db_existing_file_with_bad_data() {
+        use std::env::temp_dir;
+        use std::fs::File;
+        use std::io::Write;
+
+        let dir = temp_dir();
+        let tmpfile_path = format!("{}/tmpfile1.txt", dir.to_str().unwrap());
+
+        let mut file = File::create(&tmpfile_path).unwrap();
+        file.write_all(b"ds;flsd'f sdl;flfs~~!./'' /sf/;dsf;lsdf")
+            .unwrap();
+
+        let cli = Cli {
+            database: Some(tmpfile_path),
+            ..default_cli()
+        };
+
+        let res = cli.open_db();
+        assert!(res.is_err());
+        if let Err(msg) = res {
+            assert!(msg.contains("parse data: invalid type:"));
+        }
+    }
+}
+
+/// stop_on_signal stops the server gracefully on SIGINT or SIGTERM: it stops
+/// accepting connections, and waits up to --shutdown-timeout for the
+/// requests in progress.
+#[cfg(not(tarpaulin_include))]
+async fn stop_on_signal(server: actix_web::dev::Server) {
+    use actix_web::rt::signal::unix::{signal, SignalKind};
+
+    let mut terminate = signal(SignalKind::terminate()).expect("listen to SIGTERM");
+    let mut interrupt = signal(SignalKind::interrupt()).expect("listen to SIGINT");
+    let terminate = Box::pin(async move {
+        terminate.recv().await;
+        "SIGTERM"
+    });
+    let interrupt = Box::pin(async move {
+        interrupt.recv().await;
+        "SIGINT"
+    });
+    let (name, _) = futures::future::select(terminate, interrupt)
+        .await
+        .factor_first();
+
+    tracing::info!("Received {}, draining connections...", name);
+    server.stop(true).await;
+}
+
+#[actix_web::main]
+#[cfg(not(tarpaulin_include))]
+async fn main() -> std::io::Result<()> {
+    let args = Cli::parse();
+
+    let front_dist_directory = args.get_front_dir();
+    let addr: String = args.get_addr();
+    let bind_fd = args.get_bind_fd();
+    let tls = args.get_tls_config().expect("load TLS certificate");
+    let systemd_notify = args.systemd_notify;
+    let shutdown_timeout = args.shutdown_timeout;
+    let workers = args.get_workers();
+    let blocking_threads = args.get_blocking_threads();
+    // actix-web reads the size of its blocking thread pool from the
+    // environment when the pool is first used
+    std::env::set_var("ACTIX_THREADPOOL", blocking_threads.to_string());
+    let db = args.open_db().expect("open db");
+    let metrics = Metrics::new();
+    let preview = args.get_preview();
+    let redirect_body = args.redirect_body.clone();
+    let events = Events::new(args.ws_buffer.get());
+    let base_url = args.get_base_url();
+    let cors_origins = args.cors_origins;
+    let read_only = args.read_only;
+    let admin_password = args.admin_password.clone().map(AdminPassword);
+    let send_security_headers = !args.no_security_headers;
+    let prefix = args.prefix.clone().unwrap_or_default();
+    let front_index = FrontIndex {
+        path: Path::new(&front_dist_directory).join("index.html"),
+        api_base: prefix.clone(),
+    };
+
+    // logs written to a file are lost if the guard is dropped early
+    let (log_writer, _log_guard) =
+        logging::writer(args.log_file.as_deref(), args.log_keep_days).expect("open log file");
+    tracing::subscriber::set_global_default(logging::subscriber(
+        &args.log_format,
+        logging::env_filter(),
+        log_writer,
+    ))
+    .expect("set up logging");
+
+    {
+        let data = db.read().expect("read db");
+        if data.is_empty() {
+            tracing::info!("starting with an empty database");
+        } else {
+            tracing::info!(entries = data.len(), "loaded short urls from the database");
+        }
+    }
+
+    if read_only {
+        tracing::info!("Running in read-only mode");
+    }
+
+    tracing::info!(
+        workers,
+        blocking_threads,
+        "Starting with {} workers",
+        workers
+    );
+    let server = HttpServer::new(move || {
+        App::new()
+            .wrap(cors(&cors_origins))
+            .wrap(capabilities(read_only))
+            .wrap_fn(no_content_preflight)
+            .wrap_fn(move |req, srv| security_headers(req, srv, send_security_headers))
+            .wrap_fn(server_timing)
+            .wrap(Instrument::new(metrics.clone()))
+            .wrap_fn(request_id)
+            .wrap(TracingLogger)
+            .data(db.clone())
+            .data(metrics.clone())
+            .data(redirect_body.clone())
+            .data(events.clone())
+            .data(front_index.clone())
+            .data(Prefix(prefix.clone()))
+            .configure(|cfg| {
+                if let Some(preview) = &preview {
+                    cfg.data(preview.clone());
+                }
+                if let Some(base_url) = &base_url {
+                    cfg.data(base_url.clone());
+                }
+                if let Some(admin_password) = &admin_password {
+                    cfg.data(admin_password.clone());
+                }
+            })
+            .service(
+                web::scope(&prefix).configure(|cfg| routes(cfg, &front_dist_directory, read_only)),
+            )
+    })
+    .workers(workers)
+    .shutdown_timeout(shutdown_timeout)
+    // stop_on_signal stops the server instead, so that it can be logged
+    .disable_signals();
+
+    let server = match (bind_fd, tls) {
+        (Some(fd), tls) => {
+            let listener = systemd::listener(fd)?;
+            tracing::info!(
+                fd,
+                addr = ?listener.local_addr()?,
+                https = tls.is_some(),
+                "goto listening on file descriptor {}",
+                fd
+            );
+            match tls {
+                Some(config) => server.listen_rustls(listener, config)?,
+                None => server.listen(listener)?,
+            }
+        }
+        (None, Some(config)) => {
+            tracing::info!(addr = %addr, "goto listening at https://{}/", &addr);
+            server.bind_rustls(addr, config)?
+        }
+        (None, None) => {
+            tracing::info!(addr = %addr, "goto listening at http://{}/", &addr);
+            server.bind(addr)?
+        }
+    }
+    .run();
+    actix_web::rt::spawn(stop_on_signal(server.clone()));
+
+    if systemd_notify {
+        match systemd::notify("READY=1") {
+            Ok(true) => tracing::info!("notified systemd"),
+            Ok(false) => tracing::warn!("--systemd-notify is set, but NOTIFY_SOCKET isn't"),
+            Err(err) => tracing::warn!(error = %err, "could not notify systemd"),
+        }
+    }
+
+    server.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hash() {
+        assert_eq!("4cca4", hash("something"));
+        assert_eq!("284a1", hash("something else"));
+    }
+
+    #[test]
+    fn test_hash_with_alphabet() {
+        let got = hash_with_alphabet("something", 5, BASE62_ALPHABET.as_bytes());
+        assert_eq!(5, got.len());
+        assert!(got.chars().all(|c| c.is_ascii_alphanumeric()), "{}", got);
+        assert_eq!(
+            got,
+            hash_with_alphabet("something", 5, BASE62_ALPHABET.as_bytes())
+        );
+        assert_ne!(
+            got,
+            hash_with_alphabet("something else", 5, BASE62_ALPHABET.as_bytes())
+        );
+
+        // longer than a blake3 hash
+        let got = hash_with_alphabet("something", 40, b"ab");
+        assert_eq!(40, got.len());
+        assert!(got.chars().all(|c| c == 'a' || c == 'b'), "{}", got);
+    }
+
+    #[test]
+    fn test_parse_id_alphabet() {
+        assert_eq!(Ok(BASE62_ALPHABET.to_string()), parse_id_alphabet("base62"));
+        assert_eq!(Ok("abc123".to_string()), parse_id_alphabet("abc123"));
+        assert!(parse_id_alphabet("a").is_err());
+        assert!(parse_id_alphabet("ab/").is_err());
+        assert!(parse_id_alphabet("abca").is_err());
+
+        let data = Data::new(HashMap::new()).with_id_alphabet(parse_id_alphabet("xyz").ok());
+        let id = hashed_id(&data, "https://hello.world");
+        assert_eq!(RANDOM_URL_SIZE, id.len());
+        assert!(id.chars().all(|c| "xyz".contains(c)), "{}", id);
+    }
+
+    #[test]
+    fn test_is_valid_id() {
+        assert!(is_valid_id("hello"));
+        assert!(is_valid_id("my-link_v2"));
+
+        assert!(!is_valid_id(""));
+        assert!(!is_valid_id("hello world"));
+        assert!(!is_valid_id("hello/world"));
+        assert!(!is_valid_id("héllo"));
+    }
+
+    #[test]
+    fn test_upsert_error_status_code() {
+        use actix_web::ResponseError;
+
+        for (err, status) in vec![
+            (UpsertError::AlreadyExists, StatusCode::CONFLICT),
+            (UpsertError::NotFound, StatusCode::NOT_FOUND),
+            (UpsertError::IdTooLong, StatusCode::BAD_REQUEST),
+            (UpsertError::InvalidId, StatusCode::BAD_REQUEST),
+            (UpsertError::ReservedId, StatusCode::BAD_REQUEST),
+            (UpsertError::TargetTooLong, StatusCode::BAD_REQUEST),
+            (
+                UpsertError::MalformedUrl(url::ParseError::EmptyHost),
+                StatusCode::BAD_REQUEST,
+            ),
+            (UpsertError::ForbiddenTarget, StatusCode::BAD_REQUEST),
+            (UpsertError::DisallowedScheme, StatusCode::BAD_REQUEST),
+            (
+                UpsertError::PersistenceError(std::io::Error::from(std::io::ErrorKind::Other)),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (
+                UpsertError::PoisonedLock("poisoned".to_string()),
+                StatusCode::INTERNAL_SERVER_ERROR,
+            ),
+            (UpsertError::Canceled, StatusCode::INTERNAL_SERVER_ERROR),
+        ] {
+            assert_eq!(status, err.status_code(), "{}", err);
+        }
+    }
+
+    #[test]
+    fn test_check_id() {
+        check_id(&"a".repeat(DEFAULT_MAX_ID_LENGTH), DEFAULT_MAX_ID_LENGTH).unwrap();
+
+        let got = check_id(
+            &"a".repeat(DEFAULT_MAX_ID_LENGTH + 1),
+            DEFAULT_MAX_ID_LENGTH,
+        );
+        assert!(matches!(got, Err(UpsertError::IdTooLong)), "{:?}", got);
+
+        // the length is checked before the characters
+        let got = check_id("hello world", 5);
+        assert!(matches!(got, Err(UpsertError::IdTooLong)), "{:?}", got);
+
+        let got = check_id("hello world", DEFAULT_MAX_ID_LENGTH);
+        assert!(matches!(got, Err(UpsertError::InvalidId)), "{:?}", got);
+    }
+
+    #[test]
+    fn test_is_private_target() {
+        for target in &[
+            "http://127.0.0.1",
+            "http://localhost",
+            "http://api.localhost:8080",
+            "http://10.0.0.1",
+            "http://192.168.1.1/admin",
+            "http://172.16.0.1",
+            "http://169.254.169.254/latest/meta-data/",
+            "http://0.0.0.0",
+            "http://[::1]",
+            "http://[fd00::1]",
+            "http://[fe80::1]",
+            "http://[::ffff:127.0.0.1]",
+            "http://db.internal",
+        ] {
+            assert!(
+                is_private_target(&Url::parse(target).unwrap()),
+                "{}",
+                target
+            );
+        }
+
+        for target in &[
+            "http://1.1.1.1",
+            "http://[2606:4700::1111]",
+            "http://hello.world",
+            "mailto:a@b.c",
+        ] {
+            assert!(
+                !is_private_target(&Url::parse(target).unwrap()),
+                "{}",
+                target
+            );
+        }
+    }
+
+    #[test]
+    fn test_create_short_url_private_target() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        for target in &["http://127.0.0.1", "http://localhost", "http://10.0.0.1"] {
+            let err = create_short_url(web::Data::new(db.clone()), target, Some("hi"), None, None)
+                .unwrap_err();
+            assert!(matches!(err, UpsertError::ForbiddenTarget), "{:?}", err);
+            assert_eq!("target URL not allowed", err.to_string());
+
+            let got = upsert_short_url(web::Data::new(db.clone()), target, "hi");
+            assert!(
+                matches!(got, Err(UpsertError::ForbiddenTarget)),
+                "{:?}",
+                got
+            );
+        }
+        assert!(db.read().unwrap().is_empty());
+
+        let db: Db = Db::new(Data::new(HashMap::new()).with_private_targets(true));
+        create_short_url(
+            web::Data::new(db.clone()),
+            "http://10.0.0.1",
+            Some("hi"),
+            None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            Some(&"http://10.0.0.1".to_string()),
+            db.read().unwrap().get("hi")
+        );
+    }
+
+    #[test]
+    fn test_render_preview_escapes_target() {
+        let got = render_preview("hi", r#"https://a.b/?q="><script>"#, 5);
+        assert!(!got.contains("<script>"), "{}", got);
+        assert!(
+            got.contains(r#"url=https://a.b/?q=&quot;&gt;&lt;script&gt;""#),
+            "{}",
+            got
+        );
+    }
+
+    #[test]
+    fn test_trust_token() {
+        let preview = Preview::new(5, DEFAULT_COOKIE_NAME);
+        let tomorrow = unix_seconds(SystemTime::now()) + 24 * 60 * 60;
+        let token = preview.trust_token(tomorrow);
+        assert!(preview.is_trusted(&token), "{}", token);
+
+        // the expiry is signed
+        let (_, signature) = token.split_once('.').unwrap();
+        assert!(!preview.is_trusted(&format!("{}.{}", tomorrow + 1, signature)));
+        assert!(!preview.is_trusted(&preview.trust_token(1)));
+        assert!(!Preview::new(5, DEFAULT_COOKIE_NAME).is_trusted(&token));
+        for invalid in &["", ".", "abc", "1.%%%", "99999999999.aGVsbG8="] {
+            assert!(!preview.is_trusted(invalid), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_render_qr_code() {
+        let png = render_qr_code("http://127.0.0.1:8080/hello", 300).unwrap();
+        let image = image::load_from_memory(&png).unwrap();
+
+        use image::GenericImageView;
+        assert!(image.width() <= 300);
+        assert!(image.width() > 200);
+        assert_eq!(image.width(), image.height());
+    }
+
+    #[test]
+    fn test_create_short_malformed_url() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let target = "this is not a valid URL".to_string();
+        let id = Some("hello");
+        let err = create_short_url(web::Data::new(db), &target, id, None, None).unwrap_err();
+        assert!(matches!(err, UpsertError::MalformedUrl(_)), "{:?}", err);
+        assert_eq!(
+            "malformed URL: relative URL without a base",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn test_create_short_url() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let target = "https://google.com".to_string();
+        let id = "hello";
+        create_short_url(web::Data::new(db.clone()), &target, Some(id), None, None).unwrap();
+
+        let db = db.read().unwrap();
+        let got = db.get(id).unwrap();
+        assert_eq!(&target, got);
+    }
+
+    #[test]
+    fn test_create_short_url_hashed_id() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let target = "https://google.com";
+        create_short_url(web::Data::new(db.clone()), target, None, None, None).unwrap();
+
+        let id = hash(target);
+        let db = db.read().unwrap();
+        let got = db.get(&id).unwrap();
+        assert_eq!(&target, got);
+    }
+
+    #[test]
+    fn test_create_short_url_hashed_id_deleted() {
+        let target = "https://google.com";
+        let expired = Entry {
+            expires_at: Some(UNIX_EPOCH),
+            ..Entry::from(target.to_string())
+        };
+        let db: Db = Db::new(Data::from_entries(vec![(hash(target), expired)]));
+
+        // the expired short URL keeps its id
+        let got = create_short_url(web::Data::new(db.clone()), target, None, None, None).unwrap();
+        assert_eq!(Shortened::Created(hash(&format!("{}1", target))), got);
+
+        // the deleted short URL can still be restored
+        db.write().unwrap().delete(got.id()).unwrap();
+        let got = create_short_url(web::Data::new(db.clone()), target, None, None, None).unwrap();
+        assert_eq!(Shortened::Created(hash(&format!("{}2", target))), got);
+        assert!(db
+            .write()
+            .unwrap()
+            .restore(&hash(&format!("{}1", target)))
+            .unwrap());
+    }
+
+    #[test]
+    fn test_create_short_url_already_exists() {
+        let id = "hello";
+
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert(id.into(), "some existing value".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let target = "https://google.com";
+        let err = create_short_url(web::Data::new(db), target, Some(id), None, None).unwrap_err();
+        assert!(matches!(err, UpsertError::AlreadyExists), "{:?}", err);
+        assert_eq!("already registered", err.to_string());
+    }
+
+    #[test]
+    fn test_create_short_url_duplicate_target() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let target = "https://google.com";
+        let got = create_short_url(
+            web::Data::new(db.clone()),
+            target,
+            Some("hello"),
+            None,
+            None,
+        );
+        assert_eq!(Shortened::Created("hello".to_string()), got.unwrap());
+        let got = create_short_url(web::Data::new(db.clone()), target, None, None, None);
+        assert_eq!(Shortened::Existing("hello".to_string()), got.unwrap());
+        assert_eq!(1, db.read().unwrap().len());
+
+        // the caller expects the id it asked for
+        let got = create_short_url(web::Data::new(db.clone()), target, Some("hi"), None, None);
+        assert_eq!(Shortened::Created("hi".to_string()), got.unwrap());
+        assert_eq!(2, db.read().unwrap().len());
+
+        // deleted short URLs don't count
+        db.write().unwrap().delete("hello").unwrap();
+        db.write().unwrap().delete("hi").unwrap();
+        let got = create_short_url(web::Data::new(db.clone()), target, None, None, None);
+        assert!(matches!(got, Ok(Shortened::Created(_))), "{:?}", got);
+
+        let db: Db = Db::new(Data::new(HashMap::new()).with_duplicate_targets(true));
+        for _ in 0..2 {
+            let got = create_short_url(web::Data::new(db.clone()), target, None, None, None);
+            assert!(matches!(got, Ok(Shortened::Created(_))), "{:?}", got);
+        }
+        assert_eq!(2, db.read().unwrap().len());
+    }
+
+    #[test]
+    fn test_max_entries() {
+        let db: Db = Db::new(Data::new(HashMap::new()).with_max_entries(Some(1)));
+
+        let got = upsert_short_url(web::Data::new(db.clone()), "https://google.com", "hello");
+        assert_eq!(Upserted::Created, got.unwrap());
+        let got = upsert_short_url(web::Data::new(db.clone()), "https://bing.com", "hello");
+        assert_eq!(Upserted::Updated, got.unwrap());
+
+        let got = upsert_short_url(web::Data::new(db.clone()), "https://bing.com", "hi");
+        assert!(matches!(got, Err(UpsertError::DatabaseFull)), "{:?}", got);
+        let got = create_short_url(
+            web::Data::new(db.clone()),
+            "https://duckduckgo.com",
+            None,
+            None,
+            None,
+        );
+        assert!(matches!(got, Err(UpsertError::DatabaseFull)), "{:?}", got);
+        assert_eq!(1, db.read().unwrap().len());
+    }
+
+    #[test]
+    fn test_upsert_short_url() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let got = upsert_short_url(web::Data::new(db.clone()), "https://google.com", "hello");
+        assert_eq!(Upserted::Created, got.unwrap());
+
+        let got = upsert_short_url(web::Data::new(db.clone()), "https://bing.com", "hello");
+        assert_eq!(Upserted::Updated, got.unwrap());
+
+        let got = upsert_short_url(web::Data::new(db.clone()), "not a URL", "hello");
+        assert!(
+            matches!(got, Err(UpsertError::MalformedUrl(_))),
+            "{:?}",
+            got
+        );
+
+        let db = db.read().unwrap();
+        assert_eq!(Some(&"https://bing.com".to_string()), db.get("hello"));
+    }
+
+    #[test]
+    fn test_update_short_url() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let got = update_short_url(web::Data::new(db.clone()), "https://google.com", "hello");
+        assert!(matches!(got, Err(UpsertError::NotFound)), "{:?}", got);
+        assert_eq!(None, db.read().unwrap().get("hello"));
+
+        db.write()
+            .unwrap()
+            .insert("hello", "https://bing.com")
+            .unwrap();
+        update_short_url(web::Data::new(db.clone()), "https://google.com", "hello").unwrap();
+        assert_eq!(
+            Some(&"https://google.com".to_string()),
+            db.read().unwrap().get("hello")
+        );
+
+        let got = update_short_url(web::Data::new(db), "not a URL", "hello");
+        assert!(
+            matches!(got, Err(UpsertError::MalformedUrl(_))),
+            "{:?}",
+            got
+        );
+    }
+
+    #[test]
+    fn test_reserved_ids() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        assert_eq!(vec!["dist", "metrics", "health", "api"], reserved_ids());
+        for &id in reserved_ids() {
+            let got = create_short_url(
+                web::Data::new(db.clone()),
+                "https://google.com",
+                Some(id),
+                None,
+                None,
+            );
+            assert!(matches!(got, Err(UpsertError::ReservedId)), "{:?}", got);
+
+            let got = upsert_short_url(web::Data::new(db.clone()), "https://google.com", id);
+            assert!(matches!(got, Err(UpsertError::ReservedId)), "{:?}", got);
+        }
+
+        assert!(db.read().unwrap().is_empty());
+
+        // ids are normalised before being checked
+        let db: Db = Db::new(
+            Data::new(HashMap::new())
+                .with_case_insensitive_ids(true)
+                .unwrap(),
+        );
+        for id in reserved_ids() {
+            let id = id.to_uppercase();
+            let got = create_short_url(
+                web::Data::new(db.clone()),
+                "https://google.com",
+                Some(&id),
+                None,
+                None,
+            );
+            assert!(matches!(got, Err(UpsertError::ReservedId)), "{:?}", got);
+
+            let got = upsert_short_url(web::Data::new(db.clone()), "https://google.com", &id);
+            assert!(matches!(got, Err(UpsertError::ReservedId)), "{:?}", got);
+        }
+
+        assert!(db.read().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_upsert_short_url_persists_updates() {
+        use std::env::temp_dir;
+
+        let tmpfile_path = temp_dir().join("test_upsert_persistence.yml");
+        let db: Db = Db::new(Data::new(HashMap::new()).with_persistence(tmpfile_path.clone()));
+
+        upsert_short_url(web::Data::new(db.clone()), "https://google.com", "hello").unwrap();
+        upsert_short_url(web::Data::new(db), "https://bing.com", "hello").unwrap();
+
+        let mut got = String::new();
+        File::open(&tmpfile_path)
+            .unwrap()
+            .read_to_string(&mut got)
+            .unwrap();
+        let got = DataFormat::Yaml.parse(&got).unwrap();
+        assert_eq!("https://bing.com", got["hello"].target);
+        assert!(got["hello"].created_at.is_some());
+    }
+
+    #[test]
+    fn test_read_database() {
+        extern crate serde_yaml;
+        let data = "hello: http://hello-world.com\nkey2: value2";
+
+        let yaml_contents: HashMap<String, String> = serde_yaml::from_str(data).unwrap();
+        println!("{:?}", yaml_contents);
+    }
+}
+
+#[cfg(test)]
+mod integration_tests {
+    use super::*;
+    use actix_web::{body::Body, http::HeaderValue, test};
+
+    // create a new custom shorturl
+    #[actix_rt::test]
+    async fn integration_test_create_custom_shortened_url() {
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload("https://hello.world")
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let db = db.read().unwrap();
+        assert_eq!(db.get("hello"), Some(&"https://hello.world".to_string()));
+        assert_eq!(db.get("wwerwewrew"), None);
+    }
+
+    // create a new random shorturl
+    #[actix_rt::test]
+    async fn integration_test_create_random_shortened_url() {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_payload("https://hello.world")
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_random)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let db = db.read().unwrap();
+        assert_eq!(
+            db.get(&hash("https://hello.world")),
+            Some(&"https://hello.world".to_string())
+        );
+        assert_eq!(db.get("wwerwewrew"), None);
+    }
+
+    // another long URL already uses the hash of the target
+    #[actix_rt::test]
+    async fn integration_test_create_random_hash_collision() {
+        for retry in &[true, false] {
+            let target = "https://hello.world";
+            let mut db: HashMap<String, String> = HashMap::new();
+            db.insert(hash(target), "https://other.world".into());
+            let db: Db = Db::new(Data::new(db).with_hash_collision_retry(*retry));
+
+            let mut app =
+                test::init_service(App::new().data(db.clone()).service(create_random)).await;
+            let req = test::TestRequest::post()
+                .uri("/")
+                .set_payload(target)
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+
+            let db = db.read().unwrap();
+            assert_eq!(
+                db.get(&hash(target)),
+                Some(&"https://other.world".to_string())
+            );
+            if *retry {
+                assert_eq!(resp.status(), StatusCode::OK);
+                let id = hash(&format!("{}1", target));
+                assert_eq!(db.get(&id), Some(&target.to_string()));
+            } else {
+                assert_eq!(resp.status(), StatusCode::CONFLICT);
+                assert_eq!(1, db.len());
+            }
+        }
+    }
+
+    #[test]
+    fn test_random_id() {
+        let target = "https://linkedin.com/in/tsauvajon";
+        let data = Data::new(HashMap::new());
+        assert_eq!(hashed_id(&data, target), random_id(&data, target));
+
+        let data = Data::new(HashMap::new()).with_id_generator(IdGenerator::Nanoid, 21);
+        let id = random_id(&data, target);
+        assert_eq!(21, id.len());
+        assert!(id.chars().all(|c| NANOID_ALPHABET.contains(c)), "{}", id);
+        assert!(is_valid_id(&id), "{}", id);
+        // the same target doesn't give the same id
+        assert_ne!(id, random_id(&data, target));
+
+        let data = data.with_id_alphabet(Some("ab".to_string()));
+        let id = random_id(&data, target);
+        assert!(id.chars().all(|c| c == 'a' || c == 'b'), "{}", id);
+
+        let cli = Cli::parse_from([
+            "goto-api",
+            "--random-id-generator",
+            "nanoid",
+            "--id-length",
+            "8",
+        ]);
+        let db = cli.open_db().unwrap();
+        let data = db.read().unwrap();
+        assert_eq!(8, random_id(&data, target).len());
+        assert!(Cli::try_parse_from(["goto-api", "--id-length", "0"]).is_err());
+        assert!(Cli::try_parse_from(["goto-api", "--random-id-generator", "uuid"]).is_err());
+    }
+
+    #[test]
+    fn test_hashed_id() {
+        let target = "https://hello.world";
+        let mut hashes = vec![hash(target)];
+        hashes
+            .extend((1..MAX_HASH_ATTEMPTS).map(|attempt| hash(&format!("{}{}", target, attempt))));
+
+        let data = Data::new(vec![(hashes[0].clone(), target.to_string())]);
+        assert_eq!(hashes[0], hashed_id(&data, target));
+
+        let data = Data::new(
+            hashes[..3]
+                .iter()
+                .map(|id| (id.clone(), "https://other.world".to_string())),
+        );
+        assert_eq!(hashes[3], hashed_id(&data, target));
+
+        let data = Data::new(
+            hashes
+                .iter()
+                .map(|id| (id.clone(), "https://other.world".to_string())),
+        );
+        let id = hashed_id(&data, target);
+        assert!(!hashes.contains(&id), "{}", id);
+        assert_eq!(RANDOM_URL_SIZE, id.len());
+    }
+
+    // create a new custom shorturl from a form
+    #[actix_rt::test]
+    async fn integration_test_create_from_form() {
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .set_payload("id=hello&target=https%3A%2F%2Fhello.world%2F%3Fa%3Db")
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let db = db.read().unwrap();
+        assert_eq!(
+            db.get("hello"),
+            Some(&"https://hello.world/?a=b".to_string())
+        );
+    }
+
+    // curl -d sends the raw target with a form content type
+    #[actix_rt::test]
+    async fn integration_test_create_from_raw_form() {
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .set_payload("https://hello.world/?a=b&target=c")
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let db = db.read().unwrap();
+        assert_eq!(
+            db.get("hello"),
+            Some(&"https://hello.world/?a=b&target=c".to_string())
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_create_from_form_missing_target() {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .set_payload("id=hello")
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_random)).await;
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        assert_eq!(&Body::from("missing target field"), body);
+        assert!(db.read().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_create_from_json() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .header("Content-Type", "application/json; charset=utf-8")
+            .set_payload(r#"{"target": "https://hello.world/?a=b"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            db.read().unwrap().get("hello"),
+            Some(&"https://hello.world/?a=b".to_string())
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/world")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{"url": "https://hello.world"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(None, db.read().unwrap().get("world"));
+    }
+
+    #[test]
+    fn test_parse_target() {
+        let target = "https://hello.world/?a=b".to_string();
+        assert_eq!(Ok(target.clone()), parse_target(target.clone(), None));
+        assert_eq!(
+            Ok(target.clone()),
+            parse_target(target.clone(), Some("text/plain"))
+        );
+        assert_eq!(
+            Ok(target.clone()),
+            parse_target(
+                r#"{"target": "https://hello.world/?a=b"}"#.to_string(),
+                Some("application/json")
+            )
+        );
+        assert_eq!(
+            Ok(target),
+            parse_target(
+                "target=https%3A%2F%2Fhello.world%2F%3Fa%3Db".to_string(),
+                Some("application/x-www-form-urlencoded")
+            )
+        );
+
+        let err = parse_target(
+            r#"{"url": "https://hello.world"}"#.to_string(),
+            Some("application/json"),
+        )
+        .unwrap_err();
+        assert!(err.contains("missing field `target`"), "{}", err);
+        let err =
+            parse_target("https://hello.world".to_string(), Some("application/json")).unwrap_err();
+        assert!(err.starts_with("invalid request body: "), "{}", err);
+    }
+
+    // a read-only server doesn't register the write routes
+    #[actix_rt::test]
+    async fn integration_test_read_only() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+
+        let db: Db = Db::new(Data::new(db));
+        let read_only = true;
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(browse).configure(
+            |cfg| {
+                if !read_only {
+                    write_routes(cfg);
+                }
+            },
+        ))
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert!(
+            resp.status() == StatusCode::NOT_FOUND
+                || resp.status() == StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(db.read().unwrap().get("hello"), None);
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_log_file() {
+        let dir = std::env::temp_dir().join("test_goto_logs");
+        let _ = std::fs::remove_dir_all(&dir);
+
+        let (writer, guard) =
+            logging::writer(Some(&dir.join("goto.log")), DEFAULT_LOG_KEEP_DAYS).unwrap();
+        let subscriber = logging::subscriber(
+            &LogFormat::Json,
+            tracing_subscriber::EnvFilter::new("info"),
+            writer,
+        );
+        let default = tracing::subscriber::set_default(subscriber);
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db).service(create_with_id)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/logged")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // flush the logs
+        drop(default);
+        drop(guard);
+
+        let files: Vec<_> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        assert_eq!(1, files.len(), "{:?}", files);
+
+        let logs = std::fs::read_to_string(&files[0]).unwrap();
+        assert!(logs.contains("created short url"), "{}", logs);
+        assert!(logs.contains("logged"), "{}", logs);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_max_clicks() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app =
+            test::init_service(App::new().data(db).service(browse).service(create_with_id)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/once?max_clicks=0")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let req = test::TestRequest::post()
+            .uri("/twice?max_clicks=2")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for _ in 0..2 {
+            let req = test::TestRequest::get().uri("/twice").to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::FOUND);
+        }
+
+        let req = test::TestRequest::get().uri("/twice").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_expires_at() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(browse)
+                .service(create_with_id),
+        )
+        .await;
+
+        for (query, message) in &[
+            (
+                "expires_at=2000-01-01T00:00:00Z",
+                "expires_at is in the past",
+            ),
+            (
+                "expires_at=tomorrow",
+                "invalid expires_at: timestamp format is invalid",
+            ),
+        ] {
+            let req = test::TestRequest::post()
+                .uri(&format!("/hello?{}", query))
+                .set_payload("https://hello.world")
+                .to_request();
+            let mut resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let body = resp.take_body();
+            let body = body.as_ref().unwrap();
+            assert_eq!(&Body::from(*message), body);
+        }
+
+        let req = test::TestRequest::post()
+            .uri("/hello?expires_at=2100-01-01T00:00:00Z")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            Some(UNIX_EPOCH + Duration::from_secs(4_102_444_800)),
+            db.read().unwrap().get_entry("hello").unwrap().expires_at
+        );
+
+        let req = test::TestRequest::get().uri("/hello").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        db.write()
+            .unwrap()
+            .update_entry("hello", |entry| entry.expires_at = Some(SystemTime::now()))
+            .unwrap();
+
+        let req = test::TestRequest::get().uri("/hello").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::GONE);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_create_random_shortened_url_bad_body() {
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_payload(vec![0, 159, 146, 150])
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_random)).await;
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from("invalid request body: invalid utf-8 sequence of 1 bytes from index 1"),
+            body
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_create_random_shortened_url_overflow() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let req = test::TestRequest::post()
+            .uri("/toolong")
+            .set_payload(vec![b'a'; db.read().unwrap().max_body_size() + 1])
+            .to_request();
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        assert_eq!(&Body::from("overflow"), body);
+    }
+
+    // follow an existing shorturl
+    #[actix_rt::test]
+    async fn integration_test_use_shortened_url() {
+        let req = test::TestRequest::get().uri("/hi").to_request();
+
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(App::new().data(db).service(browse)).await;
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from("redirecting to https://linkedin.com/in/tsauvajon ..."),
+            body
+        );
+
+        assert_eq!(
+            resp.headers().get("Location"),
+            Some(&HeaderValue::from_str("https://linkedin.com/in/tsauvajon").unwrap())
+        )
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_poisoned_mutex() {
+        use std::panic;
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let _result = panic::catch_unwind(|| {
+            panic::set_hook(Box::new(|_info| {
+                // do nothing
+            }));
+
+            // This thread will acquire the mutex first, unwrapping the result of
+            // `lock` because the lock has not been poisoned.
+            let _guard = db.write().unwrap();
+
+            // This panic while holding the lock (`_guard` is in scope) will poison
+            // the mutex.
+            panic!();
+        });
+
+        let _ = panic::take_hook(); // remove the panic hook that mutes panics
+
+        let mut app = test::init_service(App::new().data(db).service(browse)).await;
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        assert_eq!(
+            &Body::from("poisoned lock: another task failed inside"),
+            body
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_head_shortened_url() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(App::new().data(db).service(exists)).await;
+
+        let req = test::TestRequest::with_uri("/hi")
+            .method(Method::HEAD)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("Location"),
+            Some(&HeaderValue::from_str("https://linkedin.com/in/tsauvajon").unwrap())
+        );
+        let body = test::read_body(resp).await;
+        assert!(body.is_empty());
+
+        let req = test::TestRequest::with_uri("/nope")
+            .method(Method::HEAD)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+        assert_eq!(resp.headers().get("Location"), None);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_health() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(App::new().data(db).service(health).service(browse)).await;
+
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+        let got: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_json::json!({"status": "ok", "version": env!("CARGO_PKG_VERSION"), "entries": 1}),
+            got
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_url_exists() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(url_exists)
+                .service(create_with_id)
+                .service(delete_short_url),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/hi")
+            .set_payload("https://linkedin.com/in/tsauvajon")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for _ in 0..2 {
+            let req = test::TestRequest::get()
+                .uri("/api/url/hi/exists")
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = test::read_body(resp).await;
+            assert_eq!(r#"{"exists":true}"#.as_bytes(), &body[..]);
+        }
+        assert_eq!(0, db.read().unwrap().get_entry("hi").unwrap().clicks);
+
+        let req = test::TestRequest::delete().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        for id in &["hi", "nope"] {
+            let req = test::TestRequest::get()
+                .uri(&format!("/api/url/{}/exists", id))
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+            let body = test::read_body(resp).await;
+            assert_eq!(r#"{"exists":false}"#.as_bytes(), &body[..]);
+        }
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_case_insensitive() {
+        for (case_insensitive, want) in &[(true, StatusCode::FOUND), (false, StatusCode::NOT_FOUND)]
+        {
+            let data = Data::new(HashMap::new())
+                .with_case_insensitive_ids(*case_insensitive)
+                .unwrap();
+            let mut app = test::init_service(
+                App::new()
+                    .data(Db::new(data))
+                    .service(browse)
+                    .service(create_with_id),
+            )
+            .await;
+
+            let req = test::TestRequest::post()
+                .uri("/MyLink")
+                .set_payload("https://linkedin.com/in/tsauvajon")
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let req = test::TestRequest::get().uri("/mylink").to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(
+                resp.status(),
+                *want,
+                "case_insensitive: {}",
+                case_insensitive
+            );
+        }
+    }
+
+    #[test]
+    fn test_validate_server_config() {
+        let valid = Data::new(HashMap::new()).server_config();
+        assert_eq!(Ok(()), valid.validate());
+
+        for (config, want) in vec![
+            (
+                ServerConfig {
+                    max_id_length: 0,
+                    ..Data::new(HashMap::new()).server_config()
+                },
+                "max_id_length must be at least 1",
+            ),
+            (
+                ServerConfig {
+                    max_target_length: 0,
+                    ..Data::new(HashMap::new()).server_config()
+                },
+                "max_target_length must be at least 1",
+            ),
+            (
+                ServerConfig {
+                    id_length: 0,
+                    ..Data::new(HashMap::new()).server_config()
+                },
+                "id_length must be at least 1",
+            ),
+            (
+                ServerConfig {
+                    allowed_schemes: vec![],
+                    ..Data::new(HashMap::new()).server_config()
+                },
+                "allowed_schemes must not be empty",
+            ),
+            (
+                ServerConfig {
+                    allowed_schemes: vec!["https".to_string(), "1http".to_string()],
+                    ..Data::new(HashMap::new()).server_config()
+                },
+                r#"invalid scheme "1http""#,
+            ),
+        ] {
+            assert_eq!(Err(want.to_string()), config.validate());
+        }
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_server_config() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .data(AdminPassword("s3cret".to_string()))
+                .service(get_server_config)
+                .configure(write_routes),
+        )
+        .await;
+
+        let req = as_admin(test::TestRequest::get().uri("/api/config")).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let got: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "allow_private_targets": false,
+                "allowed_schemes": ["http", "https"],
+                "max_id_length": DEFAULT_MAX_ID_LENGTH,
+                "max_target_length": DEFAULT_MAX_TARGET_LENGTH,
+                "max_entries": null,
+                "hard_delete": false,
+                "random_id_generator": "hash",
+                "id_length": RANDOM_URL_SIZE,
+            }),
+            got
+        );
+
+        let ftp = || {
+            test::TestRequest::post()
+                .uri("/files")
+                .set_payload("ftp://files.example.com/file.txt")
+                .to_request()
+        };
+        let resp = test::call_service(&mut app, ftp()).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+        let config = serde_json::json!({
+            "allow_private_targets": false,
+            "allowed_schemes": ["HTTPS", "ftp"],
+            "max_id_length": 10,
+            "max_target_length": 100,
+            "max_entries": 5,
+            "hard_delete": true,
+            "random_id_generator": "nanoid",
+            "id_length": 8,
+        });
+        let mut invalid = config.clone();
+        invalid["max_id_length"] = 0.into();
+        for (content_type, payload, status) in &[
+            (
+                "text/plain",
+                config.to_string(),
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                "application/json",
+                "{}".to_string(),
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "application/json",
+                invalid.to_string(),
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let req = as_admin(test::TestRequest::put().uri("/api/config"))
+                .header("Content-Type", *content_type)
+                .set_payload(payload.clone())
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), *status, "{}", payload);
+        }
+        assert_eq!(
+            Data::new(HashMap::new()).server_config(),
+            db.read().unwrap().server_config()
+        );
+
+        let req = as_admin(test::TestRequest::put().uri("/api/config"))
+            .header("Content-Type", "application/json")
+            .set_payload(config.to_string())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let got: ServerConfig = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            vec!["ftp".to_string(), "https".to_string()],
+            got.allowed_schemes
+        );
+        assert_eq!(Some(5), got.max_entries);
+        assert_eq!(IdGenerator::Nanoid, got.random_id_generator);
+
+        // the new config applies without a restart
+        let resp = test::call_service(&mut app, ftp()).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_compact() {
+        let mut path = std::env::temp_dir();
+        path.push("integration_test_compact.yml");
+        let _ = std::fs::remove_file(&path);
+        let open_db = || {
+            Cli::parse_from(["goto-api", "--database", path.to_str().unwrap()])
+                .open_db()
+                .unwrap()
+        };
+
+        let db = open_db();
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .data(AdminPassword("s3cret".to_string()))
+                .configure(write_routes),
+        )
+        .await;
+        for id in &["one", "two", "three"] {
+            let req = test::TestRequest::post()
+                .uri(&format!("/{}", id))
+                .set_payload(format!("https://linkedin.com/in/{}", id))
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        let req = as_admin(test::TestRequest::delete().uri("/two")).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        // deleted short URLs are kept in the file, so they can be restored
+        assert!(open_db().read().unwrap().contains("two"));
+
+        let req = as_admin(test::TestRequest::post().uri("/api/admin/compact")).to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let got: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(
+            serde_json::json!({
+                "entries_written": 2,
+                "file_size_bytes": std::fs::metadata(&path).unwrap().len(),
+            }),
+            got
+        );
+
+        let db = open_db();
+        let data = db.read().unwrap();
+        assert_eq!(
+            vec!["one", "three"],
+            data.entries()
+                .map(|(id, _)| id.as_str())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_admin_auth() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .data(AdminPassword("s3cret".to_string()))
+                .service(get_server_config)
+                .configure(write_routes),
+        )
+        .await;
+
+        let config = serde_json::to_string(&db.read().unwrap().server_config()).unwrap();
+        let basic = |credentials: &str| format!("Basic {}", base64::encode(credentials));
+        for authorization in &[None, Some(basic("admin:wrong")), Some(basic("root:s3cret"))] {
+            let requests = vec![
+                test::TestRequest::post().uri("/api/admin/compact"),
+                test::TestRequest::post()
+                    .uri("/api/bulk")
+                    .header("Content-Type", "application/json")
+                    .set_payload("[]"),
+                test::TestRequest::get().uri("/api/config"),
+                test::TestRequest::put()
+                    .uri("/api/config")
+                    .header("Content-Type", "application/json")
+                    .set_payload(config.clone()),
+                test::TestRequest::delete().uri("/hi"),
+            ];
+            for req in requests {
+                let req = match authorization {
+                    Some(authorization) => req.header("Authorization", authorization.as_str()),
+                    None => req,
+                };
+                let resp = test::call_service(&mut app, req.to_request()).await;
+                assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+                assert_eq!(
+                    Some(&HeaderValue::from_static(r#"Basic realm="goto""#)),
+                    resp.headers().get("WWW-Authenticate")
+                );
+            }
+        }
+        assert_eq!(
+            Some(&"https://linkedin.com/in/tsauvajon".to_string()),
+            db.read().unwrap().get("hi")
+        );
+
+        let authorization = basic("admin:s3cret");
+        for req in vec![
+            test::TestRequest::post().uri("/api/admin/compact"),
+            test::TestRequest::post()
+                .uri("/api/bulk")
+                .header("Content-Type", "application/json")
+                .set_payload("[]"),
+            test::TestRequest::get().uri("/api/config"),
+            test::TestRequest::put()
+                .uri("/api/config")
+                .header("Content-Type", "application/json")
+                .set_payload(config.clone()),
+            test::TestRequest::delete().uri("/hi"),
+        ] {
+            let req = req.header("Authorization", authorization.as_str());
+            let resp = test::call_service(&mut app, req.to_request()).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+        }
+        assert_eq!(None, db.read().unwrap().get("hi"));
+
+        // only the admin can undo a deletion
+        let restore = || test::TestRequest::post().uri("/hi/restore");
+        let resp = test::call_service(&mut app, restore().to_request()).await;
+        assert_eq!(resp.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(None, db.read().unwrap().get("hi"));
+        let req = restore()
+            .header("Authorization", authorization.as_str())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            Some(&"https://linkedin.com/in/tsauvajon".to_string()),
+            db.read().unwrap().get("hi")
+        );
+
+        // the other routes don't need the password
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_admin_only() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(get_server_config)
+                .configure(write_routes),
+        )
+        .await;
+
+        // without an admin password, nobody can change how the server runs
+        let config = serde_json::to_string(&db.read().unwrap().server_config()).unwrap();
+        for req in vec![
+            test::TestRequest::post().uri("/api/admin/compact"),
+            test::TestRequest::get().uri("/api/config"),
+            test::TestRequest::put()
+                .uri("/api/config")
+                .header("Content-Type", "application/json")
+                .set_payload(config.replace(
+                    r#""allow_private_targets":false"#,
+                    r#""allow_private_targets":true"#,
+                )),
+            as_admin(test::TestRequest::post().uri("/api/admin/compact")),
+        ] {
+            let resp = test::call_service(&mut app, req.to_request()).await;
+            assert_eq!(resp.status(), StatusCode::FORBIDDEN);
+        }
+        assert!(!db.read().unwrap().server_config().allow_private_targets);
+    }
+
+    /// as_admin authenticates a request as `admin`, with the password of the
+    /// apps created with `AdminPassword("s3cret")`.
+    fn as_admin(req: test::TestRequest) -> test::TestRequest {
+        req.header(
+            "Authorization",
+            format!("Basic {}", base64::encode("admin:s3cret")),
+        )
+    }
+
+    #[test]
+    fn test_parse_basic_auth() {
+        assert_eq!(
+            Some(("admin".to_string(), "pass:word".to_string())),
+            parse_basic_auth(&format!("Basic {}", base64::encode("admin:pass:word")))
+        );
+        assert_eq!(None, parse_basic_auth("Bearer abc"));
+        assert_eq!(None, parse_basic_auth("Basic %%%"));
+        assert_eq!(
+            None,
+            parse_basic_auth(&format!("Basic {}", base64::encode("admin")))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_url_stats() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(url_stats)
+                .service(browse),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/api/hi/stats").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            r#"{"target":"https://linkedin.com/in/tsauvajon","clicks":0,"created":null,"last_accessed":null,"expires":null}"#.as_bytes(),
+            &body[..]
+        );
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let req = test::TestRequest::get().uri("/api/hi/stats").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let stats: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(1, stats["clicks"]);
+
+        let last_accessed =
+            humantime::parse_rfc3339(stats["last_accessed"].as_str().unwrap()).unwrap();
+        let elapsed = SystemTime::now().duration_since(last_accessed).unwrap();
+        // timestamps are truncated to the second, so it may look up to a
+        // second older
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+
+        let req = test::TestRequest::get().uri("/api/nope/stats").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_redirect_body() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let cli = Cli::parse_from(["goto-api", "--redirect-body", "Go to: {url} from /{id}"]);
+        let mut app =
+            test::init_service(App::new().data(db).data(cli.redirect_body).service(browse)).await;
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            "Go to: https://linkedin.com/in/tsauvajon from /hi".as_bytes(),
+            &body[..]
+        );
+
+        let cli = Cli::parse_from(["goto-api"]);
+        assert_eq!(RedirectBody::default(), cli.redirect_body);
+
+        let got = Cli::try_parse_from(["goto-api", "--redirect-body", "Go to: {id}"]);
+        assert!(got.is_err());
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_redirect_events() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+        let events = Events::new(DEFAULT_WS_BUFFER);
+        let mut receiver = events.subscribe();
+
+        let mut app =
+            test::init_service(App::new().data(db).data(events.clone()).service(browse)).await;
+
+        for uri in &["/hi", "/nope"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            test::call_service(&mut app, req).await;
+        }
+
+        let event = receiver.try_recv().unwrap();
+        assert_eq!("hi", event.id);
+        assert_eq!("https://linkedin.com/in/tsauvajon", event.target);
+        // not found short URLs aren't published
+        assert!(receiver.try_recv().is_err());
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_preview() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .data(Preview::new(3, DEFAULT_COOKIE_NAME))
+                .service(browse),
+        )
+        .await;
+        let clicks = || db.read().unwrap().get_entry("hi").unwrap().clicks;
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(resp.headers().get("Location"), None);
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            body.contains("This link redirects to https://linkedin.com/in/tsauvajon."),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains(r#"content="3; url=https://linkedin.com/in/tsauvajon""#),
+            "{}",
+            body
+        );
+        // previews aren't clicks
+        assert_eq!(0, clicks());
+
+        let req = test::TestRequest::get().uri("/hi?preview=0").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(
+            resp.headers().get("Location"),
+            Some(&HeaderValue::from_str("https://linkedin.com/in/tsauvajon").unwrap())
+        );
+        assert_eq!(1, clicks());
+
+        let req = test::TestRequest::get().uri("/nope").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_preview_trust_cookie() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+        let preview = Preview::new(3, DEFAULT_COOKIE_NAME);
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .data(preview.clone())
+                .service(browse),
+        )
+        .await;
+        let clicks = || db.read().unwrap().get_entry("hi").unwrap().clicks;
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let body = test::read_body(test::call_service(&mut app, req).await).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(
+            body.contains(r#"<a href="hi?preview=0&amp;trust=1">"#),
+            "{}",
+            body
+        );
+
+        // continuing from the preview page sets the cookie
+        let req = test::TestRequest::get()
+            .uri("/hi?preview=0&trust=1")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let cookie = resp
+            .headers()
+            .get("Set-Cookie")
+            .unwrap()
+            .to_str()
+            .unwrap()
+            .to_string();
+        assert!(cookie.starts_with("goto_trusted="), "{}", cookie);
+        assert!(cookie.contains("; Max-Age=2592000;"), "{}", cookie);
+        // the preview and the redirection count as a single click
+        assert_eq!(1, clicks());
+        let token = cookie["goto_trusted=".len()..].split(';').next().unwrap();
+
+        let req = test::TestRequest::get()
+            .uri("/hi")
+            .header("Cookie", format!("goto_trusted={}", token))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(None, resp.headers().get("Set-Cookie"));
+        assert_eq!(2, clicks());
+
+        // forged or expired cookies don't skip the preview
+        let forged = Preview::new(3, DEFAULT_COOKIE_NAME).trust_token(u64::MAX);
+        let expired = preview.trust_token(1);
+        for token in &[forged, expired, "hello".to_string()] {
+            let req = test::TestRequest::get()
+                .uri("/hi")
+                .header("Cookie", format!("goto_trusted={}", token))
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", token);
+        }
+        assert_eq!(2, clicks());
+    }
+
+    // try to follow a shortened URL that doesn't exist
+    #[actix_rt::test]
+    async fn integration_test_link_miss() {
+        let req = test::TestRequest::get()
+            .uri("/thislinkdoesntexist")
+            .to_request();
+
+        let db: Db = Db::new(Data::new(HashMap::new()));
+
+        let mut app = test::init_service(App::new().data(db).service(browse)).await;
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let body = resp.take_body();
+        let body = body.as_ref().unwrap();
+        assert_eq!(&Body::from("not found"), body);
+
+        assert_eq!(resp.headers().get("Location"), None)
+    }
+
+    // try to follow or create a shortened URL with characters that aren't
+    // allowed in IDs
+    #[actix_rt::test]
+    async fn integration_test_invalid_id() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(browse)
+                .service(create_with_id),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/hello%20world").to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = resp.take_body();
+        assert_eq!(&Body::from("invalid id"), body.as_ref().unwrap());
+
+        let req = test::TestRequest::post()
+            .uri("/hello%20world")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = resp.take_body();
+        assert_eq!(&Body::from("invalid id"), body.as_ref().unwrap());
+        assert!(db.read().unwrap().is_empty());
+
+        let req = test::TestRequest::post()
+            .uri("/my-link_v2")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/my-link_v2").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_base_url() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db)
+                .data(BaseUrl("https://go.company.com/".to_string()))
+                .service(create_random)
+                .service(create_with_id)
+                .service(patch_with_id),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/tsauvajon")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let body = test::read_body(test::call_service(&mut app, req).await).await;
+        assert_eq!(
+            "/tsauvajon now redirects to https://github.com/tsauvajon (short URL: https://go.company.com/tsauvajon)",
+            body
+        );
+
+        let req = test::TestRequest::patch()
+            .uri("/tsauvajon")
+            .set_payload("https://linkedin.com/in/tsauvajon")
+            .to_request();
+        let body = test::read_body(test::call_service(&mut app, req).await).await;
+        assert_eq!(
+            "/tsauvajon now redirects to https://linkedin.com/in/tsauvajon (short URL: https://go.company.com/tsauvajon)",
+            body
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_payload("https://github.com/tsauvajon/goto")
+            .to_request();
+        let body = test::read_body(test::call_service(&mut app, req).await).await;
+        let id = hash("https://github.com/tsauvajon/goto");
+        assert_eq!(
+            format!(
+                "/{} now redirects to https://github.com/tsauvajon/goto (short URL: https://go.company.com/{})",
+                id, id
+            ),
+            body
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_browse_links() {
+        let db: Db = Db::new(Data::new(vec![(
+            "hi".to_string(),
+            "https://linkedin.com/in/tsauvajon".to_string(),
+        )]));
+
+        for (base_url, want) in vec![
+            (None, r#"</api/hi/stats>; rel="stats", </hi/qr>; rel="qr""#),
+            (
+                Some(BaseUrl("https://go.company.com".to_string())),
+                r#"<https://go.company.com/api/hi/stats>; rel="stats", <https://go.company.com/hi/qr>; rel="qr""#,
+            ),
+        ] {
+            let mut app = test::init_service(
+                App::new()
+                    .data(db.clone())
+                    .configure(|cfg| {
+                        if let Some(base_url) = &base_url {
+                            cfg.data(base_url.clone());
+                        }
+                    })
+                    .service(browse),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri("/hi").to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::FOUND);
+            assert_eq!(
+                Some(&HeaderValue::from_static(want)),
+                resp.headers().get("Link")
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_id_too_long() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(browse)
+                .service(create_with_id),
+        )
+        .await;
+
+        let id = "a".repeat(DEFAULT_MAX_ID_LENGTH + 1);
+        let req = test::TestRequest::post()
+            .uri(&format!("/{}", id))
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = resp.take_body();
+        assert_eq!(&Body::from("id too long"), body.as_ref().unwrap());
+        assert!(db.read().unwrap().is_empty());
+
+        let req = test::TestRequest::get()
+            .uri(&format!("/{}", id))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_target_too_long() {
+        let db: Db = Db::new(Data::new(HashMap::new()).with_limits(DEFAULT_MAX_ID_LENGTH, 30));
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
+
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload("https://github.com/tsauvajon/goto")
+            .to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        let body = resp.take_body();
+        assert_eq!(&Body::from("target URL too long"), body.as_ref().unwrap());
+        assert!(db.read().unwrap().is_empty());
+    }
+
+    // the payload limits follow the maximum target length
+    #[actix_rt::test]
+    async fn integration_test_target_max_length() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(shorten)
+                .service(create_random)
+                .service(create_with_id)
+                .service(patch_with_id),
+        )
+        .await;
 
-    #[test]
-    fn test_hash() {
-        assert_eq!("4cca4", hash("something"));
-        assert_eq!("284a1", hash("something else"));
+        let target = |length: usize| {
+            let prefix = "https://github.com/";
+            format!("{}{}", prefix, "a".repeat(length - prefix.len()))
+        };
+
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload(target(DEFAULT_MAX_TARGET_LENGTH))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::patch()
+            .uri("/hello")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .set_payload(format!(
+                "target={}",
+                url::form_urlencoded::byte_serialize(target(DEFAULT_MAX_TARGET_LENGTH).as_bytes())
+                    .collect::<String>()
+            ))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .header("Content-Type", "application/json")
+            .set_payload(
+                serde_json::json!({
+                    "id": "hi",
+                    "target": target(DEFAULT_MAX_TARGET_LENGTH),
+                    "expires_at": "2100-01-01T00:00:00Z",
+                })
+                .to_string(),
+            )
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        for uri in &["/", "/toolong"] {
+            let req = test::TestRequest::post()
+                .uri(uri)
+                .set_payload(target(DEFAULT_MAX_TARGET_LENGTH + 1))
+                .to_request();
+            let mut resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+            let body = resp.take_body();
+            assert_eq!(&Body::from("target URL too long"), body.as_ref().unwrap());
+        }
+
+        assert_eq!(2, db.read().unwrap().len());
     }
 
-    #[test]
-    fn test_create_short_malformed_url() {
+    #[actix_rt::test]
+    async fn integration_test_bulk_import_target_too_long() {
+        let target = format!("https://github.com/{}", "a".repeat(3000));
+        let req = test::TestRequest::post()
+            .uri("/api/bulk")
+            .header("Content-Type", "application/json")
+            .set_payload(format!(r#"[{{"id": "long", "target": "{}"}}]"#, target))
+            .to_request();
+
         let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db.clone()).service(bulk_import)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
 
-        let target = "this is not a valid URL".to_string();
-        let id = Some("hello");
+        let body = test::read_body(resp).await;
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
         assert_eq!(
-            Err("malformed URL: relative URL without a base".to_string()),
-            create_short_url(web::Data::new(db), &target, id)
+            serde_json::json!([{"id": "long", "reason": "target URL too long"}]),
+            report["failed"]
         );
+        assert!(db.read().unwrap().is_empty());
     }
 
-    #[test]
-    fn test_create_short_url() {
-        let db: Db = Db::new(Data::new(HashMap::new()));
+    #[actix_rt::test]
+    async fn integration_test_metrics() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+        let metrics = Metrics::new();
+
+        let mut app = test::init_service(
+            App::new()
+                .wrap(Instrument::new(metrics.clone()))
+                .data(db)
+                .data(metrics)
+                .service(metrics::expose)
+                .service(browse)
+                .service(create_with_id),
+        )
+        .await;
 
-        let target = "https://google.com".to_string();
-        let id = "hello";
-        create_short_url(web::Data::new(db.clone()), &target, Some(id)).unwrap();
+        for _ in 0..3 {
+            let req = test::TestRequest::get().uri("/hi").to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::FOUND);
+        }
+
+        let req = test::TestRequest::get().uri("/nope").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let req = test::TestRequest::get().uri("/metrics").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+        let body = String::from_utf8(body.to_vec()).unwrap();
+
+        assert!(
+            body.contains("goto_requests_total{method=\"GET\",status=\"302\"} 3"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("goto_requests_total{method=\"GET\",status=\"404\"} 1"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("goto_requests_total{method=\"POST\",status=\"200\"} 1"),
+            "{}",
+            body
+        );
+        assert!(
+            body.contains("goto_request_duration_seconds_count{handler=\"/{id}\"} 5"),
+            "{}",
+            body
+        );
+        assert!(body.contains("goto_db_entries_total 2"), "{}", body);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_qr_code() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(App::new().data(db).service(qr_code)).await;
+
+        let req = test::TestRequest::get()
+            .uri("/hi/qr")
+            .header("Host", "go.to")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("Content-Type"),
+            Some(&HeaderValue::from_static("image/png"))
+        );
+        let body = test::read_body(resp).await;
+        assert_eq!(b"\x89PNG", &body[..4]);
+
+        let req = test::TestRequest::get().uri("/nope/qr").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        let req = test::TestRequest::get()
+            .uri("/hi/qr?size=2048")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    async fn preflight(origins: &[String], origin: &str) -> ServiceResponse {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cors(origins))
+                .wrap_fn(no_content_preflight)
+                .service(create_with_id),
+        )
+        .await;
+
+        let req = test::TestRequest::with_uri("/hello")
+            .method(Method::OPTIONS)
+            .header("Origin", origin)
+            .header("Access-Control-Request-Method", "POST")
+            .to_request();
+        test::call_service(&mut app, req).await
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_cors_disabled() {
+        let resp = preflight(&[], "https://example.com").await;
+        assert_eq!(resp.headers().get("Access-Control-Allow-Origin"), None);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_cors_allowed_origin() {
+        let origins = vec![
+            "https://example.com".to_string(),
+            "https://other.example.com".to_string(),
+        ];
+
+        let resp = preflight(&origins, "https://other.example.com").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            resp.headers().get("Access-Control-Allow-Origin"),
+            Some(&HeaderValue::from_static("https://other.example.com"))
+        );
+
+        let resp = preflight(&origins, "https://evil.com").await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.headers().get("Access-Control-Allow-Origin"), None);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_cors_wildcard() {
+        let resp = preflight(&["*".to_string()], "https://example.com").await;
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            resp.headers().get("Access-Control-Allow-Origin"),
+            Some(&HeaderValue::from_static("*"))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_cors_simple_request() {
+        let mut app = test::init_service(
+            App::new()
+                .wrap(cors(&["https://example.com".to_string()]))
+                .data(Db::new(Data::new(HashMap::new())))
+                .service(create_with_id),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .header("Origin", "https://example.com")
+            .set_payload("https://hello.world")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            resp.headers().get("Access-Control-Allow-Origin"),
+            Some(&HeaderValue::from_static("https://example.com"))
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_bulk_import() {
+        // CountingBackend counts how many times the short URLs are written
+        struct CountingBackend(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+        impl StorageBackend for CountingBackend {
+            fn load(&self) -> std::io::Result<IndexMap<String, Entry>> {
+                Ok(IndexMap::new())
+            }
+
+            fn save(&self, _data: &IndexMap<String, Entry>) -> std::io::Result<()> {
+                self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                Ok(())
+            }
+        }
+
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("existing".into(), "https://github.com/tsauvajon".into());
+        let saves = std::sync::Arc::new(std::sync::atomic::AtomicUsize::new(0));
+        let db: Db = Db::new(Data::new(db).with_storage(CountingBackend(saves.clone())));
+
+        let mut entries: Vec<String> = (0..50)
+            .map(|i| {
+                format!(
+                    r#"{{"id": "id{}", "target": "https://target{}.com"}}"#,
+                    i, i
+                )
+            })
+            .collect();
+        entries.push(r#"{"id": "existing", "target": "https://hello.world"}"#.to_string());
+        entries.push(r#"{"id": "invalid", "target": "not a URL"}"#.to_string());
+
+        let req = test::TestRequest::post()
+            .uri("/api/bulk")
+            .header("Content-Type", "application/json")
+            .set_payload(format!("[{}]", entries.join(",")))
+            .to_request();
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(bulk_import)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let body = test::read_body(resp).await;
+        let report: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(50, report["created"].as_array().unwrap().len());
+        assert_eq!(serde_json::json!(["existing"]), report["updated"]);
+        assert_eq!(
+            serde_json::json!([{"id": "invalid", "reason": "malformed URL: relative URL without a base"}]),
+            report["failed"]
+        );
 
         let db = db.read().unwrap();
-        let got = db.get(&id).unwrap();
-        assert_eq!(&target, got);
+        for i in 0..50 {
+            assert_eq!(
+                Some(&format!("https://target{}.com", i)),
+                db.get(&format!("id{}", i))
+            );
+        }
+        assert_eq!(Some(&"https://hello.world".to_string()), db.get("existing"));
+        assert_eq!(None, db.get("invalid"));
+
+        // the whole import is written at once
+        assert_eq!(1, saves.load(std::sync::atomic::Ordering::SeqCst));
     }
 
-    #[test]
-    fn test_create_short_url_hashed_id() {
+    #[actix_rt::test]
+    async fn integration_test_bulk_import_too_many_entries() {
+        let entries: Vec<String> = (0..MAX_BULK_ENTRIES + 1)
+            .map(|i| format!(r#"{{"id":"{}","target":"http://a.b"}}"#, i))
+            .collect();
+
+        let req = test::TestRequest::post()
+            .uri("/api/bulk")
+            .header("Content-Type", "application/json")
+            .set_payload(format!("[{}]", entries.join(",")))
+            .to_request();
+
         let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db.clone()).service(bulk_import)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert!(db.read().unwrap().is_empty());
+    }
 
-        let target = "https://google.com";
-        create_short_url(web::Data::new(db.clone()), target, None).unwrap();
+    #[actix_rt::test]
+    async fn integration_test_bulk_import_not_json() {
+        let req = test::TestRequest::post()
+            .uri("/api/bulk")
+            .set_payload("hello")
+            .to_request();
 
-        let id = hash(target);
-        let db = db.read().unwrap();
-        let got = db.get(&id).unwrap();
-        assert_eq!(&target, got);
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db).service(bulk_import)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_shorten() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .data(BaseUrl("https://go.company.com/".to_string()))
+                .service(shorten),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{"id": "hello", "target": "https://hello.world", "max_clicks": 2}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            ShortenResponse {
+                id: "hello".to_string(),
+                short_url: "https://go.company.com/hello".to_string(),
+                target: "https://hello.world".to_string(),
+            },
+            serde_json::from_slice(&body).unwrap()
+        );
+        assert_eq!(
+            Some(2),
+            db.read().unwrap().get_entry("hello").unwrap().max_clicks
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{"target": "https://random.world"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body = test::read_body(resp).await;
+        let got: ShortenResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!(hash("https://random.world"), got.id);
+        assert_eq!(
+            Some(&"https://random.world".to_string()),
+            db.read().unwrap().get(&got.id)
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{"id": "hello", "target": "https://other.world"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_shorten_invalid() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db.clone()).service(shorten)).await;
+
+        for (content_type, payload, status) in &[
+            (
+                "text/plain",
+                r#"{"target": "https://hello.world"}"#,
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ),
+            (
+                "application/json",
+                "https://hello.world",
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "application/json",
+                r#"{"id": "hello"}"#,
+                StatusCode::BAD_REQUEST,
+            ),
+            (
+                "application/json",
+                r#"{"target": "https://hello.world", "expires_at": "2000-01-01T00:00:00Z"}"#,
+                StatusCode::BAD_REQUEST,
+            ),
+        ] {
+            let req = test::TestRequest::post()
+                .uri("/api/shorten")
+                .header("Content-Type", *content_type)
+                .set_payload(*payload)
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), *status, "{}", payload);
+        }
+        assert!(db.read().unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_max_entries() {
+        let db: Db = Db::new(Data::new(HashMap::new()).with_max_entries(Some(2)));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(create_random)
+                .service(create_with_id),
+        )
+        .await;
+
+        for (uri, target, status) in &[
+            ("/hello", "https://google.com", StatusCode::OK),
+            ("/", "https://bing.com", StatusCode::OK),
+            (
+                "/world",
+                "https://duckduckgo.com",
+                StatusCode::INSUFFICIENT_STORAGE,
+            ),
+            (
+                "/",
+                "https://duckduckgo.com",
+                StatusCode::INSUFFICIENT_STORAGE,
+            ),
+        ] {
+            let req = test::TestRequest::post()
+                .uri(uri)
+                .set_payload(target.to_string())
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), *status, "{} {}", uri, target);
+        }
+        assert_eq!(2, db.read().unwrap().len());
     }
 
     #[test]
-    fn test_create_short_url_already_exists() {
-        let id = "hello";
+    fn test_parse_prefix() {
+        assert_eq!(Ok("/goto".to_string()), parse_prefix("/goto"));
+        assert_eq!(Ok("/tools/goto".to_string()), parse_prefix("/tools/goto"));
+        for invalid in &["goto", "/goto/", "/", ""] {
+            assert_eq!(
+                Err("the prefix must start with '/' and not end with '/'".to_string()),
+                parse_prefix(invalid),
+                "{}",
+                invalid
+            );
+        }
+        assert!(parse_prefix("/go\"><script>").is_err());
+
+        let cli = Cli::parse_from(["goto-api", "--prefix", "/goto"]);
+        assert_eq!(Some("/goto".to_string()), cli.prefix);
+        assert!(Cli::try_parse_from(["goto-api", "--prefix", "goto"]).is_err());
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_prefix() {
+        let dir = std::env::temp_dir().join("integration_test_prefix");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("index.html"),
+            "<!doctype html>\n<html>\n<head>\n    <title>Go To</title>\n</head>\n</html>\n",
+        )
+        .unwrap();
+        let front_dist_directory = dir.to_str().unwrap().to_string();
 
         let mut db: HashMap<String, String> = HashMap::new();
-        db.insert(id.into(), "some existing value".into());
-        let db: Db = Db::new(Data::new(db.into()));
+        db.insert(
+            "tsauvajon".into(),
+            "https://linkedin.com/in/tsauvajon".into(),
+        );
+        let db: Db = Db::new(Data::new(db));
+        let mut app = test::init_service(
+            App::new()
+                .data(db)
+                .data(FrontIndex {
+                    path: dir.join("index.html"),
+                    api_base: "/goto".to_string(),
+                })
+                .data(Prefix("/goto".to_string()))
+                .service(
+                    web::scope("/goto").configure(|cfg| routes(cfg, &front_dist_directory, false)),
+                ),
+        )
+        .await;
 
-        let target = "https://google.com";
+        let req = test::TestRequest::get().uri("/goto/tsauvajon").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        assert_eq!(
+            resp.headers().get(header::LOCATION).unwrap(),
+            "https://linkedin.com/in/tsauvajon"
+        );
+        assert_eq!(
+            resp.headers().get(header::LINK).unwrap(),
+            r#"</goto/api/tsauvajon/stats>; rel="stats", </goto/tsauvajon/qr>; rel="qr""#
+        );
+
+        let req = test::TestRequest::get().uri("/tsauvajon").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+
+        // the front-end is told where the API is
+        for uri in &["/goto", "/goto/"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+            let body = test::read_body(resp).await;
+            let body = String::from_utf8(body.to_vec()).unwrap();
+            assert!(
+                body.contains(
+                    "<head>\n    <meta name=\"api-base\" content=\"/goto\">\n    <title>"
+                ),
+                "{}",
+                body
+            );
+        }
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_security_headers() {
+        let dir = std::env::temp_dir().join("integration_test_security_headers");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("index.html"), "<!doctype html>").unwrap();
+        std::fs::write(dir.join("main.js"), "init()").unwrap();
+
+        for enabled in &[true, false] {
+            let enabled = *enabled;
+            let db: Db = Db::new(Data::new(HashMap::new()));
+            let mut app = test::init_service(
+                App::new()
+                    .wrap_fn(move |req, srv| security_headers(req, srv, enabled))
+                    .data(db)
+                    .service(Files::new("/dist", &dir))
+                    .service(health),
+            )
+            .await;
+
+            for (uri, html) in &[
+                ("/dist/index.html", true),
+                ("/dist/main.js", false),
+                ("/health", false),
+            ] {
+                let req = test::TestRequest::get().uri(uri).to_request();
+                let resp = test::call_service(&mut app, req).await;
+                assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+
+                let header = |name| {
+                    resp.headers()
+                        .get(name)
+                        .map(|value| value.to_str().unwrap())
+                };
+                assert_eq!(
+                    if enabled { Some("nosniff") } else { None },
+                    header("x-content-type-options"),
+                    "{}",
+                    uri
+                );
+                for &(name, value) in HTML_SECURITY_HEADERS {
+                    let want = if enabled && *html { Some(value) } else { None };
+                    assert_eq!(want, header(name), "{} {}", uri, name);
+                }
+            }
+        }
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_server_timing() {
+        let db: Db = Db::new(Data::from_entries(vec![(
+            "hi".to_string(),
+            Entry::from("https://linkedin.com/in/tsauvajon".to_string()),
+        )]));
+        let mut app = test::init_service(
+            App::new()
+                .wrap_fn(server_timing)
+                .data(db)
+                .service(browse)
+                .service(health),
+        )
+        .await;
+
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+        let value = resp
+            .headers()
+            .get(SERVER_TIMING_HEADER)
+            .expect("server timing")
+            .to_str()
+            .unwrap();
+        let names: Vec<&str> = value
+            .split(", ")
+            .map(|metric| {
+                let (name, dur) = metric.split_once(";dur=").expect(value);
+                let dur: f64 = dur.parse().expect(value);
+                assert!(dur >= 0., "{}", value);
+                name
+            })
+            .collect();
+        assert_eq!(vec!["db-lookup", "redirect"], names);
+
+        // handlers which record nothing send no header
+        let req = test::TestRequest::get().uri("/health").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(None, resp.headers().get(SERVER_TIMING_HEADER));
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_request_id() {
+        let db: Db = Db::new(Data::from_entries(vec![(
+            "hi".to_string(),
+            Entry::from("https://linkedin.com/in/tsauvajon".to_string()),
+        )]));
+        let mut app =
+            test::init_service(App::new().wrap_fn(request_id).data(db).service(browse)).await;
+
+        // the request id of the load balancer is sent back
+        let req = test::TestRequest::get()
+            .uri("/hi")
+            .header(REQUEST_ID_HEADER, "abc-123")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
         assert_eq!(
-            Err("already registered".to_string()),
-            create_short_url(web::Data::new(db), target, Some(id))
+            Some(&HeaderValue::from_static("abc-123")),
+            resp.headers().get(REQUEST_ID_HEADER)
+        );
+        assert_eq!(
+            Some(RequestId("abc-123".to_string())),
+            resp.request().extensions().get::<RequestId>().cloned()
         );
-    }
-
-    #[test]
-    fn test_read_database() {
-        extern crate serde_yaml;
-        let data = "hello: http://hello-world.com\nkey2: value2";
 
-        let yaml_contents: HashMap<String, String> = serde_yaml::from_str(&data).unwrap();
-        println!("{:?}", yaml_contents);
+        // or one is generated
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let generated = resp.headers().get(REQUEST_ID_HEADER).unwrap();
+        assert_eq!(36, generated.len());
+
+        for invalid in &[
+            "abc_123",
+            "abc 123",
+            "a".repeat(MAX_REQUEST_ID_LENGTH + 1).as_str(),
+        ] {
+            let req = test::TestRequest::get()
+                .uri("/hi")
+                .header(REQUEST_ID_HEADER, *invalid)
+                .to_request();
+            let err = app.call(req).await.err().expect(invalid);
+            assert_eq!(
+                StatusCode::BAD_REQUEST,
+                err.as_response_error().status_code()
+            );
+        }
     }
 
     #[test]
-    // We write new database (= file) lines one at a time, and serde_yaml
-    // to_string method doesn't help for two reasons:
-    //   - we don't need the error handling
-    //   - we don't want the `---\n` prefix
-    //
-    // On the other hand, if we wanted to write the entire database every
-    // time, it would work well.
-    fn test_write_database() {
-        extern crate serde_yaml;
-        let mut database: HashMap<String, String> = HashMap::new();
-        database.insert(
-            "tsauvajon".to_string(),
-            "https://linkedin.com/in/tsauvajon".to_string(),
+    fn test_parse_request_id() {
+        let parse = |id: &str| parse_request_id(&HeaderValue::from_str(id).unwrap());
+        assert_eq!(Ok(RequestId("abc-123".to_string())), parse("abc-123"));
+        assert!(parse(&"a".repeat(MAX_REQUEST_ID_LENGTH)).is_ok());
+        assert_eq!(
+            Err("X-Request-ID must be 1 to 128 characters long".to_string()),
+            parse(&"a".repeat(MAX_REQUEST_ID_LENGTH + 1))
         );
-        let want = serde_yaml::to_string(&database).unwrap();
-        let want = want.trim_start_matches("---\n").to_string();
-
-        let got = serialise_entry(
-            "tsauvajon".to_string(),
-            "https://linkedin.com/in/tsauvajon".to_string(),
+        assert_eq!(
+            Err("X-Request-ID must only contain letters, digits and hyphens".to_string()),
+            parse("abc/123")
         );
-
-        assert_eq!(want, got)
     }
-}
-
-#[cfg(test)]
-mod integration_tests {
-    use super::*;
-    use actix_web::{
-        body::Body,
-        http::{HeaderValue, StatusCode},
-        test,
-    };
 
-    // create a new custom shorturl
     #[actix_rt::test]
-    async fn integration_test_create_custom_shortened_url() {
-        let req = test::TestRequest::post()
-            .uri("/hello")
-            .set_payload("https://hello.world")
-            .to_request();
+    async fn integration_test_capabilities() {
+        for (read_only, want) in &[(false, Some("shorten")), (true, None)] {
+            let db: Db = Db::new(Data::new(HashMap::new()));
+            let mut app = test::init_service(
+                App::new()
+                    .wrap(capabilities(*read_only))
+                    .data(db)
+                    .service(browse),
+            )
+            .await;
+
+            let req = test::TestRequest::get().uri("/hi").to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(
+                resp.headers()
+                    .get(CAPABILITIES_HEADER)
+                    .map(|value| value.to_str().unwrap()),
+                *want
+            );
+        }
+    }
 
+    #[actix_rt::test]
+    async fn integration_test_disallowed_scheme() {
         let db: Db = Db::new(Data::new(HashMap::new()));
-
         let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
-        let resp = test::call_service(&mut app, req).await;
-        assert_eq!(resp.status(), StatusCode::OK);
 
-        let db = db.read().unwrap();
-        assert_eq!(db.get("hello"), Some(&"https://hello.world".to_string()));
-        assert_eq!(db.get("wwerwewrew"), None);
-    }
+        for target in &["javascript:alert(1)", "ftp://files.example.com/file.txt"] {
+            let req = test::TestRequest::post()
+                .uri("/hello")
+                .set_payload(target.to_string())
+                .to_request();
+            let mut resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+
+            let body = resp.take_body();
+            let body = body.as_ref().unwrap();
+            assert_eq!(&Body::from("target URL scheme not allowed"), body);
+        }
+        assert!(db.read().unwrap().is_empty());
 
-    // create a new random shorturl
-    #[actix_rt::test]
-    async fn integration_test_create_random_shortened_url() {
+        let db: Db = Db::new(
+            Data::new(HashMap::new())
+                .with_allowed_schemes(&["http".to_string(), "FTP".to_string()]),
+        );
+        let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
         let req = test::TestRequest::post()
-            .uri("/")
-            .set_payload("https://hello.world")
+            .uri("/hello")
+            .set_payload("ftp://files.example.com/file.txt")
             .to_request();
-
-        let db: Db = Db::new(Data::new(HashMap::new()));
-
-        let mut app = test::init_service(App::new().data(db.clone()).service(create_random)).await;
         let resp = test::call_service(&mut app, req).await;
         assert_eq!(resp.status(), StatusCode::OK);
-
-        let db = db.read().unwrap();
         assert_eq!(
-            db.get(&hash("https://hello.world")),
-            Some(&"https://hello.world".to_string())
+            Some(&"ftp://files.example.com/file.txt".to_string()),
+            db.read().unwrap().get("hello")
         );
-        assert_eq!(db.get("wwerwewrew"), None);
     }
 
+    // each of the ROUTES registers a resource with its path
     #[actix_rt::test]
-    async fn integration_test_create_random_shortened_url_bad_body() {
-        let req = test::TestRequest::post()
-            .uri("/")
-            .set_payload(vec![0, 159, 146, 150])
-            .to_request();
-
-        let db: Db = Db::new(Data::new(HashMap::new()));
-
-        let mut app = test::init_service(App::new().data(db.clone()).service(create_random)).await;
-        let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    async fn integration_test_routes() {
+        async fn pattern(req: HttpRequest) -> HttpResponse {
+            let path = format!("/{}", req.match_info().query("path"));
+            HttpResponse::Ok().json(req.resource_map().match_pattern(&path))
+        }
 
-        let body = resp.take_body();
-        let body = body.as_ref().unwrap();
-        assert_eq!(
-            &Body::from("invalid request body: invalid utf-8 sequence of 1 bytes from index 1"),
-            body
-        );
+        let front_dist_directory = std::env::temp_dir().to_str().unwrap().to_string();
+        for route in ROUTES {
+            let mut app = test::init_service(
+                App::new()
+                    .route("/pattern/{path:.*}", web::get().to(pattern))
+                    .configure(|cfg| (route.register)(cfg, &front_dist_directory)),
+            )
+            .await;
+
+            let path = route.path.trim_start_matches('/').replace("{id}", "hi");
+            let uri = format!("/pattern/{}", path);
+            let req = test::TestRequest::get().uri(&uri).to_request();
+            let got: Option<String> = test::read_response_json(&mut app, req).await;
+            assert_eq!(Some(route.path.to_string()), got);
+        }
     }
 
     #[actix_rt::test]
-    async fn integration_test_create_random_shortened_url_overflow() {
+    async fn integration_test_reserved_id() {
         let req = test::TestRequest::post()
-            .uri("/toolong")
-            .set_payload(vec![b'a'; 2000])
+            .uri("/health")
+            .set_payload("https://hello.world")
             .to_request();
 
         let db: Db = Db::new(Data::new(HashMap::new()));
-
         let mut app = test::init_service(App::new().data(db.clone()).service(create_with_id)).await;
         let mut resp = test::call_service(&mut app, req).await;
         assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 
         let body = resp.take_body();
         let body = body.as_ref().unwrap();
-        assert_eq!(&Body::from("overflow"), body);
+        assert_eq!(&Body::from("reserved id"), body);
+        assert_eq!(db.read().unwrap().get("health"), None);
     }
 
-    // follow an existing shorturl
     #[actix_rt::test]
-    async fn integration_test_use_shortened_url() {
-        let req = test::TestRequest::get().uri("/hi").to_request();
+    async fn integration_test_delete_shortened_url() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app =
+            test::init_service(App::new().data(db.clone()).service(delete_short_url)).await;
+
+        let req = test::TestRequest::delete().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(db.read().unwrap().get("hi"), None);
+
+        let req = test::TestRequest::delete().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
 
+    #[actix_rt::test]
+    async fn integration_test_restore_shortened_url() {
         let mut db: HashMap<String, String> = HashMap::new();
         db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(
+            App::new()
+                .data(db)
+                .service(browse)
+                .service(exists)
+                .service(restore_short_url)
+                .service(delete_short_url),
+        )
+        .await;
 
-        let db: Db = Db::new(Data::new(db.into()));
+        let req = test::TestRequest::delete().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
 
-        let mut app = test::init_service(App::new().data(db).service(browse)).await;
-        let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(resp.status(), StatusCode::FOUND);
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::GONE);
+
+        let req = test::TestRequest::default()
+            .method(Method::HEAD)
+            .uri("/hi")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::GONE);
 
+        let req = test::TestRequest::post().uri("/hi/restore").to_request();
+        let mut resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
         let body = resp.take_body();
         let body = body.as_ref().unwrap();
-        assert_eq!(
-            &Body::from("redirecting to https://linkedin.com/in/tsauvajon ..."),
-            body
-        );
+        assert_eq!(&Body::from("/hi restored"), body);
 
-        assert_eq!(
-            resp.headers().get("Location"),
-            Some(&HeaderValue::from_str("https://linkedin.com/in/tsauvajon").unwrap())
-        )
+        let req = test::TestRequest::get().uri("/hi").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::FOUND);
+
+        let req = test::TestRequest::post().uri("/hello/restore").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
     }
 
     #[actix_rt::test]
-    async fn integration_test_poisoned_mutex() {
-        use std::panic;
+    async fn integration_test_list_urls() {
+        let db: Db = Db::new(Data::new(vec![
+            ("hi".into(), "https://linkedin.com/in/tsauvajon".into()),
+            ("gh".into(), "https://github.com/tsauvajon".into()),
+        ]));
+        db.write().unwrap().click("hi").unwrap();
+
+        let mut app = test::init_service(App::new().data(db).service(list_urls)).await;
+        let req = test::TestRequest::get().uri("/api/urls").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
 
-        let req = test::TestRequest::get().uri("/hi").to_request();
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            r#"{"items":[{"id":"hi","target":"https://linkedin.com/in/tsauvajon","clicks":1},{"id":"gh","target":"https://github.com/tsauvajon"}],"next_cursor":null,"total":2}"#,
+            String::from_utf8(body.to_vec()).unwrap()
+        );
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_list_urls_etag() {
         let mut db: HashMap<String, String> = HashMap::new();
         db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
-        let db: Db = Db::new(Data::new(db.into()));
+        let db: Db = Db::new(Data::new(db));
 
-        let _result = panic::catch_unwind(|| {
-            panic::set_hook(Box::new(|_info| {
-                // do nothing
-            }));
+        let mut app = test::init_service(App::new().data(db.clone()).service(list_urls)).await;
+        let req = test::TestRequest::get().uri("/api/urls").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let etag = resp.headers().get("ETag").unwrap().clone();
+
+        for if_none_match in &[
+            etag.to_str().unwrap().to_string(),
+            format!("W/{}, \"other\"", etag.to_str().unwrap()),
+            "*".to_string(),
+        ] {
+            let req = test::TestRequest::get()
+                .uri("/api/urls")
+                .header("If-None-Match", if_none_match.as_str())
+                .to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::NOT_MODIFIED, "{}", if_none_match);
+            assert_eq!(Some(&etag), resp.headers().get("ETag"));
+            let body = test::read_body(resp).await;
+            assert!(body.is_empty());
+        }
 
-            // This thread will acquire the mutex first, unwrapping the result of
-            // `lock` because the lock has not been poisoned.
-            let _guard = db.write().unwrap();
+        // the list changed
+        db.write().unwrap().click("hi").unwrap();
+        let req = test::TestRequest::get()
+            .uri("/api/urls")
+            .header("If-None-Match", etag.clone())
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_ne!(Some(&etag), resp.headers().get("ETag"));
+    }
 
-            // This panic while holding the lock (`_guard` is in scope) will poison
-            // the mutex.
-            panic!();
-        });
+    #[actix_rt::test]
+    async fn integration_test_search_urls() {
+        let db: Db = Db::new(Data::new(vec![
+            ("hi".into(), "https://linkedin.com/in/tsauvajon".into()),
+            ("gh".into(), "https://github.com/tsauvajon".into()),
+            ("gone".into(), "https://github.com/gone".into()),
+        ]));
+        db.write().unwrap().delete("gone").unwrap();
+
+        let mut app = test::init_service(App::new().data(db).service(search_urls)).await;
+        for (uri, want) in &[
+            (
+                "/api/search?q=github",
+                r#"{"items":[{"id":"gh","target":"https://github.com/tsauvajon"}],"next_cursor":null,"total":1}"#,
+            ),
+            (
+                "/api/search?q=h&field=id",
+                r#"{"items":[{"id":"hi","target":"https://linkedin.com/in/tsauvajon"},{"id":"gh","target":"https://github.com/tsauvajon"}],"next_cursor":null,"total":2}"#,
+            ),
+            (
+                "/api/search?q=hi&field=target",
+                r#"{"items":[],"next_cursor":null,"total":0}"#,
+            ),
+        ] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK, "{}", uri);
+            let body = test::read_body(resp).await;
+            assert_eq!(*want, String::from_utf8(body.to_vec()).unwrap(), "{}", uri);
+        }
 
-        let _ = panic::take_hook(); // remove the panic hook that mutes panics
+        for uri in &["/api/search", "/api/search?q=gh&field=url"] {
+            let req = test::TestRequest::get().uri(uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::BAD_REQUEST, "{}", uri);
+        }
+    }
 
-        let mut app = test::init_service(App::new().data(db).service(browse)).await;
-        let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    #[actix_rt::test]
+    async fn integration_test_list_urls_pagination() {
+        // the pages follow insertion order, not the order of the ids
+        let db: Vec<(String, String)> = (0..25)
+            .rev()
+            .map(|i| (format!("id{:02}", i), format!("https://{}.com", i)))
+            .collect();
+        let db: Db = Db::new(Data::new(db));
+
+        let mut app = test::init_service(App::new().data(db).service(list_urls)).await;
+
+        let mut ids = Vec::new();
+        let mut pages = 0;
+        let mut uri = "/api/urls?limit=10".to_string();
+        loop {
+            let req = test::TestRequest::get().uri(&uri).to_request();
+            let resp = test::call_service(&mut app, req).await;
+            assert_eq!(resp.status(), StatusCode::OK);
+
+            let body: serde_json::Value =
+                serde_json::from_slice(&test::read_body(resp).await).unwrap();
+            assert_eq!(25, body["total"]);
+            pages += 1;
+
+            for item in body["items"].as_array().unwrap() {
+                ids.push(item["id"].as_str().unwrap().to_string());
+            }
 
-        let body = resp.take_body();
-        let body = body.as_ref().unwrap();
-        assert_eq!(
-            &Body::from("poisoned lock: another task failed inside"),
-            body
-        );
+            match body["next_cursor"].as_str() {
+                Some(cursor) => uri = format!("/api/urls?limit=10&cursor={}", cursor),
+                None => break,
+            }
+        }
+
+        assert_eq!(3, pages);
+        let want: Vec<String> = (0..25).rev().map(|i| format!("id{:02}", i)).collect();
+        assert_eq!(want, ids);
     }
 
-    // try to follow a shortened URL that doesn't exist
     #[actix_rt::test]
-    async fn integration_test_link_miss() {
+    async fn integration_test_list_urls_invalid_cursor() {
+        let db: Db = Db::new(Data::new(HashMap::new()));
+        let mut app = test::init_service(App::new().data(db).service(list_urls)).await;
+
         let req = test::TestRequest::get()
-            .uri("/thislinkdoesntexist")
+            .uri("/api/urls?cursor=%25%25%25")
             .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 
-        let db: Db = Db::new(Data::new(HashMap::new()));
+        // the short URL of the cursor doesn't exist
+        let req = test::TestRequest::get()
+            .uri(&format!("/api/urls?cursor={}", encode_cursor("removed")))
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
 
-        let mut app = test::init_service(App::new().data(db).service(browse)).await;
+        let req = test::TestRequest::get()
+            .uri("/api/urls?limit=0")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_list_urls_include_deleted() {
+        let db: Db = Db::new(Data::new(vec![
+            ("hi".into(), "https://linkedin.com/in/tsauvajon".into()),
+            ("gh".into(), "https://github.com/tsauvajon".into()),
+        ]));
+        db.write().unwrap().delete("gh").unwrap();
+
+        let mut app = test::init_service(App::new().data(db).service(list_urls)).await;
+        let req = test::TestRequest::get().uri("/api/urls").to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+        assert_eq!(
+            serde_json::json!([{"id": "hi", "target": "https://linkedin.com/in/tsauvajon"}]),
+            body["items"]
+        );
+        assert_eq!(1, body["total"]);
+
+        let req = test::TestRequest::get()
+            .uri("/api/urls?include_deleted=true")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        let body: serde_json::Value = serde_json::from_slice(&test::read_body(resp).await).unwrap();
+        let items = body["items"].as_array().unwrap();
+        assert_eq!(2, items.len());
+        assert_eq!("gh", items[1]["id"]);
+        assert!(items[0].get("deleted_at").is_none());
+        assert!(items[1]["deleted_at"].is_u64());
+    }
+
+    #[actix_rt::test]
+    async fn integration_test_patch_shortened_url() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("hi".into(), "https://linkedin.com/in/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+        db.write()
+            .unwrap()
+            .update_entry("hi", |entry| entry.clicks = 3)
+            .unwrap();
+
+        let mut app = test::init_service(App::new().data(db.clone()).service(patch_with_id)).await;
+
+        let req = test::TestRequest::patch()
+            .uri("/hi")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        assert_eq!(
+            db.read().unwrap().get("hi"),
+            Some(&"https://github.com/tsauvajon".to_string())
+        );
+        assert_eq!(3, db.read().unwrap().get_entry("hi").unwrap().clicks);
+
+        let req = test::TestRequest::patch()
+            .uri("/unknown")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
         let mut resp = test::call_service(&mut app, req).await;
         assert_eq!(resp.status(), StatusCode::NOT_FOUND);
 
         let body = resp.take_body();
         let body = body.as_ref().unwrap();
         assert_eq!(&Body::from("not found"), body);
-
-        assert_eq!(resp.headers().get("Location"), None)
+        assert_eq!(db.read().unwrap().get("unknown"), None);
     }
 
     // try to add a link for an already existing short-url
@@ -765,13 +7969,91 @@ mod integration_tests {
             "https://github.com/tsauvajon".into(),
         );
 
-        let db: Db = Db::new(Data::new(db.into()));
+        let db: Db = Db::new(Data::new(db));
         let mut app = test::init_service(App::new().data(db).service(create_with_id)).await;
         let mut resp = test::call_service(&mut app, req).await;
-        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(resp.status(), StatusCode::CONFLICT);
 
         let body = resp.take_body();
         let body = body.as_ref().unwrap();
         assert_eq!(&Body::from("already registered"), body);
     }
+
+    // shorten a long URL that already has a short URL
+    #[actix_rt::test]
+    async fn integration_test_duplicate_target() {
+        let mut db: HashMap<String, String> = HashMap::new();
+        db.insert("gh".into(), "https://github.com/tsauvajon".into());
+        let db: Db = Db::new(Data::new(db));
+        let mut app = test::init_service(
+            App::new()
+                .data(db.clone())
+                .service(shorten)
+                .service(create_random)
+                .service(create_with_id),
+        )
+        .await;
+
+        let req = test::TestRequest::post()
+            .uri("/")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        assert_eq!("/gh already maps to https://github.com/tsauvajon", body);
+
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{"target": "https://github.com/tsauvajon"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        let got: ShortenResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("gh", got.id);
+        assert_eq!(1, db.read().unwrap().len());
+
+        // short URLs with an id are created anyway
+        let req = test::TestRequest::post()
+            .uri("/github")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = test::read_body(resp).await;
+        assert_eq!(
+            "/github now redirects to https://github.com/tsauvajon",
+            body
+        );
+
+        let req = test::TestRequest::post()
+            .uri("/api/shorten")
+            .header("Content-Type", "application/json")
+            .set_payload(r#"{"id": "hub", "target": "https://github.com/tsauvajon"}"#)
+            .to_request();
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::CREATED);
+        let body = test::read_body(resp).await;
+        let got: ShortenResponse = serde_json::from_slice(&body).unwrap();
+        assert_eq!("hub", got.id);
+
+        assert_eq!(3, db.read().unwrap().len());
+    }
+
+    // fail to persist the database, e.g. because the disk is full
+    #[actix_rt::test]
+    async fn integration_test_persistence_error() {
+        let req = test::TestRequest::post()
+            .uri("/hello")
+            .set_payload("https://github.com/tsauvajon")
+            .to_request();
+
+        let path = std::env::temp_dir().join("does/not/exist/database.yml");
+        let db: Db = Db::new(Data::new(HashMap::new()).with_persistence(path));
+        let mut app = test::init_service(App::new().data(db).service(create_with_id)).await;
+        let resp = test::call_service(&mut app, req).await;
+        assert_eq!(resp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    }
 }
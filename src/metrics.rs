@@ -0,0 +1,198 @@
+//! Prometheus metrics for the goto API.
+//!
+//! Every request going through the [`Instrument`] middleware increments
+//! `goto_requests_total` and records its duration in
+//! `goto_request_duration_seconds`. The number of entries in the database is
+//! exposed as `goto_db_entries_total`.
+
+use actix_web::dev::{Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::{error, get, web, Error, HttpResponse, Responder};
+use futures::future::{ok, LocalBoxFuture, Ready};
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::Db;
+
+/// Metrics holds the Prometheus collectors of a goto instance.
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests_total: IntCounterVec,
+    request_duration: HistogramVec,
+    db_entries: IntGauge,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let requests_total = IntCounterVec::new(
+            Opts::new("goto_requests_total", "Number of HTTP requests handled."),
+            &["method", "status"],
+        )
+        .expect("create requests counter");
+
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "goto_request_duration_seconds",
+                "Time spent handling HTTP requests.",
+            ),
+            &["handler"],
+        )
+        .expect("create request duration histogram");
+
+        let db_entries = IntGauge::new(
+            "goto_db_entries_total",
+            "Number of short URLs in the database.",
+        )
+        .expect("create database entries gauge");
+
+        registry
+            .register(Box::new(requests_total.clone()))
+            .expect("register requests counter");
+        registry
+            .register(Box::new(request_duration.clone()))
+            .expect("register request duration histogram");
+        registry
+            .register(Box::new(db_entries.clone()))
+            .expect("register database entries gauge");
+
+        Metrics {
+            registry,
+            requests_total,
+            request_duration,
+            db_entries,
+        }
+    }
+
+    fn observe(&self, method: &str, status: u16, handler: &str, duration: Duration) {
+        self.requests_total
+            .with_label_values(&[method, &status.to_string()])
+            .inc();
+        self.request_duration
+            .with_label_values(&[handler])
+            .observe(duration.as_secs_f64());
+    }
+
+    /// render encodes all the metrics in the Prometheus text exposition format.
+    fn render(&self) -> Result<String, String> {
+        let mut buf = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .map_err(|err| format!("encode metrics: {}", err))?;
+
+        String::from_utf8(buf).map_err(|err| format!("encode metrics: {}", err))
+    }
+}
+
+/// expose renders the Prometheus metrics of this instance.
+#[get("/metrics")]
+pub async fn expose(metrics: web::Data<Metrics>, db: web::Data<Db>) -> impl Responder {
+    match db.read() {
+        Ok(db) => metrics.db_entries.set(db.len() as i64),
+        Err(err) => return Err(error::ErrorInternalServerError(err.to_string())),
+    }
+
+    let body = metrics.render().map_err(error::ErrorInternalServerError)?;
+    Ok(HttpResponse::Ok()
+        .content_type(TextEncoder::new().format_type())
+        .body(body))
+}
+
+/// Instrument is a middleware recording the count and the duration of every
+/// request it wraps.
+pub struct Instrument {
+    metrics: Metrics,
+}
+
+impl Instrument {
+    pub fn new(metrics: Metrics) -> Self {
+        Instrument { metrics }
+    }
+}
+
+impl<S, B> Transform<S> for Instrument
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = InstrumentMiddleware<S>;
+    type Future = Ready<Result<Self::Transform, Self::InitError>>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        ok(InstrumentMiddleware {
+            service,
+            metrics: self.metrics.clone(),
+        })
+    }
+}
+
+pub struct InstrumentMiddleware<S> {
+    service: S,
+    metrics: Metrics,
+}
+
+impl<S, B> Service for InstrumentMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: 'static,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = LocalBoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.service.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        let start = Instant::now();
+        let method = req.method().to_string();
+        let handler = req
+            .match_pattern()
+            .unwrap_or_else(|| "unmatched".to_string());
+        let metrics = self.metrics.clone();
+
+        let fut = self.service.call(req);
+        Box::pin(async move {
+            let res = fut.await?;
+            metrics.observe(&method, res.status().as_u16(), &handler, start.elapsed());
+            Ok(res)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::new();
+        metrics.observe("GET", 302, "/{id}", Duration::from_millis(3));
+        metrics.db_entries.set(12);
+
+        let got = metrics.render().unwrap();
+        assert!(
+            got.contains("goto_requests_total{method=\"GET\",status=\"302\"} 1"),
+            "{}",
+            got
+        );
+        assert!(
+            got.contains("goto_request_duration_seconds_count{handler=\"/{id}\"} 1"),
+            "{}",
+            got
+        );
+        assert!(got.contains("goto_db_entries_total 12"), "{}", got);
+    }
+}
@@ -0,0 +1,97 @@
+//! Search over the short URLs of the goto API, for `/api/search`.
+
+use crate::Entry;
+use indexmap::IndexMap;
+
+/// SearchField is what a search pattern is matched against.
+#[derive(serde::Deserialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SearchField {
+    Id,
+    Target,
+    Any,
+}
+
+impl Default for SearchField {
+    fn default() -> Self {
+        SearchField::Any
+    }
+}
+
+/// search returns the short URLs whose id or target, depending on `field`,
+/// contains `pattern`, in the order of `data`.
+pub fn search<'a>(
+    data: &'a IndexMap<String, Entry>,
+    pattern: &'a str,
+    field: SearchField,
+) -> impl Iterator<Item = (&'a String, &'a Entry)> {
+    data.iter().filter(move |(id, entry)| {
+        let id_matches = || id.contains(pattern);
+        let target_matches = || entry.target.contains(pattern);
+        match field {
+            SearchField::Id => id_matches(),
+            SearchField::Target => target_matches(),
+            SearchField::Any => id_matches() || target_matches(),
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn seed() -> IndexMap<String, Entry> {
+        vec![
+            ("gh", "https://github.com/tsauvajon"),
+            ("gh-goto", "https://github.com/tsauvajon/goto"),
+            ("li", "https://linkedin.com/in/tsauvajon"),
+            ("blog", "https://blog.example.com"),
+            ("docs", "https://docs.example.com/gh"),
+            ("rust", "https://www.rust-lang.org"),
+            ("crates", "https://crates.io"),
+            ("actix", "https://actix.rs"),
+            ("yew", "https://yew.rs"),
+            ("tokio", "https://tokio.rs"),
+        ]
+        .into_iter()
+        .map(|(id, target)| (id.to_string(), Entry::from(target.to_string())))
+        .collect()
+    }
+
+    fn ids(data: &IndexMap<String, Entry>, pattern: &str, field: SearchField) -> Vec<String> {
+        search(data, pattern, field)
+            .map(|(id, _)| id.clone())
+            .collect()
+    }
+
+    #[test]
+    fn test_search() {
+        let data = seed();
+        assert_eq!(10, data.len());
+
+        assert_eq!(vec!["gh", "gh-goto"], ids(&data, "gh", SearchField::Id));
+        assert_eq!(
+            vec!["gh", "gh-goto"],
+            ids(&data, "github.com", SearchField::Target)
+        );
+        assert_eq!(
+            vec!["gh", "gh-goto", "docs"],
+            ids(&data, "gh", SearchField::Any)
+        );
+        assert_eq!(
+            vec!["actix", "yew", "tokio"],
+            ids(&data, ".rs", SearchField::Target)
+        );
+        assert!(ids(&data, "gitlab", SearchField::Any).is_empty());
+        // an empty pattern matches everything
+        assert_eq!(10, ids(&data, "", SearchField::Id).len());
+    }
+
+    #[test]
+    fn test_search_field() {
+        let field: SearchField = serde_json::from_str(r#""target""#).unwrap();
+        assert_eq!(SearchField::Target, field);
+        assert_eq!(SearchField::Any, SearchField::default());
+        assert!(serde_json::from_str::<SearchField>(r#""url""#).is_err());
+    }
+}
@@ -0,0 +1,228 @@
+//! Where the goto API stores its short URLs.
+//!
+//! [`Data`](crate::Data) keeps every short URL in memory, and hands each
+//! change to a [`StorageBackend`] to make it last. [`InMemoryBackend`] keeps
+//! nothing, and [`FileBackend`] rewrites a YAML or JSON file, or appends to an
+//! NDJSON file.
+
+use crate::{ndjson_tombstone, serialise_ndjson, DataFormat, Entry};
+use indexmap::IndexMap;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// StorageBackend persists the short URLs. Backends are given every short
+/// URL along with each change, so that those which can only store them all at
+/// once, like files, only need to implement `load` and `save`.
+pub trait StorageBackend: Send + Sync {
+    /// load reads the stored short URLs, in their stored order.
+    fn load(&self) -> io::Result<IndexMap<String, Entry>>;
+
+    /// save replaces the stored short URLs by `data`.
+    fn save(&self, data: &IndexMap<String, Entry>) -> io::Result<()>;
+
+    /// persist stores the short URL `id` of `data`, after it was created or
+    /// changed.
+    fn persist(&self, data: &IndexMap<String, Entry>, _id: &str) -> io::Result<()> {
+        self.save(data)
+    }
+
+    /// remove forgets the short URL `id`, which is no longer in `data`.
+    fn remove(&self, data: &IndexMap<String, Entry>, _id: &str) -> io::Result<()> {
+        self.save(data)
+    }
+
+    /// path returns the file the short URLs are stored in, if any.
+    fn path(&self) -> Option<&Path> {
+        None
+    }
+
+    /// size returns the number of bytes the short URLs take in storage, or 0
+    /// if it is unknown.
+    fn size(&self) -> io::Result<u64> {
+        match self.path() {
+            Some(path) => Ok(std::fs::metadata(path)?.len()),
+            None => Ok(0),
+        }
+    }
+}
+
+/// InMemoryBackend doesn't store anything: the short URLs are lost when goto
+/// stops.
+pub struct InMemoryBackend;
+
+impl StorageBackend for InMemoryBackend {
+    fn load(&self) -> io::Result<IndexMap<String, Entry>> {
+        Ok(IndexMap::new())
+    }
+
+    fn save(&self, _data: &IndexMap<String, Entry>) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// FileBackend stores the short URLs in a file. YAML and JSON files are
+/// rewritten entirely on every change, while NDJSON files get a line per
+/// change appended, and are only rewritten by `save`, e.g. when compacting.
+#[derive(Clone)]
+pub struct FileBackend {
+    path: PathBuf,
+    format: DataFormat,
+}
+
+impl FileBackend {
+    pub fn new(path: PathBuf, format: DataFormat) -> Self {
+        FileBackend { path, format }
+    }
+
+    /// append writes `lines` at the end of the database file.
+    fn append(&self, lines: &str) -> io::Result<()> {
+        let mut file = OpenOptions::new()
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        file.write_all(lines.as_bytes())?;
+        file.sync_all()
+    }
+}
+
+impl StorageBackend for FileBackend {
+    /// load parses the file, which is empty for a new database.
+    fn load(&self) -> io::Result<IndexMap<String, Entry>> {
+        let contents = std::fs::read_to_string(&self.path)?;
+        self.format
+            .parse(&contents)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    /// save writes the short URLs to a temporary file, and then renames it
+    /// to the database file. Renaming is atomic, so a crash can never leave a
+    /// partially written database behind.
+    fn save(&self, data: &IndexMap<String, Entry>) -> io::Result<()> {
+        let mut tmp_path = self.path.clone().into_os_string();
+        tmp_path.push(".tmp");
+
+        let mut file = File::create(&tmp_path)?;
+        file.write_all(self.format.serialise(data).as_bytes())?;
+        file.sync_all()?;
+
+        std::fs::rename(&tmp_path, &self.path)
+    }
+
+    /// persist appends the short URL `id` to NDJSON files, and rewrites the
+    /// other files.
+    fn persist(&self, data: &IndexMap<String, Entry>, id: &str) -> io::Result<()> {
+        match (self.format, data.get_key_value(id)) {
+            (DataFormat::Ndjson, Some(entry)) => {
+                self.append(&serialise_ndjson(std::iter::once(entry)))
+            }
+            _ => self.save(data),
+        }
+    }
+
+    /// remove appends a `deleted` line for `id` to NDJSON files, and rewrites
+    /// the other files.
+    fn remove(&self, data: &IndexMap<String, Entry>, id: &str) -> io::Result<()> {
+        match self.format {
+            DataFormat::Ndjson => self.append(&ndjson_tombstone(id)),
+            _ => self.save(data),
+        }
+    }
+
+    fn path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env::temp_dir;
+
+    fn seed() -> IndexMap<String, Entry> {
+        vec![("hi", "https://hello.world"), ("gh", "https://github.com")]
+            .into_iter()
+            .map(|(id, target)| (id.to_string(), Entry::from(target.to_string())))
+            .collect()
+    }
+
+    #[test]
+    fn test_in_memory_backend() {
+        let backend = InMemoryBackend;
+        backend.persist(&seed(), "hi").unwrap();
+        assert!(backend.load().unwrap().is_empty());
+        assert_eq!(None, backend.path());
+        assert_eq!(0, backend.size().unwrap());
+    }
+
+    #[test]
+    fn test_file_backend() {
+        for (name, format) in &[
+            ("test_file_backend.yml", DataFormat::Yaml),
+            ("test_file_backend.json", DataFormat::Json),
+            ("test_file_backend.ndjson", DataFormat::Ndjson),
+        ] {
+            let path = temp_dir().join(name);
+            File::create(&path).unwrap();
+            let backend = FileBackend::new(path.clone(), *format);
+            assert!(backend.load().unwrap().is_empty());
+
+            let mut data = seed();
+            backend.save(&data).unwrap();
+            data["hi"].clicks = 1;
+            backend.persist(&data, "hi").unwrap();
+            let got = backend.load().unwrap();
+            assert_eq!(
+                data.keys().collect::<Vec<_>>(),
+                got.keys().collect::<Vec<_>>()
+            );
+            assert_eq!(data["hi"], got["hi"]);
+
+            data.shift_remove("hi");
+            backend.remove(&data, "hi").unwrap();
+            assert_eq!(data, backend.load().unwrap());
+
+            assert_eq!(Some(path.as_path()), backend.path());
+            assert_eq!(
+                std::fs::metadata(&path).unwrap().len(),
+                backend.size().unwrap()
+            );
+            let mut tmp_path = path.into_os_string();
+            tmp_path.push(".tmp");
+            assert!(!Path::new(&tmp_path).exists());
+        }
+    }
+
+    #[test]
+    fn test_file_backend_ndjson_appends() {
+        let path = temp_dir().join("test_file_backend_appends.ndjson");
+        let backend = FileBackend::new(path.clone(), DataFormat::Ndjson);
+        let lines = || std::fs::read_to_string(&path).unwrap().lines().count();
+
+        let mut data = seed();
+        backend.save(&data).unwrap();
+        assert_eq!(2, lines());
+
+        data["hi"].clicks = 1;
+        backend.persist(&data, "hi").unwrap();
+        data.shift_remove("gh");
+        backend.remove(&data, "gh").unwrap();
+        assert_eq!(4, lines());
+        assert_eq!(data, backend.load().unwrap());
+
+        // saving compacts the file
+        backend.save(&data).unwrap();
+        assert_eq!(1, lines());
+        assert_eq!(data, backend.load().unwrap());
+    }
+
+    #[test]
+    fn test_file_backend_bad_data() {
+        let path = temp_dir().join("test_file_backend_bad_data.json");
+        std::fs::write(&path, "hello: \"http://world\"\n").unwrap();
+
+        let err = FileBackend::new(path, DataFormat::Json).load().unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidData, err.kind());
+        assert!(err.to_string().contains("expected value"), "{}", err);
+    }
+}
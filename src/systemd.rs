@@ -0,0 +1,136 @@
+//! systemd integration for the goto API.
+//!
+//! With socket activation, systemd opens the listening socket itself and
+//! passes it to goto as a file descriptor, following
+//! <https://www.freedesktop.org/software/systemd/man/sd_listen_fds.html>.
+//! goto can also tell systemd when it is ready to serve requests, following
+//! <https://www.freedesktop.org/software/systemd/man/sd_notify.html>.
+
+use std::net::TcpListener;
+use std::os::unix::io::{FromRawFd, RawFd};
+use std::os::unix::net::UnixDatagram;
+
+/// First file descriptor passed by systemd, after stdin, stdout and stderr.
+const LISTEN_FDS_START: RawFd = 3;
+
+/// listen_fd returns the file descriptor of the socket passed by systemd, if
+/// any.
+pub fn listen_fd() -> Option<RawFd> {
+    listen_fd_from(
+        std::env::var("LISTEN_PID").ok().as_deref(),
+        std::env::var("LISTEN_FDS").ok().as_deref(),
+        std::process::id(),
+    )
+}
+
+/// listen_fd_from returns the first file descriptor passed by systemd, if
+/// the sockets were passed to this process.
+fn listen_fd_from(listen_pid: Option<&str>, listen_fds: Option<&str>, pid: u32) -> Option<RawFd> {
+    if listen_pid?.parse::<u32>().ok()? != pid {
+        return None;
+    }
+
+    match listen_fds?.parse::<RawFd>().ok()? {
+        0 => None,
+        _ => Some(LISTEN_FDS_START),
+    }
+}
+
+/// listener takes ownership of the listening socket `fd`.
+pub fn listener(fd: RawFd) -> std::io::Result<TcpListener> {
+    if fd < 0 {
+        return Err(std::io::Error::new(
+            std::io::ErrorKind::InvalidInput,
+            format!("invalid file descriptor {}", fd),
+        ));
+    }
+
+    // Safety: the file descriptor was handed over to goto, by systemd or
+    // with --bind-fd, and nothing else uses it.
+    let listener = unsafe { TcpListener::from_raw_fd(fd) };
+    // fails if fd isn't a socket
+    listener.local_addr()?;
+    Ok(listener)
+}
+
+/// notify sends `state` to systemd, e.g. `READY=1`. It returns false if goto
+/// wasn't started by systemd.
+pub fn notify(state: &str) -> std::io::Result<bool> {
+    match std::env::var("NOTIFY_SOCKET") {
+        Ok(path) => send(&path, state).map(|_| true),
+        Err(_) => Ok(false),
+    }
+}
+
+/// send sends `state` to the socket at `path`, which is in the abstract
+/// namespace if it starts with `@`.
+fn send(path: &str, state: &str) -> std::io::Result<()> {
+    let socket = UnixDatagram::unbound()?;
+    match path.strip_prefix('@') {
+        Some(name) => send_abstract(&socket, name, state),
+        None => socket.send_to(state.as_bytes(), path).map(|_| ()),
+    }
+}
+
+/// send_abstract sends `state` to a socket of the abstract namespace.
+#[cfg(target_os = "linux")]
+fn send_abstract(socket: &UnixDatagram, name: &str, state: &str) -> std::io::Result<()> {
+    use std::os::linux::net::SocketAddrExt;
+    use std::os::unix::net::SocketAddr;
+
+    let addr = SocketAddr::from_abstract_name(name)?;
+    socket.send_to_addr(state.as_bytes(), &addr)?;
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+fn send_abstract(_socket: &UnixDatagram, name: &str, _state: &str) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        format!("abstract socket @{} is only supported on Linux", name),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::io::IntoRawFd;
+
+    #[test]
+    fn test_listen_fd_from() {
+        assert_eq!(Some(3), listen_fd_from(Some("42"), Some("1"), 42));
+        assert_eq!(Some(3), listen_fd_from(Some("42"), Some("2"), 42));
+        // the sockets were passed to another process
+        assert_eq!(None, listen_fd_from(Some("41"), Some("1"), 42));
+        assert_eq!(None, listen_fd_from(Some("42"), Some("0"), 42));
+        assert_eq!(None, listen_fd_from(None, Some("1"), 42));
+        assert_eq!(None, listen_fd_from(Some("42"), None, 42));
+        assert_eq!(None, listen_fd_from(Some("nope"), Some("1"), 42));
+    }
+
+    #[test]
+    fn test_listener() {
+        let fd = TcpListener::bind("127.0.0.1:0").unwrap().into_raw_fd();
+        let got = listener(fd).unwrap();
+        assert!(got.local_addr().unwrap().ip().is_loopback());
+
+        let err = listener(-1).unwrap_err();
+        assert_eq!("invalid file descriptor -1", err.to_string());
+    }
+
+    #[test]
+    fn test_send() {
+        let mut path = std::env::temp_dir();
+        path.push("goto_test_systemd_send.sock");
+        let _ = std::fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path).unwrap();
+
+        send(path.to_str().unwrap(), "READY=1").unwrap();
+
+        let mut buf = [0; 16];
+        let len = socket.recv(&mut buf).unwrap();
+        assert_eq!(b"READY=1", &buf[..len]);
+
+        assert!(send("/does/not/exist.sock", "READY=1").is_err());
+    }
+}